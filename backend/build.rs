@@ -0,0 +1,19 @@
+use vergen_gitcl::{BuildBuilder, CargoBuilder, Emitter, GitclBuilder, RustcBuilder};
+
+// Embebe versión, commit y toolchain para GET /api/v1/info. Fuera de un
+// repositorio git (p. ej. en la imagen Docker) los valores de git quedan por
+// defecto en lugar de fallar la compilación.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let build = BuildBuilder::default().build_timestamp(true).build()?;
+    let cargo = CargoBuilder::default().features(true).build()?;
+    let git = GitclBuilder::default().sha(false).dirty(false).build()?;
+    let rustc = RustcBuilder::default().semver(true).build()?;
+
+    Emitter::default()
+        .add_instructions(&build)?
+        .add_instructions(&cargo)?
+        .add_instructions(&git)?
+        .add_instructions(&rustc)?
+        .emit()?;
+    Ok(())
+}
@@ -0,0 +1,2 @@
+-- Add optional Markdown description to todos
+ALTER TABLE todos ADD COLUMN IF NOT EXISTS description TEXT;
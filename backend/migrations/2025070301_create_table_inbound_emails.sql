@@ -0,0 +1,6 @@
+-- Track inbound emails already turned into todos so provider retries are ignored
+CREATE TABLE IF NOT EXISTS inbound_emails (
+    message_id TEXT PRIMARY KEY,
+    todo_id UUID REFERENCES todos(id) ON DELETE SET NULL,
+    received_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
@@ -0,0 +1,4 @@
+-- Identifier of the todo in the system it was imported from, used to dedupe re-imports
+ALTER TABLE todos ADD COLUMN IF NOT EXISTS external_ref TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_todos_external_ref ON todos(external_ref);
@@ -0,0 +1,2 @@
+-- Prefix index for case-insensitive title suggestions
+CREATE INDEX IF NOT EXISTS idx_todos_title_prefix ON todos (lower(title) text_pattern_ops);
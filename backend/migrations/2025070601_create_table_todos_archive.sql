@@ -0,0 +1,17 @@
+-- Completed todos moved out of the live table after ARCHIVE_AFTER_DAYS
+CREATE TABLE IF NOT EXISTS todos_archive (
+    id UUID PRIMARY KEY,
+    title VARCHAR(255) NOT NULL,
+    description TEXT,
+    completed BOOLEAN NOT NULL,
+    due_date TIMESTAMP WITH TIME ZONE,
+    external_ref TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    archived_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_todos_archive_archived_at ON todos_archive(archived_at);
+
+-- Candidates for archival are looked up by completion state and age
+CREATE INDEX IF NOT EXISTS idx_todos_completed_updated_at ON todos(updated_at) WHERE completed;
@@ -0,0 +1,35 @@
+-- Change log behind GET /api/v1/todos/changes, filled by a trigger so that
+-- every write path (API, imports, archival) is recorded
+CREATE TABLE IF NOT EXISTS todo_changes (
+    seq BIGSERIAL PRIMARY KEY,
+    todo_id UUID NOT NULL,
+    op TEXT NOT NULL CHECK (op IN ('created', 'updated', 'deleted')),
+    changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_todo_changes_changed_at ON todo_changes(changed_at);
+
+CREATE OR REPLACE FUNCTION record_todo_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    -- Serializa a los escritores hasta el commit para que seq crezca en orden
+    -- de commit: un lector nunca ve seq 11 antes de que aparezca seq 10
+    PERFORM pg_advisory_xact_lock(hashtext('todo_changes'));
+
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO todo_changes (todo_id, op) VALUES (OLD.id, 'deleted');
+    ELSIF TG_OP = 'INSERT' THEN
+        INSERT INTO todo_changes (todo_id, op) VALUES (NEW.id, 'created');
+    ELSE
+        INSERT INTO todo_changes (todo_id, op) VALUES (NEW.id, 'updated');
+    END IF;
+
+    PERFORM pg_notify('todo_changes', '');
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER record_todos_change
+    AFTER INSERT OR UPDATE OR DELETE ON todos
+    FOR EACH ROW
+    EXECUTE FUNCTION record_todo_change();
@@ -0,0 +1,29 @@
+-- The database maintains updated_at, so no UPDATE has to remember to set it,
+-- and keeps created_at as it was inserted
+CREATE OR REPLACE FUNCTION set_updated_at()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at = NOW();
+    NEW.created_at = OLD.created_at;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+DROP TRIGGER IF EXISTS update_todos_updated_at ON todos;
+DROP FUNCTION IF EXISTS update_updated_at_column();
+
+CREATE TRIGGER set_todos_updated_at
+    BEFORE UPDATE ON todos
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+-- Inserts that omit the timestamps get NOW(); inserts that carry them
+-- (restores from the archive or a backup) keep them
+UPDATE todos SET created_at = COALESCE(created_at, NOW()), updated_at = COALESCE(updated_at, created_at, NOW())
+WHERE created_at IS NULL OR updated_at IS NULL;
+
+ALTER TABLE todos
+    ALTER COLUMN created_at SET DEFAULT NOW(),
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN updated_at SET DEFAULT NOW(),
+    ALTER COLUMN updated_at SET NOT NULL;
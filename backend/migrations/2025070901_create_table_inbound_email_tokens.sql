@@ -0,0 +1,9 @@
+-- Tokens of signed inbound webhooks already accepted, so a captured request
+-- cannot be replayed. Rows older than the accepted timestamp window are
+-- purged as new deliveries come in, since those are rejected anyway
+CREATE TABLE IF NOT EXISTS inbound_email_tokens (
+    token TEXT PRIMARY KEY,
+    signed_at TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_inbound_email_tokens_signed_at ON inbound_email_tokens(signed_at);
@@ -0,0 +1,2 @@
+-- Imports skip entries whose external_ref is already archived
+CREATE INDEX IF NOT EXISTS idx_todos_archive_external_ref ON todos_archive(external_ref) WHERE external_ref IS NOT NULL;
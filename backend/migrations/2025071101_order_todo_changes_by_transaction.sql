@@ -0,0 +1,25 @@
+-- The change feed no longer serializes writers: each change records the
+-- transaction that wrote it, and readers only return changes of transactions
+-- older than every one still running (pg_snapshot_xmin), ordered by
+-- transaction and then seq. A transaction that commits later always has a
+-- position after whatever was already returned, so no change is skipped.
+ALTER TABLE todo_changes
+    ADD COLUMN IF NOT EXISTS txid BIGINT NOT NULL DEFAULT pg_current_xact_id()::text::bigint;
+
+CREATE INDEX IF NOT EXISTS idx_todo_changes_txid_seq ON todo_changes(txid, seq);
+
+CREATE OR REPLACE FUNCTION record_todo_change()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO todo_changes (todo_id, op) VALUES (OLD.id, 'deleted');
+    ELSIF TG_OP = 'INSERT' THEN
+        INSERT INTO todo_changes (todo_id, op) VALUES (NEW.id, 'created');
+    ELSE
+        INSERT INTO todo_changes (todo_id, op) VALUES (NEW.id, 'updated');
+    END IF;
+
+    PERFORM pg_notify('todo_changes', '');
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
@@ -0,0 +1,509 @@
+//! Optional access log on disk, one line per request, for installs whose
+//! stdout is not collected.
+//!
+//! Lines go through a `tracing_appender` non-blocking writer: a background
+//! thread does the file IO, so a slow disk never holds up a request (past
+//! its buffer of 128k lines, new lines are dropped instead). Each line is a
+//! single write, and files are only rotated between writes, so lines are
+//! never split or interleaved across files.
+
+use axum::{
+    body::HttpBody,
+    extract::{Request, State},
+    http::{header::{REFERER, USER_AGENT}, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{Builder, Rotation},
+};
+use crate::{client_ip::ClientIp, config::Config, telemetry::RequestId};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessLogFormat {
+    /// Apache combined log format, followed by the latency in seconds and the
+    /// request id
+    Combined,
+    /// One JSON object per line
+    Json,
+}
+
+impl FromStr for AccessLogFormat {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "combined" => Ok(AccessLogFormat::Combined),
+            "json" => Ok(AccessLogFormat::Json),
+            _ => Err("ACCESS_LOG_FORMAT must be combined or json"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessLogRotation {
+    /// A new `<file>.YYYY-MM-DD` every day (UTC)
+    Daily,
+    /// A new `<file>.YYYY-MM-DD-HH` every hour (UTC)
+    Hourly,
+    /// `<file>` is renamed to `<file>.1` (and older ones shifted) once it
+    /// would exceed `ACCESS_LOG_MAX_SIZE_MB`
+    Size,
+}
+
+impl FromStr for AccessLogRotation {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "daily" => Ok(AccessLogRotation::Daily),
+            "hourly" => Ok(AccessLogRotation::Hourly),
+            "size" => Ok(AccessLogRotation::Size),
+            _ => Err("ACCESS_LOG_ROTATION must be daily, hourly or size"),
+        }
+    }
+}
+
+pub struct AccessLog {
+    writer: NonBlocking,
+    format: AccessLogFormat,
+}
+
+/// Opens the access log when `ACCESS_LOG_PATH` is set. The guard flushes the
+/// pending lines when dropped, so it must live until the server stops.
+pub fn init(config: &Config) -> io::Result<Option<(Arc<AccessLog>, WorkerGuard)>> {
+    let Some(path) = config.access_log_path.as_deref().map(Path::new) else {
+        return Ok(None);
+    };
+    let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "ACCESS_LOG_PATH must name a file"))?;
+    fs::create_dir_all(directory)?;
+
+    let max_files = config.access_log_max_files;
+    let (writer, guard) = match config.access_log_rotation {
+        AccessLogRotation::Size => {
+            let max_bytes = config.access_log_max_size_mb.saturating_mul(1024 * 1024);
+            tracing_appender::non_blocking(SizeRotatingFile::open(path.to_path_buf(), max_bytes, max_files)?)
+        }
+        time_based => {
+            let rotation = match time_based {
+                AccessLogRotation::Hourly => Rotation::HOURLY,
+                _ => Rotation::DAILY,
+            };
+            let appender = Builder::new()
+                .rotation(rotation)
+                .filename_prefix(file_name)
+                .max_log_files(max_files)
+                .build(directory)
+                .map_err(io::Error::other)?;
+            tracing_appender::non_blocking(appender)
+        }
+    };
+
+    Ok(Some((Arc::new(AccessLog { writer, format: config.access_log_format }), guard)))
+}
+
+/// Writes a line once the response headers are ready; the latency does not
+/// include streaming the body.
+pub async fn log_request(State(log): State<Arc<AccessLog>>, request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let timestamp = Utc::now();
+    let client = request.extensions().get::<ClientIp>().map(|client| client.0.to_string());
+    let method = request.method().to_string();
+    let target = request
+        .uri()
+        .path_and_query()
+        .map_or_else(|| request.uri().path().to_string(), |target| target.to_string());
+    let version = format!("{:?}", request.version());
+    let referer = header(request.headers(), REFERER.as_str());
+    let user_agent = header(request.headers(), USER_AGENT.as_str());
+    let request_id = request.extensions().get::<RequestId>().map(|id| id.0.clone());
+
+    let response = next.run(request).await;
+
+    let entry = Entry {
+        timestamp,
+        client,
+        method,
+        target,
+        version,
+        status: response.status().as_u16(),
+        bytes: response.body().size_hint().exact(),
+        referer,
+        user_agent,
+        latency: started.elapsed().as_secs_f64(),
+        request_id,
+    };
+    let line = format_line(log.format, &entry);
+    // El escritor no bloquea; si su cola está llena la línea se pierde
+    let _ = log.writer.clone().write_all(line.as_bytes());
+
+    response
+}
+
+/// What is logged about one request.
+struct Entry {
+    timestamp: DateTime<Utc>,
+    client: Option<String>,
+    method: String,
+    target: String,
+    version: String,
+    status: u16,
+    bytes: Option<u64>,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    /// Seconds until the response headers were ready
+    latency: f64,
+    request_id: Option<String>,
+}
+
+/// The line for `entry`, newline included.
+fn format_line(format: AccessLogFormat, entry: &Entry) -> String {
+    match format {
+        AccessLogFormat::Combined => format!(
+            "{} - - [{}] {} {} {} {} {} {:.6} {}\n",
+            entry.client.as_deref().unwrap_or("-"),
+            entry.timestamp.format("%d/%b/%Y:%H:%M:%S %z"),
+            quoted(&format!("{} {} {}", entry.method, entry.target, entry.version)),
+            entry.status,
+            entry.bytes.map_or_else(|| "-".to_string(), |bytes| bytes.to_string()),
+            entry.referer.as_deref().map_or_else(|| "\"-\"".to_string(), quoted),
+            entry.user_agent.as_deref().map_or_else(|| "\"-\"".to_string(), quoted),
+            entry.latency,
+            entry.request_id.as_deref().map_or_else(|| "\"-\"".to_string(), quoted),
+        ),
+        AccessLogFormat::Json => {
+            let mut line = serde_json::json!({
+                "timestamp": entry.timestamp,
+                "client_ip": entry.client,
+                "method": entry.method,
+                "path": entry.target,
+                "protocol": entry.version,
+                "status": entry.status,
+                "latency_ms": entry.latency * 1000.0,
+                "bytes": entry.bytes,
+                "referer": entry.referer,
+                "user_agent": entry.user_agent,
+                "request_id": entry.request_id,
+            })
+            .to_string();
+            line.push('\n');
+            line
+        }
+    }
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+}
+
+/// Quotes a combined-log field, escaping quotes, backslashes and control
+/// characters so a line can always be split back into its fields.
+fn quoted(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                result.push('\\');
+                result.push(c);
+            }
+            c if c.is_control() => result.push_str(&format!("\\x{:02x}", u32::from(c))),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Appends to `path`, keeping up to `max_files` rotated copies as
+/// `path.1` (newest) to `path.<max_files>`.
+struct SizeRotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRotatingFile {
+    fn open(path: PathBuf, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(SizeRotatingFile { path, max_bytes, max_files, file, size })
+    }
+
+    fn numbered(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        name.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        for index in (1..self.max_files).rev() {
+            let from = self.numbered(index);
+            if from.exists() {
+                fs::rename(&from, self.numbered(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.numbered(1))?;
+
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingFile {
+    /// Each call is one whole line from the non-blocking worker, so it is
+    /// written entirely to one file.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Method, StatusCode};
+    use chrono::TimeZone;
+    use std::collections::HashSet;
+    use tower::ServiceExt;
+    use crate::testing;
+
+    fn directory() -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("ha_todo_access_log_{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// The lines of `path` and its rotated copies, oldest first.
+    fn lines(path: &Path, max_files: usize) -> Vec<String> {
+        let mut files = (1..=max_files)
+            .rev()
+            .map(|index| PathBuf::from(format!("{}.{}", path.display(), index)))
+            .filter(|file| file.exists())
+            .collect::<Vec<_>>();
+        files.push(path.to_path_buf());
+        files
+            .iter()
+            .flat_map(|file| fs::read_to_string(file).unwrap().lines().map(str::to_string).collect::<Vec<_>>())
+            .collect()
+    }
+
+    fn entry() -> Entry {
+        Entry {
+            timestamp: Utc.with_ymd_and_hms(2030, 6, 1, 9, 5, 7).unwrap(),
+            client: Some("192.0.2.10".to_string()),
+            method: "GET".to_string(),
+            target: "/api/v1/todos?limit=5".to_string(),
+            version: "HTTP/1.1".to_string(),
+            status: 200,
+            bytes: Some(1234),
+            referer: Some("https://example.com/".to_string()),
+            user_agent: Some("curl/8.5.0".to_string()),
+            latency: 0.25,
+            request_id: Some("req-1".to_string()),
+        }
+    }
+
+    #[test]
+    fn rotates_by_size_keeping_whole_lines_and_max_files() {
+        let path = directory().join("access.log");
+        let mut file = SizeRotatingFile::open(path.clone(), 100, 3).unwrap();
+
+        for number in 0..50 {
+            file.write_all(format!("line {:03}\n", number).as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        // Caben once líneas de 9 bytes en 100; el primer fichero (0 a 10) ya se descartó
+        for index in 1..=3 {
+            let rotated = fs::read_to_string(format!("{}.{}", path.display(), index)).unwrap();
+            assert_eq!(rotated.len(), 99, "{}.{}", path.display(), index);
+        }
+        assert!(!PathBuf::from(format!("{}.4", path.display())).exists());
+        let expected = (11..50).map(|number| format!("line {:03}", number)).collect::<Vec<_>>();
+        assert_eq!(lines(&path, 3), expected);
+    }
+
+    #[test]
+    fn keeps_a_line_longer_than_the_limit_whole() {
+        let path = directory().join("access.log");
+        let mut file = SizeRotatingFile::open(path.clone(), 10, 5).unwrap();
+
+        file.write_all(b"short\n").unwrap();
+        file.write_all(format!("{}\n", "x".repeat(30)).as_bytes()).unwrap();
+        file.write_all(b"after\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(lines(&path, 5), ["short".to_string(), "x".repeat(30), "after".to_string()]);
+        assert_eq!(fs::read_to_string(format!("{}.1", path.display())).unwrap(), format!("{}\n", "x".repeat(30)));
+    }
+
+    #[test]
+    fn continues_the_size_of_an_existing_file() {
+        let path = directory().join("access.log");
+        fs::write(&path, "x".repeat(95)).unwrap();
+        let mut file = SizeRotatingFile::open(path.clone(), 100, 2).unwrap();
+
+        file.write_all(b"new line\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(format!("{}.1", path.display())).unwrap(), "x".repeat(95));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new line\n");
+    }
+
+    #[test]
+    fn concurrent_writers_never_lose_or_interleave_lines() {
+        let path = directory().join("access.log");
+        let (writer, guard) = tracing_appender::non_blocking(SizeRotatingFile::open(path.clone(), 4096, 1000).unwrap());
+
+        let threads = (0..8)
+            .map(|thread| {
+                let mut writer = writer.clone();
+                std::thread::spawn(move || {
+                    for number in 0..500 {
+                        let line = format!("thread {} line {:03} {}\n", thread, number, "-".repeat(number % 40));
+                        writer.write_all(line.as_bytes()).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        drop(writer);
+        drop(guard);
+
+        let lines = lines(&path, 1000);
+        let expected = (0..8)
+            .flat_map(|thread| (0..500).map(move |number| format!("thread {} line {:03} {}", thread, number, "-".repeat(number % 40))))
+            .collect::<HashSet<_>>();
+        assert_eq!(lines.len(), expected.len());
+        assert_eq!(lines.iter().cloned().collect::<HashSet<_>>(), expected);
+        // Las líneas son más cortas que el límite: ningún fichero lo pasa
+        for index in 1..1000 {
+            match fs::metadata(format!("{}.{}", path.display(), index)) {
+                Ok(metadata) => assert!(metadata.len() <= 4096),
+                Err(_) => break,
+            }
+        }
+    }
+
+    #[test]
+    fn quotes_escape_quotes_backslashes_and_control_characters() {
+        assert_eq!(quoted("curl/8.5.0"), "\"curl/8.5.0\"");
+        assert_eq!(quoted(""), "\"\"");
+        assert_eq!(quoted("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quoted("C:\\temp"), "\"C:\\\\temp\"");
+        assert_eq!(quoted("a\nb\rc\td\u{1b}[31m\u{7f}"), "\"a\\x0ab\\x0dc\\x09d\\x1b[31m\\x7f\"");
+        assert_eq!(quoted("café ☕"), "\"café ☕\"");
+    }
+
+    #[test]
+    fn formats_combined_lines() {
+        assert_eq!(
+            format_line(AccessLogFormat::Combined, &entry()),
+            "192.0.2.10 - - [01/Jun/2030:09:05:07 +0000] \"GET /api/v1/todos?limit=5 HTTP/1.1\" 200 1234 \
+             \"https://example.com/\" \"curl/8.5.0\" 0.250000 \"req-1\"\n"
+        );
+
+        let bare = Entry { client: None, bytes: None, referer: None, user_agent: None, request_id: None, ..entry() };
+        assert_eq!(
+            format_line(AccessLogFormat::Combined, &bare),
+            "- - - [01/Jun/2030:09:05:07 +0000] \"GET /api/v1/todos?limit=5 HTTP/1.1\" 200 - \"-\" \"-\" 0.250000 \"-\"\n"
+        );
+
+        let hostile = Entry { user_agent: Some("evil\" 500 \"x\nfake line".to_string()), ..entry() };
+        let line = format_line(AccessLogFormat::Combined, &hostile);
+        assert_eq!(line.matches('\n').count(), 1);
+        assert!(line.contains(" \"evil\\\" 500 \\\"x\\x0afake line\" "), "{}", line);
+    }
+
+    #[test]
+    fn formats_json_lines() {
+        assert_eq!(
+            format_line(AccessLogFormat::Json, &entry()),
+            concat!(
+                r#"{"bytes":1234,"client_ip":"192.0.2.10","latency_ms":250.0,"method":"GET","path":"/api/v1/todos?limit=5","#,
+                r#""protocol":"HTTP/1.1","referer":"https://example.com/","request_id":"req-1","status":200,"#,
+                r#""timestamp":"2030-06-01T09:05:07Z","user_agent":"curl/8.5.0"}"#,
+                "\n"
+            )
+        );
+
+        let bare = Entry { client: None, bytes: None, referer: None, user_agent: None, request_id: None, ..entry() };
+        let line = format_line(AccessLogFormat::Json, &bare);
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        for field in ["bytes", "client_ip", "referer", "user_agent", "request_id"] {
+            assert_eq!(json[field], serde_json::Value::Null, "{}", field);
+        }
+    }
+
+    #[tokio::test]
+    async fn logs_each_request_through_the_service() {
+        let path = directory().join("access.log");
+        let config = testing::config(&[
+            ("ACCESS_LOG_PATH", path.to_str().unwrap()),
+            ("ACCESS_LOG_FORMAT", "json"),
+            ("ACCESS_LOG_ROTATION", "size"),
+        ]);
+        let (log, guard) = init(&config).unwrap().unwrap();
+        let state = testing::state(sqlx::PgPool::connect_lazy("postgres://test@localhost/test").unwrap(), config);
+
+        for number in 0..3 {
+            let mut request = Request::builder()
+                .method(Method::GET)
+                .uri(format!("/api/v1/health?n={}", number))
+                .header(USER_AGENT, "test-agent")
+                .header("x-request-id", format!("req-{}", number))
+                .body(axum::body::Body::empty())
+                .unwrap();
+            request.extensions_mut().insert(axum::extract::ConnectInfo(testing::PEER.parse::<std::net::SocketAddr>().unwrap()));
+            let response = crate::app(state.clone(), false, Some(log.clone())).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        drop(log);
+        drop(guard);
+
+        let lines = lines(&path, 7)
+            .iter()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        for (number, line) in lines.iter().enumerate() {
+            assert_eq!(line["path"], format!("/api/v1/health?n={}", number));
+            assert_eq!(line["request_id"], format!("req-{}", number));
+            assert_eq!(
+                (&line["client_ip"], &line["method"], &line["status"], &line["user_agent"]),
+                (&serde_json::json!("192.0.2.10"), &serde_json::json!("GET"), &serde_json::json!(200), &serde_json::json!("test-agent"))
+            );
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use axum::{
+    extract::{Json, Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::sync::{atomic::Ordering, Arc};
+use std::time::Duration;
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+use crate::{
+    model::AppState,
+    response::ApiResponse,
+    error::AppError,
+    signature,
+};
+
+/// Header carrying `ADMIN_SHARED_SECRET`.
+const ADMIN_SECRET_HEADER: &str = "x-admin-secret";
+const MAX_DRAIN_SECS: u64 = 300;
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct ShutdownQuery {
+    #[schema(example = "drain-only")]
+    /// `shutdown` (default) drains and then exits; `drain-only` stops at the not-ready stage
+    mode: Option<String>,
+    #[schema(example = 30)]
+    /// Seconds to keep draining before shutting down (max 300)
+    drain_secs: Option<u64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/shutdown",
+    params(ShutdownQuery),
+    responses(
+        (status = 202, description = "Draining started", body = ApiResponseString),
+        (status = 400, description = "Invalid mode or drain period, or shutdown already in progress", body = ApiResponseString),
+        (status = 401, description = "Missing or wrong X-Admin-Secret header", body = ApiResponseString)
+    ),
+    tag = "admin"
+)]
+pub async fn shutdown(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ShutdownQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    authorize(&state, &headers)?;
+
+    let drain_only = match query.mode.as_deref() {
+        None | Some("shutdown") => false,
+        Some("drain-only") => true,
+        Some(other) => {
+            return Err(AppError::ValidationError(format!(
+                "Unknown mode '{}', expected 'shutdown' or 'drain-only'",
+                other
+            )));
+        }
+    };
+    let drain_secs = query.drain_secs.unwrap_or(0);
+    if drain_secs > MAX_DRAIN_SECS {
+        return Err(AppError::ValidationError(format!(
+            "drain_secs must be at most {}",
+            MAX_DRAIN_SECS
+        )));
+    }
+    if state.shutting_down.load(Ordering::Acquire) {
+        return Err(AppError::ValidationError("Shutdown already in progress".to_string()));
+    }
+
+    if !state.draining.swap(true, Ordering::AcqRel) {
+        info!("🚧 Draining: readiness now failing, rejecting new requests");
+        state.changes.wake();
+    }
+    if drain_only {
+        return Ok((StatusCode::ACCEPTED, Json(ApiResponse::<String>::success("Draining".to_string()))));
+    }
+
+    state.shutting_down.store(true, Ordering::Release);
+    info!("🛑 Shutdown requested, exiting in {}s", drain_secs);
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(drain_secs)).await;
+        state.shutdown.notify_one();
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(ApiResponse::<String>::success(format!(
+        "Shutting down in {}s",
+        drain_secs
+    )))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/undrain",
+    responses(
+        (status = 200, description = "Service accepts requests again", body = ApiResponseString),
+        (status = 400, description = "Shutdown already in progress", body = ApiResponseString),
+        (status = 401, description = "Missing or wrong X-Admin-Secret header", body = ApiResponseString)
+    ),
+    tag = "admin"
+)]
+pub async fn undrain(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    authorize(&state, &headers)?;
+
+    if state.shutting_down.load(Ordering::Acquire) {
+        return Err(AppError::ValidationError("Shutdown already in progress".to_string()));
+    }
+    if state.draining.swap(false, Ordering::AcqRel) {
+        info!("✅ Undrained: accepting requests again");
+    }
+
+    Ok((StatusCode::OK, Json(ApiResponse::<String>::success("Accepting requests".to_string()))))
+}
+
+/// Rejects everything but health and admin endpoints with 503 while draining.
+pub async fn reject_while_draining(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    let exempt = path.starts_with("/api/v1/health") || path == "/api/v1/metrics" || path.starts_with("/api/v1/admin/");
+    if state.draining.load(Ordering::Acquire) && !exempt {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ApiResponse::<()>::error("Service is draining")),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Resolves on SIGINT, SIGTERM or a shutdown requested through the admin endpoint.
+pub async fn shutdown_signal(state: Arc<AppState>) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("🛑 Received Ctrl+C"),
+        _ = terminate => info!("🛑 Received SIGTERM"),
+        _ = state.shutdown.notified() => {}
+    }
+
+    state.draining.store(true, Ordering::Release);
+    state.shutting_down.store(true, Ordering::Release);
+    // Los long-polls aparcados responden ya en vez de retrasar el cierre
+    state.changes.wake();
+    info!("👋 Shutting down gracefully, waiting for in-flight requests");
+}
+
+pub(crate) fn authorize(state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let provided = headers.get(ADMIN_SECRET_HEADER).and_then(|value| value.to_str().ok());
+    match (state.config.admin_shared_secret.as_deref(), provided) {
+        (Some(secret), Some(provided)) if signature::secret_matches(secret, provided) => Ok(()),
+        _ => {
+            warn!("Rejected admin request without a valid {} header", ADMIN_SECRET_HEADER);
+            Err(AppError::Unauthorized("Invalid admin secret".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, extract::Request, http::{Method, StatusCode}};
+    use sqlx::PgPool;
+    use std::sync::{atomic::Ordering, Arc};
+    use std::time::Duration;
+    use crate::{model::AppState, testing};
+
+    const SECRET: &str = "test-admin-secret";
+
+    fn state(db: PgPool) -> Arc<AppState> {
+        testing::state(db, testing::config(&[("ADMIN_SHARED_SECRET", SECRET)]))
+    }
+
+    fn admin(uri: &str, secret: Option<&str>) -> Request {
+        let mut request = testing::request(Method::POST, uri, None, Body::empty());
+        if let Some(secret) = secret {
+            request.headers_mut().insert(super::ADMIN_SECRET_HEADER, secret.parse().unwrap());
+        }
+        request
+    }
+
+    async fn status(state: &Arc<AppState>, request: Request) -> StatusCode {
+        testing::send(state, request).await.status()
+    }
+
+    #[sqlx::test]
+    async fn drains_and_undrains(db: PgPool) {
+        let state = state(db);
+
+        let drain = testing::send(&state, admin("/api/v1/admin/shutdown?mode=drain-only", Some(SECRET))).await;
+        assert_eq!(drain.status(), StatusCode::ACCEPTED);
+        assert_eq!(testing::json(drain).await["data"], "Draining");
+
+        let ready = testing::send(&state, testing::get("/api/v1/health/ready")).await;
+        assert_eq!(ready.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(testing::json(ready).await["error"], "Service is draining");
+        assert_eq!(status(&state, testing::get("/api/v1/todos")).await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(status(&state, testing::get("/api/v1/health")).await, StatusCode::OK);
+        // Drenar dos veces no cambia nada
+        assert_eq!(status(&state, admin("/api/v1/admin/shutdown?mode=drain-only", Some(SECRET))).await, StatusCode::ACCEPTED);
+
+        assert_eq!(status(&state, admin("/api/v1/admin/undrain", Some(SECRET))).await, StatusCode::OK);
+        assert_eq!(status(&state, testing::get("/api/v1/health/ready")).await, StatusCode::OK);
+        assert_eq!(status(&state, testing::get("/api/v1/todos")).await, StatusCode::OK);
+        assert!(!state.shutting_down.load(Ordering::Acquire));
+    }
+
+    #[sqlx::test]
+    async fn shutdown_cannot_be_undrained(db: PgPool) {
+        let state = state(db);
+
+        let response = testing::send(&state, admin("/api/v1/admin/shutdown?drain_secs=0", Some(SECRET))).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(testing::json(response).await["data"], "Shutting down in 0s");
+        // El permiso de notify_one espera a que shutdown_signal lo recoja
+        tokio::time::timeout(Duration::from_secs(1), state.shutdown.notified()).await.unwrap();
+
+        assert!(state.draining.load(Ordering::Acquire) && state.shutting_down.load(Ordering::Acquire));
+        assert_eq!(status(&state, admin("/api/v1/admin/undrain", Some(SECRET))).await, StatusCode::BAD_REQUEST);
+        assert_eq!(status(&state, admin("/api/v1/admin/shutdown", Some(SECRET))).await, StatusCode::BAD_REQUEST);
+        assert!(state.draining.load(Ordering::Acquire));
+    }
+
+    #[sqlx::test]
+    async fn rejects_invalid_modes_and_drain_periods(db: PgPool) {
+        let state = state(db);
+
+        for uri in ["/api/v1/admin/shutdown?mode=reboot", "/api/v1/admin/shutdown?drain_secs=301"] {
+            assert_eq!(status(&state, admin(uri, Some(SECRET))).await, StatusCode::BAD_REQUEST, "{}", uri);
+        }
+        assert!(!state.draining.load(Ordering::Acquire));
+    }
+
+    #[sqlx::test]
+    async fn requires_the_admin_secret(db: PgPool) {
+        let state = state(db.clone());
+        for secret in [None, Some(""), Some("wrong"), Some("test-admin-secret ")] {
+            for uri in ["/api/v1/admin/shutdown?mode=drain-only", "/api/v1/admin/undrain"] {
+                assert_eq!(status(&state, admin(uri, secret)).await, StatusCode::UNAUTHORIZED, "{:?} {}", secret, uri);
+            }
+        }
+        assert!(!state.draining.load(Ordering::Acquire));
+
+        // Sin ADMIN_SHARED_SECRET configurado no hay secreto que valga
+        let unconfigured = testing::state(db, testing::config(&[]));
+        let request = admin("/api/v1/admin/shutdown?mode=drain-only", Some(SECRET));
+        assert_eq!(status(&unconfigured, request).await, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,136 @@
+//! Types of the HTTP API shared by the server and the `todo-cli` client.
+//!
+//! The client includes this file (and [`crate::timestamp`]) by path, so it
+//! may only depend on those two modules and on external crates.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiResponse<T> {
+    pub status: String,
+    pub data: Option<T>,
+    pub error: Option<String>,
+}
+
+impl<T: Serialize> ApiResponse<T> {
+    pub fn success(data: T) -> Self {
+        Self {
+            status: "success".to_string(),
+            data: Some(data),
+            error: None,
+        }
+    }
+
+    pub fn error(message: &str) -> Self {
+        Self {
+            status: "error".to_string(),
+            data: None,
+            error: Some(message.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, FromRow)]
+#[schema(example = json!({
+    "id": "550e8400-e29b-41d4-a716-446655440000",
+    "title": "Buy groceries",
+    "description": "- [ ] Milk\n- [ ] Eggs",
+    "completed": false,
+    "due_date": "2030-01-01T18:00:00.000Z",
+    "created_at": "2023-01-01T00:00:00.000Z",
+    "updated_at": "2023-01-01T00:00:00.000Z"
+}))]
+pub struct Todo {
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub id: Uuid,
+    #[schema(example = "Buy groceries")]
+    pub title: String,
+    #[schema(example = "- [ ] Milk\n- [ ] Eggs")]
+    /// Markdown description
+    pub description: Option<String>,
+    #[schema(example = false)]
+    pub completed: bool,
+    #[schema(example = "2030-01-01T18:00:00.000Z")]
+    #[serde(default, with = "crate::timestamp::option")]
+    pub due_date: Option<DateTime<Utc>>,
+    #[schema(example = "2023-01-01T00:00:00.000Z")]
+    #[serde(with = "crate::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[schema(example = "2023-01-01T00:00:00.000Z")]
+    #[serde(with = "crate::timestamp")]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Body of `POST /api/v1/todos` as JSON.
+#[derive(Serialize, Deserialize, ToSchema, Validate)]
+#[schema(example = json!({
+    "title": "Buy groceries",
+    "description": "- [ ] Milk\n- [ ] Eggs",
+    "completed": false,
+    "due_date": "2030-01-01T18:00:00Z"
+}))]
+pub struct CreateTodo {
+    #[validate(length(min = 1, max = 255, message = "Title must be between 1 and 255 characters"))]
+    #[schema(example = "Buy groceries")]
+    pub title: String,
+    #[validate(length(max = 10000, message = "Description must be at most 10000 characters"))]
+    #[schema(example = "- [ ] Milk\n- [ ] Eggs")]
+    /// Markdown description
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[schema(example = false)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed: Option<bool>,
+    #[schema(example = "2030-01-01T18:00:00Z")]
+    /// When the todo is due: an RFC 3339 timestamp, a date (`2030-01-01`,
+    /// midnight in `tz`) or a phrase such as "tomorrow", "next friday",
+    /// "in 3 days" or "may 15 5pm"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<String>,
+    #[schema(example = "Europe/Madrid")]
+    /// IANA timezone used to resolve dates and phrases (defaults to UTC)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tz: Option<String>,
+}
+
+/// Body of a PATCH: only the fields present are changed, `null` clears
+/// `description` and `due_date`.
+#[derive(Serialize, Deserialize, ToSchema, Validate)]
+#[schema(example = json!({ "completed": true }))]
+pub struct PatchTodo {
+    #[validate(length(min = 1, max = 255, message = "Title must be between 1 and 255 characters"))]
+    #[schema(example = "Buy groceries")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[validate(length(max = 10000, message = "Description must be at most 10000 characters"))]
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "- [ ] Milk\n- [ ] Eggs")]
+    /// Markdown description, `null` to remove it
+    pub description: Option<Option<String>>,
+    #[schema(example = true)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed: Option<bool>,
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "2030-01-01T18:00:00Z")]
+    /// When the todo is due, `null` to remove it: an RFC 3339 timestamp, a date
+    /// (midnight in `tz`) or a phrase such as "tomorrow" or "in 3 days"
+    pub due_date: Option<Option<String>>,
+    #[schema(example = "Europe/Madrid")]
+    /// IANA timezone used to resolve dates and phrases (defaults to UTC)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tz: Option<String>,
+}
+
+/// Tells a missing field (`None`) apart from an explicit `null` (`Some(None)`).
+pub fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
@@ -0,0 +1,318 @@
+use axum::{
+    extract::{rejection::QueryRejection, Json, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::sync::{atomic::Ordering, Arc};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::{
+    model::{AppState, Todo},
+    response::ApiResponse,
+    error::AppError,
+    handler::PaginationQuery,
+    admin
+};
+
+#[derive(Serialize, Deserialize, ToSchema, FromRow)]
+pub struct ArchivedTodo {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub todo: Todo,
+    #[schema(example = "2026-01-01T03:00:00.000Z")]
+    #[serde(with = "crate::timestamp")]
+    pub archived_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct ArchiveSearch {
+    #[schema(example = "groceries")]
+    /// Case-insensitive text to look for in the title or description
+    q: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct ArchiveRunQuery {
+    #[schema(example = 365)]
+    /// Archive completed todos not updated for this many days (defaults to ARCHIVE_AFTER_DAYS)
+    older_than_days: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ArchiveRun {
+    /// Number of todos moved to the archive
+    #[schema(example = 1200)]
+    pub archived: u64,
+    /// Completed todos last updated before this instant were archived
+    #[schema(example = "2025-01-01T03:00:00.000Z")]
+    #[serde(with = "crate::timestamp")]
+    pub cutoff: DateTime<Utc>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/archive",
+    params(PaginationQuery, ArchiveSearch),
+    responses(
+        (status = 200, description = "Archived todos, most recently archived first", body = ApiResponseVecArchivedTodo),
+        (status = 400, description = "Invalid pagination", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn list_archived(
+    State(state): State<Arc<AppState>>,
+    pagination: Result<Query<PaginationQuery>, QueryRejection>,
+    Query(search): Query<ArchiveSearch>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(pagination) = pagination?;
+    let (page, limit, offset) = pagination.resolve(&state.config)?;
+    let pattern = search
+        .q
+        .as_deref()
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+        .map(|q| format!("%{}%", q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")));
+
+    let todos = sqlx::query_as::<_, ArchivedTodo>(
+        r#"
+        SELECT id, title, description, completed, due_date, created_at, updated_at, archived_at
+        FROM todos_archive
+        WHERE $1::TEXT IS NULL OR title ILIKE $1 OR description ILIKE $1
+        ORDER BY archived_at DESC, id
+        LIMIT $2 OFFSET $3
+        "#
+    )
+    .bind(pattern)
+    .bind(i64::from(limit))
+    .bind(offset)
+    .fetch_all(&state.db)
+    .await?;
+
+    info!("Retrieved {} archived todos (page: {}, limit: {})", todos.len(), page, limit);
+    Ok((StatusCode::OK, Json(ApiResponse::success(todos))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/archive/{id}/restore",
+    params(
+        ("id" = Uuid, Path, description = "Archived todo ID")
+    ),
+    responses(
+        (status = 200, description = "Todo moved back to the live table", body = ApiResponseTodo),
+        (status = 400, description = "A live todo was imported from the same source meanwhile", body = ApiResponseString),
+        (status = 404, description = "Archived todo not found", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn restore_archived(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = state.db.begin().await?;
+
+    let external_ref = sqlx::query_scalar::<_, Option<String>>(
+        r#"
+        SELECT external_ref
+        FROM todos_archive
+        WHERE id = $1
+        FOR UPDATE
+        "#
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    if let Some(external_ref) = &external_ref {
+        let taken = sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM todos WHERE external_ref = $1)")
+            .bind(external_ref)
+            .fetch_one(&mut *tx)
+            .await?;
+        if taken {
+            return Err(AppError::ValidationError(format!(
+                "A live todo was already imported from {}",
+                external_ref
+            )));
+        }
+    }
+
+    // Se marca como actualizado para que la siguiente pasada no lo vuelva a archivar
+    let todo = sqlx::query_as::<_, Todo>(
+        r#"
+        WITH restored AS (
+            DELETE FROM todos_archive
+            WHERE id = $1
+            RETURNING id, title, description, completed, due_date, external_ref, created_at, updated_at
+        )
+        INSERT INTO todos (id, title, description, completed, due_date, external_ref, created_at, updated_at)
+        SELECT id, title, description, completed, due_date, external_ref, created_at, $2
+        FROM restored
+        RETURNING id, title, description, completed, due_date, created_at, updated_at
+        "#
+    )
+    .bind(id)
+    .bind(state.clock.now())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    info!("Archived todo restored with id: {}", id);
+    Ok((StatusCode::OK, Json(ApiResponse::success(todo))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/admin/archive",
+    params(ArchiveRunQuery),
+    responses(
+        (status = 200, description = "Archival pass finished", body = ApiResponseArchiveRun),
+        (status = 400, description = "No age given and ARCHIVE_AFTER_DAYS is not set", body = ApiResponseString),
+        (status = 401, description = "Missing or wrong X-Admin-Secret header", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "admin"
+)]
+pub async fn run_archival(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ArchiveRunQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    admin::authorize(&state, &headers)?;
+
+    let days = query
+        .older_than_days
+        .or(state.config.archive_after_days)
+        .ok_or_else(|| AppError::ValidationError("older_than_days is required when ARCHIVE_AFTER_DAYS is not set".to_string()))?;
+    let cutoff = state.clock.now() - Duration::days(days.into());
+
+    let archived = archive_completed_before(&state, cutoff).await?;
+    Ok((StatusCode::OK, Json(ApiResponse::success(ArchiveRun { archived, cutoff }))))
+}
+
+/// Moves completed todos last updated before `cutoff` into `todos_archive`,
+/// `ARCHIVE_BATCH_SIZE` rows per transaction.
+///
+/// Each batch is a single DELETE ... RETURNING feeding an INSERT, so a row is
+/// either still live or already archived: a crash mid-run loses nothing and
+/// the next run picks up where this one stopped.
+pub async fn archive_completed_before(state: &AppState, cutoff: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+    let mut total = 0;
+    loop {
+        let mut tx = state.db.begin().await?;
+        let moved = sqlx::query(
+            r#"
+            WITH moved AS (
+                DELETE FROM todos
+                WHERE id IN (
+                    SELECT id
+                    FROM todos
+                    WHERE completed AND updated_at < $1
+                    ORDER BY updated_at
+                    LIMIT $2
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, title, description, completed, due_date, external_ref, created_at, updated_at
+            )
+            INSERT INTO todos_archive (id, title, description, completed, due_date, external_ref, created_at, updated_at, archived_at)
+            SELECT id, title, description, completed, due_date, external_ref, created_at, updated_at, $3
+            FROM moved
+            "#
+        )
+        .bind(cutoff)
+        .bind(state.config.archive_batch_size as i64)
+        .bind(state.clock.now())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+
+        total += moved;
+        if moved < state.config.archive_batch_size as u64 || state.shutting_down.load(Ordering::Acquire) {
+            break;
+        }
+    }
+
+    if total > 0 {
+        info!("🗃️ Archived {} completed todos last updated before {}", total, cutoff);
+    }
+    Ok(total)
+}
+
+/// Runs an archival pass every `ARCHIVE_INTERVAL_SECS` until shutdown.
+pub async fn run_job(state: Arc<AppState>, after_days: u32) {
+    let interval = std::time::Duration::from_secs(state.config.archive_interval_secs);
+    info!("🗃️ Archival enabled for completed todos older than {} days, every {:?}", after_days, interval);
+
+    loop {
+        if state.shutting_down.load(Ordering::Acquire) {
+            info!("Archival job stopped");
+            return;
+        }
+        // Hasta que las migraciones terminen (modo lazy) la tabla puede no existir
+        if state.migrations_done.load(Ordering::Acquire) {
+            let cutoff = state.clock.now() - Duration::days(after_days.into());
+            if let Err(e) = archive_completed_before(&state, cutoff).await {
+                warn!("Archival pass failed: {}", e);
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use sqlx::PgPool;
+    use crate::{clock::{Clock, MockClock}, testing};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    async fn insert(db: &PgPool, title: &str, completed: bool, updated_at: DateTime<Utc>) {
+        sqlx::query("INSERT INTO todos (title, completed, updated_at) VALUES ($1, $2, $3)")
+            .bind(title)
+            .bind(completed)
+            .bind(updated_at)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn archives_completed_todos_updated_strictly_before_the_cutoff(db: PgPool) {
+        let clock = MockClock::new(now());
+        let state = testing::state_at(db.clone(), testing::config(&[("ADMIN_SHARED_SECRET", "s3cret")]), &clock);
+        let cutoff = now() - Duration::days(10);
+        insert(&db, "old", true, cutoff - Duration::seconds(1)).await;
+        insert(&db, "at the cutoff", true, cutoff).await;
+        insert(&db, "old but open", false, cutoff - Duration::days(30)).await;
+
+        let mut request = testing::request(Method::POST, "/api/v1/admin/archive?older_than_days=10", None, Default::default());
+        request.headers_mut().insert("x-admin-secret", "s3cret".parse().unwrap());
+        let response = testing::send(&state, request).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let run = testing::json(response).await;
+        assert_eq!(run["data"]["archived"], 1);
+        assert_eq!(run["data"]["cutoff"], "2029-12-22T12:00:00.000Z");
+        let archived: Vec<(String, DateTime<Utc>)> = sqlx::query_as("SELECT title, archived_at FROM todos_archive")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(archived, [("old".to_string(), now())]);
+
+        // Un segundo después el que estaba en el límite también sale
+        clock.advance(Duration::seconds(1));
+        assert_eq!(super::archive_completed_before(&state, clock.now() - Duration::days(10)).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,449 @@
+//! `backend backup` and `backend restore`: operational backups taken
+//! straight from the database instead of through the HTTP API.
+//!
+//! A backup is a gzipped JSON Lines file. It starts with a header carrying
+//! the format version and the schema (last migration) it was taken from,
+//! then, for every table, a `table` record, the rows in batches and a
+//! `table_end` record with the row count, and finally an `end` record. A
+//! file missing any of these is rejected on restore.
+//!
+//! The change feed (`todo_changes`) is left out: its positions only mean
+//! something to the clients of the database that wrote them. Restoring todos
+//! records them as created like any other write, and `--wipe` also empties
+//! the feed, so clients holding a cursor from before get `410 Gone` and
+//! resync instead of reading a history that no longer matches the data.
+
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Value};
+use sqlx::PgPool;
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use crate::{config::Config, migrate};
+
+const FORMAT: &str = "ha-todo-backup";
+const FORMAT_VERSION: u32 = 1;
+const BATCH_SIZE: usize = 1000;
+
+/// Tables in the backup with the column they are read in order of, listed so
+/// that referenced tables are restored first.
+const TABLES: &[(&str, &str)] = &[
+    ("todos", "id"),
+    ("todos_archive", "id"),
+    ("inbound_emails", "message_id"),
+    ("inbound_email_tokens", "token"),
+];
+
+const USAGE: &str = "Usage:
+  backend                                         start the API server
+  backend backup --out <backup.json.gz>           write a backup of every table
+  backend restore --file <backup.json.gz> [--wipe]
+                                                  load a backup, emptying the tables first with --wipe";
+
+pub enum Command {
+    Serve,
+    Backup { out: PathBuf },
+    Restore { file: PathBuf, wipe: bool },
+}
+
+impl Command {
+    /// Parses the command line (without the program name).
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let Some(command) = args.next() else {
+            return Ok(Command::Serve);
+        };
+
+        let mut path = None;
+        let mut wipe = false;
+        let flag = match command.as_str() {
+            "backup" => "--out",
+            "restore" => "--file",
+            "help" | "--help" | "-h" => return Err(USAGE.to_string()),
+            other => return Err(format!("Unknown command '{}'\n\n{}", other, USAGE)),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--wipe" if command == "restore" => wipe = true,
+                arg if arg == flag => {
+                    path = Some(args.next().ok_or_else(|| format!("{} needs a path\n\n{}", flag, USAGE))?);
+                }
+                other => return Err(format!("Unknown argument '{}'\n\n{}", other, USAGE)),
+            }
+        }
+        let path = PathBuf::from(path.ok_or_else(|| format!("{} is required\n\n{}", flag, USAGE))?);
+
+        Ok(match command.as_str() {
+            "backup" => Command::Backup { out: path },
+            _ => Command::Restore { file: path, wipe },
+        })
+    }
+}
+
+/// A line of the backup. Rows are written as the JSON Postgres produced
+/// (`RawValue`) and read back as `Value`, which serde can buffer for the
+/// internally tagged enum.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<R> {
+    Header {
+        format: String,
+        version: u32,
+        schema_version: i64,
+        created_at: DateTime<Utc>,
+    },
+    Table {
+        name: String,
+    },
+    Rows {
+        rows: Vec<R>,
+    },
+    TableEnd {
+        name: String,
+        rows: u64,
+    },
+    End {
+        tables: usize,
+    },
+}
+
+/// Writes every table to `out` from a single consistent snapshot, reading
+/// through server-side cursors so memory use does not grow with the data.
+pub async fn backup(config: &Config, out: &Path) -> Result<(), Box<dyn Error>> {
+    let pool = PgPool::connect(&config.database_url).await?;
+    backup_from(&pool, out).await
+}
+
+async fn backup_from(pool: &PgPool, out: &Path) -> Result<(), Box<dyn Error>> {
+    // Se escribe a un fichero temporal para no dejar un backup a medias con el nombre final
+    let partial = PathBuf::from(format!("{}.partial", out.display()));
+    let mut writer = GzEncoder::new(BufWriter::new(File::create(&partial)?), Compression::default());
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    let schema_version = applied_schema_version(&mut tx).await?;
+    write_record(&mut writer, &Record::Header {
+        format: FORMAT.to_string(),
+        version: FORMAT_VERSION,
+        schema_version,
+        created_at: Utc::now(),
+    })?;
+
+    for (table, key) in TABLES {
+        write_record(&mut writer, &Record::Table { name: table.to_string() })?;
+        sqlx::query(&format!(
+            "DECLARE backup_rows NO SCROLL CURSOR FOR SELECT row_to_json(t)::text FROM {} t ORDER BY {}",
+            table, key
+        ))
+        .execute(&mut *tx)
+        .await?;
+
+        let mut count = 0u64;
+        loop {
+            let rows = sqlx::query_scalar::<_, String>(&format!("FETCH {} FROM backup_rows", BATCH_SIZE))
+                .fetch_all(&mut *tx)
+                .await?;
+            if rows.is_empty() {
+                break;
+            }
+            count += rows.len() as u64;
+            let rows = rows.into_iter().map(RawValue::from_string).collect::<Result<_, _>>()?;
+            write_record(&mut writer, &Record::Rows { rows })?;
+        }
+        sqlx::query("CLOSE backup_rows").execute(&mut *tx).await?;
+
+        write_record(&mut writer, &Record::TableEnd { name: table.to_string(), rows: count })?;
+        eprintln!("{}: {} rows", table, count);
+    }
+    write_record(&mut writer, &Record::End { tables: TABLES.len() })?;
+    tx.commit().await?;
+
+    let file = writer.finish()?.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    std::fs::rename(&partial, out)?;
+    eprintln!("Backup of schema version {} written to {}", schema_version, out.display());
+    Ok(())
+}
+
+/// Loads a backup written by [`backup`], one transaction per table. The
+/// whole file is checked before anything is written, and it must come from
+/// the schema version the database is migrated to.
+pub async fn restore(config: &Config, file: &Path, wipe: bool) -> Result<(), Box<dyn Error>> {
+    let schema_version = verify(file)?;
+
+    let pool = PgPool::connect(&config.database_url).await?;
+    let lock_timeout = Duration::from_secs(config.migration_lock_timeout_secs);
+    restore_into(&pool, lock_timeout, file, schema_version, wipe).await
+}
+
+async fn restore_into(
+    pool: &PgPool,
+    lock_timeout: Duration,
+    file: &Path,
+    schema_version: i64,
+    wipe: bool,
+) -> Result<(), Box<dyn Error>> {
+    if !migrate::run(pool, lock_timeout).await? {
+        return Err("Another instance is holding the migration lock, try again later".into());
+    }
+    let current = applied_schema_version(&mut *pool.acquire().await?).await?;
+    if schema_version != current {
+        return Err(format!(
+            "The backup is from schema version {} but the database is at {}, restore it with the matching release",
+            schema_version, current
+        )
+        .into());
+    }
+
+    let mut tables = 0;
+    let mut current_table = None;
+    for record in records(file)?.skip(1) {
+        match record? {
+            Record::Table { name } => {
+                let mut tx = pool.begin().await?;
+                if wipe {
+                    let deleted = sqlx::query(&format!("DELETE FROM {}", name)).execute(&mut *tx).await?;
+                    eprintln!("{}: deleted {} existing rows", name, deleted.rows_affected());
+                    if name == "todos" {
+                        // El feed describe los datos borrados: se vacía con ellos, borrados incluidos
+                        let reset = sqlx::query("DELETE FROM todo_changes").execute(&mut *tx).await?;
+                        eprintln!("todo_changes: reset the change feed ({} rows)", reset.rows_affected());
+                    }
+                }
+                current_table = Some((name, tx, 0u64));
+            }
+            Record::Rows { rows } => {
+                let (name, tx, count) = current_table.as_mut().ok_or("The backup records are out of order")?;
+                let inserted = sqlx::query(&format!(
+                    "INSERT INTO {0} SELECT * FROM json_populate_recordset(NULL::{0}, $1::json)",
+                    name
+                ))
+                .bind(serde_json::to_string(&rows)?)
+                .execute(&mut **tx)
+                .await?;
+                *count += inserted.rows_affected();
+            }
+            Record::TableEnd { rows, .. } => {
+                let (name, tx, count) = current_table.take().ok_or("The backup records are out of order")?;
+                if rows != count {
+                    return Err(format!("{}: the backup lists {} rows but {} were inserted", name, rows, count).into());
+                }
+                tx.commit().await?;
+                eprintln!("{}: restored {} rows", name, count);
+                tables += 1;
+            }
+            Record::Header { .. } | Record::End { .. } => {}
+        }
+    }
+
+    eprintln!("Restored {} tables from {}", tables, file.display());
+    Ok(())
+}
+
+/// Checks the structure and row counts of a backup without touching the
+/// database, returning its schema version.
+fn verify(file: &Path) -> Result<i64, Box<dyn Error>> {
+    let mut records = records(file)?;
+    let schema_version = match records.next().transpose()? {
+        Some(Record::Header { format, version, schema_version, .. }) if format == FORMAT => {
+            if version != FORMAT_VERSION {
+                return Err(format!("Unsupported backup format version {} (expected {})", version, FORMAT_VERSION).into());
+            }
+            schema_version
+        }
+        _ => return Err(format!("{} is not a backup written by this service", file.display()).into()),
+    };
+
+    let mut seen = Vec::new();
+    let mut current_table: Option<(String, u64)> = None;
+    loop {
+        let record = records.next().transpose()?.ok_or("The backup is truncated (no end record)")?;
+        match (record, current_table.take()) {
+            (Record::Table { name }, None) => {
+                if !TABLES.iter().any(|(table, _)| *table == name) || seen.contains(&name) {
+                    return Err(format!("Unexpected table '{}' in the backup", name).into());
+                }
+                current_table = Some((name, 0));
+            }
+            (Record::Rows { rows }, Some((name, count))) => {
+                current_table = Some((name, count + rows.len() as u64));
+            }
+            (Record::TableEnd { name: end, rows }, Some((name, count))) if end == name => {
+                if rows != count {
+                    return Err(format!("{}: the backup lists {} rows but contains {}", name, rows, count).into());
+                }
+                seen.push(name);
+            }
+            (Record::End { tables }, None) => {
+                if tables != seen.len() {
+                    return Err(format!("The backup lists {} tables but contains {}", tables, seen.len()).into());
+                }
+                return Ok(schema_version);
+            }
+            _ => return Err("The backup records are out of order".into()),
+        }
+    }
+}
+
+type ReadRecord = Result<Record<Value>, Box<dyn Error>>;
+
+fn records(file: &Path) -> Result<impl Iterator<Item = ReadRecord>, Box<dyn Error>> {
+    let lines = BufReader::new(GzDecoder::new(File::open(file)?)).lines();
+    Ok(lines.map(|line| Ok(serde_json::from_str(&line?)?)))
+}
+
+/// Version of the last migration applied to the database.
+async fn applied_schema_version(conn: &mut sqlx::PgConnection) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM _sqlx_migrations WHERE success")
+        .fetch_one(conn)
+        .await
+}
+
+fn write_record(writer: &mut impl Write, record: &Record<Box<RawValue>>) -> Result<(), Box<dyn Error>> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use sqlx::PgPool;
+    use std::{path::PathBuf, time::Duration};
+    use uuid::Uuid;
+    use super::TABLES;
+    use crate::testing;
+
+    fn backup_path() -> PathBuf {
+        std::env::temp_dir().join(format!("ha_todo_backup_{}.json.gz", Uuid::new_v4().simple()))
+    }
+
+    /// Every table in the backup as one JSON array, in key order.
+    async fn snapshot(db: &PgPool) -> Vec<String> {
+        let mut tables = Vec::new();
+        for (table, key) in TABLES {
+            let rows = sqlx::query_scalar(&format!(
+                "SELECT COALESCE(json_agg(row_to_json(t) ORDER BY {})::text, '[]') FROM {} t",
+                key, table
+            ))
+            .fetch_one(db)
+            .await
+            .unwrap();
+            tables.push(rows);
+        }
+        tables
+    }
+
+    async fn seed(db: &PgPool) {
+        // Más filas que un lote, para cubrir la lectura por cursor
+        sqlx::query(
+            r#"
+            INSERT INTO todos (title, description, completed, due_date, external_ref)
+            SELECT 'Todo ' || n, CASE WHEN n % 2 = 0 THEN 'Línea "uno"' || E'\n' || 'dos' END, n % 3 = 0,
+                   CASE WHEN n % 5 = 0 THEN '2030-01-01T08:30:00.123456Z'::timestamptz END,
+                   CASE WHEN n % 7 = 0 THEN 'ref-' || n END
+            FROM generate_series(1, 1234) AS n
+            "#,
+        )
+        .execute(db)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO todos_archive (id, title, description, completed, due_date, external_ref, created_at, updated_at, archived_at)
+            VALUES (gen_random_uuid(), 'Archived', NULL, TRUE, NULL, 'ref-archived', '2024-01-01Z', '2024-02-01Z', '2024-03-01Z')
+            "#,
+        )
+        .execute(db)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO inbound_emails (message_id, todo_id) SELECT '<a@example.com>', id FROM todos WHERE title = 'Todo 1'")
+            .execute(db)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO inbound_emails (message_id, todo_id) VALUES ('<b@example.com>', NULL)")
+            .execute(db)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO inbound_email_tokens (token, signed_at) VALUES ('token', '2030-01-01T12:00:00Z')")
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn a_wiped_restore_brings_back_every_table_as_it_was(db: PgPool) {
+        seed(&db).await;
+        let before = snapshot(&db).await;
+        let path = backup_path();
+        super::backup_from(&db, &path).await.unwrap();
+
+        // Cambios posteriores al backup que el restore debe deshacer
+        sqlx::query("UPDATE todos SET completed = NOT completed WHERE title = 'Todo 2'").execute(&db).await.unwrap();
+        sqlx::query("DELETE FROM todos WHERE title = 'Todo 3'").execute(&db).await.unwrap();
+        testing::insert_todo(&db, "After the backup").await;
+        sqlx::query("DELETE FROM inbound_email_tokens").execute(&db).await.unwrap();
+        assert_ne!(snapshot(&db).await, before);
+
+        let schema_version = super::verify(&path).unwrap();
+        super::restore_into(&db, Duration::from_secs(5), &path, schema_version, true).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(snapshot(&db).await, before);
+    }
+
+    #[sqlx::test]
+    async fn a_wiped_restore_resets_the_change_feed(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        seed(&db).await;
+        let path = backup_path();
+        super::backup_from(&db, &path).await.unwrap();
+        testing::insert_todo(&db, "After the backup").await;
+        let old_cursor: i64 = sqlx::query_scalar("SELECT MAX(seq) FROM todo_changes").fetch_one(&db).await.unwrap();
+
+        let schema_version = super::verify(&path).unwrap();
+        super::restore_into(&db, Duration::from_secs(5), &path, schema_version, true).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Solo quedan las altas de lo restaurado, sin las bajas del borrado
+        let ops: Vec<(String, i64)> = sqlx::query_as("SELECT op, COUNT(*) FROM todo_changes GROUP BY op")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(ops, [("created".to_string(), 1234)]);
+        let response = testing::send(&state, testing::get(&format!("/api/v1/todos/changes?since={}", old_cursor))).await;
+        assert_eq!(response.status(), StatusCode::GONE);
+    }
+
+    #[sqlx::test]
+    async fn rejects_a_backup_without_its_end_record(db: PgPool) {
+        seed(&db).await;
+        let path = backup_path();
+        super::backup_from(&db, &path).await.unwrap();
+
+        // Se reescribe sin la última línea (el registro end)
+        let lines = super::records(&path).unwrap().count();
+        let mut truncated = Vec::new();
+        {
+            use std::io::{BufRead, Write};
+            let reader = std::io::BufReader::new(flate2::read::GzDecoder::new(std::fs::File::open(&path).unwrap()));
+            let mut writer = flate2::write::GzEncoder::new(&mut truncated, flate2::Compression::default());
+            for line in reader.lines().take(lines - 1) {
+                writeln!(writer, "{}", line.unwrap()).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        std::fs::write(&path, truncated).unwrap();
+
+        let error = super::verify(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.to_string(), "The backup is truncated (no end record)");
+    }
+}
@@ -0,0 +1,235 @@
+//! Settings, HTTP calls and errors of `todo-cli`.
+
+use reqwest::{header, Method, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fmt, path::PathBuf, process::ExitCode, time::Duration};
+use uuid::Uuid;
+use crate::api::{ApiResponse, CreateTodo, PatchTodo, Todo};
+
+const DEFAULT_URL: &str = "http://localhost:8080";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Todos fetched per request while looking up a short id; the server clamps
+/// it to its PAGINATION_MAX_LIMIT.
+const LOOKUP_PAGE_SIZE: u32 = 100;
+/// Shortest prefix accepted as a short id.
+const MIN_SHORT_ID: usize = 4;
+
+/// Why a command failed. Each kind exits with its own code (from
+/// sysexits.h) so scripts can tell them apart.
+#[derive(Debug)]
+pub enum CliError {
+    Usage(String),
+    Config(String),
+    NotFound(String),
+    Rejected(String),
+    Connection(reqwest::Error),
+    Server(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            // EX_USAGE
+            CliError::Usage(_) => 64,
+            // EX_CONFIG
+            CliError::Config(_) => 78,
+            // EX_NOINPUT
+            CliError::NotFound(_) => 66,
+            // EX_DATAERR
+            CliError::Rejected(_) => 65,
+            // EX_UNAVAILABLE
+            CliError::Connection(_) => 69,
+            // EX_SOFTWARE
+            CliError::Server(_) => 70,
+        })
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) | CliError::Config(message) => write!(f, "{}", message),
+            CliError::NotFound(message) => write!(f, "not found: {}", message),
+            CliError::Rejected(message) => write!(f, "rejected by the server: {}", message),
+            CliError::Connection(e) => write!(f, "could not reach the server: {}", e),
+            CliError::Server(message) => write!(f, "server error: {}", message),
+        }
+    }
+}
+
+/// `~/.config/ha-todo/config.toml`; every key is optional.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct FileSettings {
+    url: Option<String>,
+    api_key: Option<String>,
+    tz: Option<String>,
+}
+
+/// Where and how to reach the service. `TODO_URL`, `TODO_API_KEY` and
+/// `TODO_TZ` take precedence over the config file.
+pub struct Settings {
+    /// Including any BASE_PATH the service is mounted under
+    pub url: String,
+    /// Sent as a bearer token, for a proxy in front of the service
+    pub api_key: Option<String>,
+    /// IANA timezone for due dates, both those sent and those shown
+    pub tz: Option<String>,
+}
+
+impl Settings {
+    pub fn load() -> Result<Self, CliError> {
+        let file = match config_path() {
+            Some(path) if path.exists() => {
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| CliError::Config(format!("could not read {}: {}", path.display(), e)))?;
+                toml::from_str::<FileSettings>(&text)
+                    .map_err(|e| CliError::Config(format!("invalid {}: {}", path.display(), e)))?
+            }
+            _ => FileSettings::default(),
+        };
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+
+        Ok(Settings {
+            url: env("TODO_URL").or(file.url).unwrap_or_else(|| DEFAULT_URL.to_string()),
+            api_key: env("TODO_API_KEY").or(file.api_key),
+            tz: env("TODO_TZ").or(file.tz),
+        })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/ha-todo/config.toml`, by default under `~/.config`.
+fn config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|base| !base.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("ha-todo").join("config.toml"))
+}
+
+pub struct Client {
+    http: reqwest::Client,
+    todos_url: String,
+    api_key: Option<String>,
+}
+
+impl Client {
+    pub fn new(settings: &Settings) -> Result<Self, CliError> {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("todo-cli/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| CliError::Config(format!("could not create the HTTP client: {}", e)))?;
+        Ok(Client {
+            http,
+            todos_url: format!("{}/api/v1/todos", settings.url.trim_end_matches('/')),
+            api_key: settings.api_key.clone(),
+        })
+    }
+
+    /// One page of todos, newest first.
+    pub async fn list(&self, filter: Option<&str>, page: u32, limit: Option<u32>) -> Result<Vec<Todo>, CliError> {
+        let mut query = vec![("page", page.to_string())];
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+        if let Some(filter) = filter {
+            query.push(("filter", filter.to_string()));
+        }
+        self.send(Method::GET, "", &query, None::<&()>).await
+    }
+
+    pub async fn create(&self, todo: &CreateTodo) -> Result<Todo, CliError> {
+        self.send(Method::POST, "", &[], Some(todo)).await
+    }
+
+    pub async fn patch(&self, id: Uuid, patch: &PatchTodo) -> Result<Todo, CliError> {
+        self.send(Method::PATCH, &format!("/{}", id), &[], Some(patch)).await
+    }
+
+    pub async fn delete(&self, id: Uuid) -> Result<(), CliError> {
+        self.send::<String>(Method::DELETE, &format!("/{}", id), &[], None::<&()>).await?;
+        Ok(())
+    }
+
+    /// Accepts a full id or an unambiguous prefix of one (as the table shows).
+    pub async fn resolve(&self, id: &str) -> Result<Uuid, CliError> {
+        if let Ok(id) = id.parse::<Uuid>() {
+            return Ok(id);
+        }
+        let prefix = id.to_ascii_lowercase();
+        if prefix.len() < MIN_SHORT_ID || !prefix.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+            return Err(CliError::Usage(format!(
+                "'{}' is not a todo id, pass the id or at least its first {} characters",
+                id, MIN_SHORT_ID
+            )));
+        }
+
+        let mut matches = Vec::new();
+        for page in 1.. {
+            let todos = self.list(None, page, Some(LOOKUP_PAGE_SIZE)).await?;
+            if todos.is_empty() {
+                break;
+            }
+            matches.extend(todos.iter().map(|todo| todo.id).filter(|id| id.to_string().starts_with(&prefix)));
+        }
+        match matches.as_slice() {
+            [id] => Ok(*id),
+            [] => Err(CliError::NotFound(format!("no todo id starts with '{}'", prefix))),
+            _ => Err(CliError::Usage(format!(
+                "'{}' matches {} todos, use more characters: {}",
+                prefix,
+                matches.len(),
+                matches.iter().map(Uuid::to_string).collect::<Vec<_>>().join(", ")
+            ))),
+        }
+    }
+
+    /// Sends a request and unwraps the `data` of the response envelope, or
+    /// turns the envelope's `error` into a [`CliError`] by status.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        body: Option<&impl Serialize>,
+    ) -> Result<T, CliError> {
+        let mut request = self.http.request(method, format!("{}{}", self.todos_url, path)).query(query);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        if let Some(body) = body {
+            let body = serde_json::to_vec(body).map_err(|e| CliError::Server(e.to_string()))?;
+            request = request.header(header::CONTENT_TYPE, "application/json").body(body);
+        }
+
+        let response = request.send().await.map_err(CliError::Connection)?;
+        let status = response.status();
+        let bytes = response.bytes().await.map_err(CliError::Connection)?;
+        let envelope = serde_json::from_slice::<ApiResponse<serde_json::Value>>(&bytes);
+
+        if !status.is_success() {
+            let message = match envelope {
+                Ok(ApiResponse { error: Some(error), .. }) => error,
+                _ => String::from_utf8_lossy(&bytes).trim().to_string(),
+            };
+            return Err(match status {
+                StatusCode::NOT_FOUND => CliError::NotFound(message),
+                status if status.is_client_error() => CliError::Rejected(message),
+                _ => CliError::Server(format!("{} {}", status.as_u16(), message)),
+            });
+        }
+
+        let unexpected = |e: &dyn fmt::Display| {
+            CliError::Server(format!(
+                "unexpected response, check TODO_URL and that the service uses JSON_CASE=snake: {}",
+                e
+            ))
+        };
+        let data = envelope
+            .map_err(|e| unexpected(&e))?
+            .data
+            .ok_or_else(|| unexpected(&"no data in the response"))?;
+        serde_json::from_value(data).map_err(|e| unexpected(&e))
+    }
+}
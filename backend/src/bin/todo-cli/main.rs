@@ -0,0 +1,224 @@
+//! `todo-cli`, a client of the todo API for shell scripts and Home Assistant
+//! shell_commands. Only built with `--features cli-client`.
+//!
+//! The request and response types are the server's own ([`api`]), so the
+//! client cannot drift from the API it talks to.
+
+// Los módulos compartidos traen lo que el servidor usa y el cliente no
+#[allow(dead_code)]
+#[path = "../../api.rs"]
+mod api;
+#[allow(dead_code)]
+#[path = "../../timestamp.rs"]
+mod timestamp;
+mod client;
+
+use chrono_tz::Tz;
+use std::process::ExitCode;
+use crate::{
+    api::{CreateTodo, PatchTodo, Todo},
+    client::{CliError, Client, Settings},
+};
+
+const USAGE: &str = "Usage: todo-cli [--json] <command>
+
+Commands:
+  add <title> [--due <when>] [--description <text>]
+          Create a todo; <when> is a date, a timestamp or a phrase such as \"tomorrow\"
+  list [--status active|completed|all] [--limit <n>]
+          List todos, newest first (all of them by default)
+  done <id>
+          Mark a todo as completed
+  rm <id>
+          Delete a todo
+
+<id> is a full id or its first characters, as list shows them.
+--json prints JSON instead of a table.
+
+Settings come from the environment, or else from ~/.config/ha-todo/config.toml
+(keys url, api_key and tz):
+  TODO_URL       Base URL of the service, with any BASE_PATH (default http://localhost:8080)
+  TODO_API_KEY   Sent as a bearer token, for a proxy in front of the service
+  TODO_TZ        IANA timezone for due dates sent and shown (default UTC)
+
+Exit codes: 64 usage, 65 rejected by the server, 66 not found,
+69 server unreachable, 70 server error, 78 invalid settings";
+
+enum Status {
+    Active,
+    Completed,
+    All,
+}
+
+enum Command {
+    Add { title: String, due: Option<String>, description: Option<String> },
+    List { status: Status, limit: Option<u32> },
+    Done { id: String },
+    Remove { id: String },
+}
+
+impl Command {
+    /// Parses the command line (without the program name and `--json`);
+    /// `None` asks for the usage.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, CliError> {
+        let usage = |message: String| CliError::Usage(format!("{}\n\n{}", message, USAGE));
+        let Some(command) = args.next() else {
+            return Err(usage("A command is required".to_string()));
+        };
+        let value = |args: &mut dyn Iterator<Item = String>, flag: &str| {
+            args.next().ok_or_else(|| usage(format!("{} needs a value", flag)))
+        };
+
+        let command = match command.as_str() {
+            "help" | "--help" | "-h" => return Ok(None),
+            "add" => {
+                let (mut title, mut due, mut description) = (None, None, None);
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--due" => due = Some(value(&mut args, "--due")?),
+                        "--description" => description = Some(value(&mut args, "--description")?),
+                        flag if flag.starts_with("--") => return Err(usage(format!("Unknown argument '{}'", flag))),
+                        _ if title.is_none() => title = Some(arg),
+                        _ => return Err(usage(format!("Unexpected '{}', quote a title with spaces", arg))),
+                    }
+                }
+                let title = title.ok_or_else(|| usage("add needs a title".to_string()))?;
+                Command::Add { title, due, description }
+            }
+            "list" => {
+                let (mut status, mut limit) = (Status::All, None);
+                while let Some(arg) = args.next() {
+                    match arg.as_str() {
+                        "--status" => {
+                            status = match value(&mut args, "--status")?.as_str() {
+                                "active" => Status::Active,
+                                "completed" => Status::Completed,
+                                "all" => Status::All,
+                                other => {
+                                    return Err(usage(format!(
+                                        "Unknown status '{}', expected active, completed or all",
+                                        other
+                                    )));
+                                }
+                            }
+                        }
+                        "--limit" => {
+                            let text = value(&mut args, "--limit")?;
+                            limit = Some(text.parse().map_err(|_| usage(format!("Invalid limit '{}'", text)))?);
+                        }
+                        other => return Err(usage(format!("Unknown argument '{}'", other))),
+                    }
+                }
+                Command::List { status, limit }
+            }
+            "done" | "rm" => {
+                let id = args.next().ok_or_else(|| usage(format!("{} needs a todo id", command)))?;
+                if let Some(extra) = args.next() {
+                    return Err(usage(format!("Unexpected '{}'", extra)));
+                }
+                if command == "done" { Command::Done { id } } else { Command::Remove { id } }
+            }
+            other => return Err(usage(format!("Unknown command '{}'", other))),
+        };
+        Ok(Some(command))
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let (json, args): (Vec<_>, Vec<_>) = std::env::args().skip(1).partition(|arg| arg == "--json");
+    let result = match Command::parse(args.into_iter()) {
+        Ok(Some(command)) => run(command, !json.is_empty()).await,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            e.exit_code()
+        }
+    }
+}
+
+async fn run(command: Command, json: bool) -> Result<(), CliError> {
+    let settings = Settings::load()?;
+    let tz = match settings.tz.as_deref() {
+        None => Tz::UTC,
+        Some(tz) => tz
+            .parse()
+            .map_err(|_| CliError::Config(format!("Unknown timezone '{}' in TODO_TZ or tz", tz)))?,
+    };
+    let client = Client::new(&settings)?;
+
+    match command {
+        Command::Add { title, due, description } => {
+            let todo = CreateTodo {
+                title,
+                description,
+                completed: None,
+                due_date: due,
+                tz: settings.tz.clone(),
+            };
+            print_todos(&[client.create(&todo).await?], json, tz);
+        }
+        Command::List { status, limit } => {
+            let filter = match status {
+                Status::Active => Some("completed:false"),
+                Status::Completed => Some("completed:true"),
+                Status::All => None,
+            };
+            print_todos(&client.list(filter, 1, limit).await?, json, tz);
+        }
+        Command::Done { id } => {
+            let id = client.resolve(&id).await?;
+            let patch = PatchTodo {
+                title: None,
+                description: None,
+                completed: Some(true),
+                due_date: None,
+                tz: None,
+            };
+            print_todos(&[client.patch(id, &patch).await?], json, tz);
+        }
+        Command::Remove { id } => {
+            let id = client.resolve(&id).await?;
+            client.delete(id).await?;
+            if json {
+                println!("{}", serde_json::json!({ "deleted": id }));
+            } else {
+                println!("Deleted {}", id);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A table with short ids and due dates in `tz`, or the todos as JSON.
+fn print_todos(todos: &[Todo], json: bool, tz: Tz) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(todos).unwrap_or_default());
+        return;
+    }
+    if todos.is_empty() {
+        println!("No todos");
+        return;
+    }
+    println!("{:<8}  {:<4}  {:<16}  TITLE", "ID", "DONE", "DUE");
+    for todo in todos {
+        let due = todo
+            .due_date
+            .map(|due| due.with_timezone(&tz).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        println!(
+            "{:<8}  {:<4}  {:<16}  {}",
+            &todo.id.to_string()[..8],
+            if todo.completed { "yes" } else { "" },
+            due,
+            todo.title
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Optional camelCase field names for the JSON API.
+//!
+//! The types keep their snake_case serde names; with `JSON_CASE=camel` a
+//! middleware rewrites the object keys of JSON bodies under `/api/v1`
+//! (camelCase to snake_case on the way in, the reverse on the way out) and
+//! the OpenAPI document is rewritten to match. Keys already in snake_case are
+//! accepted either way, so both casings work on input.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header::{CONTENT_LENGTH, CONTENT_TYPE}, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{Map, Value};
+use std::str::FromStr;
+use crate::error::AppError;
+
+/// Same as axum's default body limit, which the rewritten request still goes
+/// through afterwards.
+const REQUEST_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JsonCase {
+    Snake,
+    Camel,
+}
+
+impl FromStr for JsonCase {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "snake" => Ok(JsonCase::Snake),
+            "camel" => Ok(JsonCase::Camel),
+            _ => Err("JSON_CASE must be snake or camel"),
+        }
+    }
+}
+
+/// Rewrites JSON bodies between the camelCase used by clients and the
+/// snake_case used by the handlers. Only installed when `JSON_CASE=camel`.
+pub async fn camel_case_json(request: Request, next: Next) -> Response {
+    if !request.uri().path().starts_with("/api/v1/") {
+        return next.run(request).await;
+    }
+
+    let request = if is_json(request.headers()) {
+        let (mut parts, body) = request.into_parts();
+        let bytes = match to_bytes(body, REQUEST_BODY_LIMIT).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return AppError::ValidationError(format!("Failed to read the request body: {}", e))
+                    .into_response()
+            }
+        };
+        // Un JSON inválido se deja tal cual para que el extractor dé su error
+        let bytes = match serde_json::from_slice::<Value>(&bytes) {
+            Ok(value) => serde_json::to_vec(&rename_keys(value, camel_to_snake)).unwrap_or_default().into(),
+            Err(_) => bytes,
+        };
+        parts.headers.remove(CONTENT_LENGTH);
+        Request::from_parts(parts, Body::from(bytes))
+    } else {
+        request
+    };
+
+    let response = next.run(request).await;
+    if !is_json(response.headers()) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return AppError::InternalError(format!("Failed to read the response body: {}", e)).into_response(),
+    };
+    let bytes = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(value) => serde_json::to_vec(&rename_keys(value, snake_to_camel)).unwrap_or_default().into(),
+        Err(_) => bytes,
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Renames the properties, `required` lists and examples of every schema in
+/// the OpenAPI document to camelCase.
+pub fn document(openapi: &mut utoipa::openapi::OpenApi) {
+    let Ok(mut value) = serde_json::to_value(&*openapi) else {
+        return;
+    };
+    rename_schemas(&mut value);
+    if let Ok(renamed) = serde_json::from_value(value) {
+        *openapi = renamed;
+    }
+}
+
+fn rename_schemas(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (key, child) in object.iter_mut() {
+                match key.as_str() {
+                    "properties" => {
+                        if let Value::Object(properties) = child {
+                            *properties = std::mem::take(properties)
+                                .into_iter()
+                                .map(|(name, schema)| (snake_to_camel(&name), schema))
+                                .collect();
+                        }
+                    }
+                    "required" => {
+                        if let Value::Array(names) = child {
+                            for name in names.iter_mut() {
+                                if let Value::String(name) = name {
+                                    *name = snake_to_camel(name);
+                                }
+                            }
+                        }
+                    }
+                    "example" => *child = rename_keys(std::mem::take(child), snake_to_camel),
+                    _ => {}
+                }
+                rename_schemas(child);
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(rename_schemas),
+        _ => {}
+    }
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| {
+            let mime = mime.trim();
+            mime.eq_ignore_ascii_case("application/json") || mime.ends_with("+json")
+        })
+}
+
+/// Renames the keys of every object in `value`, recursively.
+fn rename_keys(value: Value, rename: fn(&str) -> String) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(key, value)| (rename(&key), rename_keys(value, rename)))
+                .collect::<Map<_, _>>(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(|item| rename_keys(item, rename)).collect()),
+        other => other,
+    }
+}
+
+fn snake_to_camel(key: &str) -> String {
+    let mut result = String::with_capacity(key.len());
+    let mut upper = false;
+    for c in key.chars() {
+        match c {
+            '_' if !result.is_empty() => upper = true,
+            c if upper => {
+                result.extend(c.to_uppercase());
+                upper = false;
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+pub(crate) fn camel_to_snake(key: &str) -> String {
+    let mut result = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            if !result.is_empty() {
+                result.push('_');
+            }
+            result.push(c.to_ascii_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::testing;
+    use super::{camel_to_snake, rename_keys, snake_to_camel};
+
+    #[test]
+    fn converts_between_the_casings() {
+        for (snake, camel) in [("due_date", "dueDate"), ("created_at", "createdAt"), ("title", "title"), ("no_due_date_yet", "noDueDateYet")] {
+            assert_eq!(snake_to_camel(snake), camel);
+            assert_eq!(camel_to_snake(camel), snake);
+        }
+        assert_eq!(snake_to_camel("_private"), "_private");
+        assert_eq!(camel_to_snake("due_date"), "due_date");
+
+        let nested = json!({"todo_list": [{"due_date": null, "tags": ["keep_values"]}]});
+        assert_eq!(rename_keys(nested, snake_to_camel), json!({"todoList": [{"dueDate": null, "tags": ["keep_values"]}]}));
+    }
+
+    #[sqlx::test]
+    async fn camel_case_instances_speak_camel_case_both_ways(db: PgPool) {
+        let state = testing::state(db, testing::config(&[("JSON_CASE", "camel")]));
+
+        let create = json!({"title": "Call", "dueDate": "2030-06-01T10:00:00Z"});
+        let response = testing::send(&state, testing::json_request(Method::POST, "/api/v1/todos", &create)).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = testing::json(response).await;
+        let todo = body["data"].as_object().unwrap();
+        let mut keys = todo.keys().map(String::as_str).collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, ["completed", "createdAt", "description", "dueDate", "id", "title", "updatedAt"]);
+        assert_eq!(todo["dueDate"], "2030-06-01T10:00:00.000Z");
+
+        // snake_case también se acepta en la entrada
+        let uri = format!("/api/v1/todos/{}", todo["id"].as_str().unwrap());
+        let replace = json!({"title": "Call", "description": null, "completed": true, "due_date": null});
+        let body = testing::json(testing::send(&state, testing::json_request(Method::PUT, &uri, &replace)).await).await;
+        assert_eq!((&body["data"]["completed"], &body["data"]["dueDate"]), (&json!(true), &json!(null)));
+
+        let openapi = testing::json(testing::send(&state, testing::get("/api-docs/openapi.json")).await).await;
+        let todo = &openapi["components"]["schemas"]["Todo"];
+        assert!(todo["properties"].get("createdAt").is_some() && todo["properties"].get("created_at").is_none());
+        assert!(todo["required"].as_array().unwrap().contains(&json!("createdAt")));
+        assert_eq!(openapi["components"]["schemas"]["CreateTodo"]["example"]["dueDate"], "2030-01-01T18:00:00Z");
+    }
+
+    #[sqlx::test]
+    async fn snake_case_stays_the_default(db: PgPool) {
+        let state = testing::state(db, testing::config(&[]));
+
+        let create = json!({"title": "Call", "due_date": "2030-06-01T10:00:00Z"});
+        let body = testing::json(testing::send(&state, testing::json_request(Method::POST, "/api/v1/todos", &create)).await).await;
+        assert_eq!(body["data"]["due_date"], "2030-06-01T10:00:00.000Z");
+        assert!(body["data"].get("createdAt").is_none());
+
+        let openapi = testing::json(testing::send(&state, testing::get("/api-docs/openapi.json")).await).await;
+        assert!(openapi["components"]["schemas"]["Todo"]["properties"].get("created_at").is_some());
+    }
+}
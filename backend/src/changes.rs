@@ -0,0 +1,416 @@
+//! Long-polling feed of todo changes for clients that cannot keep an SSE or
+//! WebSocket connection open.
+//!
+//! A trigger appends every insert, update and delete on `todos` to
+//! `todo_changes` and sends a `NOTIFY`; the cursor is the `seq` of the last
+//! change a client has seen. [`run_listener`] turns the notifications into a
+//! wake-up for the requests parked in [`get_changes`], so changes made by any
+//! replica are seen by all of them.
+//!
+//! Writers do not wait for each other, so `seq` does not follow commit order:
+//! a transaction can commit seq 11 while the one holding seq 10 is still
+//! running. Changes are therefore returned in the order of the transaction
+//! that wrote them, then `seq`, and only once every older transaction has
+//! finished (`pg_snapshot_xmin`). Whatever commits later comes after the
+//! cursor, so no change is ever skipped. The watermark covers the whole
+//! server, so a transaction left open on any of its databases holds the feed
+//! back until it ends.
+
+use axum::{
+    extract::{rejection::QueryRejection, Json, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, FromRow};
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc},
+};
+use tokio::{
+    sync::{watch, Semaphore},
+    time::Instant,
+};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::{
+    model::{AppState, Todo},
+    response::ApiResponse,
+    error::AppError
+};
+
+const CHANNEL: &str = "todo_changes";
+const MAX_WAIT_SECS: u64 = 60;
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3600);
+/// How often a parked request looks again while committed changes wait for
+/// an older transaction, which sends no notification when it ends without
+/// touching todos.
+const PENDING_RECHECK: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Changes that have been committed and follow every running transaction.
+const SETTLED: &str = "txid < pg_snapshot_xmin(pg_current_snapshot())::text::bigint";
+
+/// Wakes the parked long-poll requests and bounds how many there can be.
+pub struct ChangeFeed {
+    signal: watch::Sender<()>,
+    waiters: Semaphore,
+}
+
+impl ChangeFeed {
+    pub fn new(max_waiters: usize) -> Self {
+        ChangeFeed {
+            signal: watch::Sender::new(()),
+            waiters: Semaphore::new(max_waiters),
+        }
+    }
+
+    /// Makes every parked request look for changes again.
+    pub fn wake(&self) {
+        self.signal.send_replace(());
+    }
+}
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct ChangesQuery {
+    #[schema(example = 1042)]
+    /// Cursor returned by the previous call; omit it to get the current cursor
+    since: Option<i64>,
+    #[schema(example = 25)]
+    /// Seconds to wait for a change when there is none yet (default 0, max 60)
+    wait: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, FromRow)]
+pub struct TodoChange {
+    /// Cursor of this change
+    #[schema(example = 1043)]
+    #[sqlx(rename = "seq")]
+    pub cursor: i64,
+    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
+    pub todo_id: Uuid,
+    /// `created`, `updated` or `deleted` (archived todos are deleted from
+    /// the live list, restored ones created again)
+    #[schema(example = "updated")]
+    pub op: String,
+    #[schema(example = "2026-01-01T10:00:00.000Z")]
+    #[serde(with = "crate::timestamp")]
+    pub changed_at: DateTime<Utc>,
+    /// Current state of the todo (not as of this change), absent once it no
+    /// longer exists
+    #[sqlx(skip)]
+    pub todo: Option<Todo>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Changes {
+    /// Changes after `since`, oldest first
+    pub changes: Vec<TodoChange>,
+    /// Cursor to pass as `since` on the next call
+    #[schema(example = 1043)]
+    pub cursor: i64,
+    /// More changes are available right away
+    #[schema(example = false)]
+    pub has_more: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/changes",
+    params(ChangesQuery),
+    responses(
+        (status = 200, description = "Changes after the cursor, or none with the same cursor once `wait` runs out", body = ApiResponseChanges),
+        (status = 400, description = "Invalid query", body = ApiResponseString),
+        (status = 410, description = "The cursor is older than CHANGES_RETENTION_DAYS, reload the full list", body = ApiResponseString),
+        (status = 503, description = "Too many requests are already waiting for changes", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn get_changes(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<ChangesQuery>, QueryRejection>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(query) = query?;
+    let limit = state.config.pagination_max_limit;
+
+    let Some(since) = query.since else {
+        let cursor = sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT seq FROM todo_changes WHERE {} ORDER BY txid DESC, seq DESC LIMIT 1",
+            SETTLED
+        ))
+        .fetch_optional(&state.db)
+        .await?
+        .unwrap_or(0);
+        return Ok((StatusCode::OK, Json(ApiResponse::success(Changes { changes: Vec::new(), cursor, has_more: false }))));
+    };
+    let position = match since {
+        ..=0 => Some((0, 0)),
+        _ => cursor_position(&state, since).await?,
+    };
+    let Some(position) = position else {
+        return Err(AppError::Gone(format!(
+            "Cursor {} has expired, reload the todos and start again without since",
+            since
+        )));
+    };
+
+    let wait = std::time::Duration::from_secs(query.wait.unwrap_or(0).min(MAX_WAIT_SECS));
+    let deadline = Instant::now() + wait;
+    let mut signal = state.changes.signal.subscribe();
+    let mut permit = None;
+
+    // Si el cliente se desconecta, axum descarta este future y con él el permiso
+    loop {
+        signal.borrow_and_update();
+        let (mut changes, pending) = changes_after(&state, position, limit).await?;
+        let stopping = state.draining.load(Ordering::Acquire) || state.shutting_down.load(Ordering::Acquire);
+
+        if !changes.is_empty() || stopping || Instant::now() >= deadline {
+            let has_more = changes.len() > limit as usize;
+            changes.truncate(limit as usize);
+            let cursor = changes.last().map_or(since, |change| change.cursor);
+            return Ok((StatusCode::OK, Json(ApiResponse::success(Changes { changes, cursor, has_more }))));
+        }
+
+        if permit.is_none() {
+            permit = Some(state.changes.waiters.try_acquire().map_err(|_| {
+                AppError::ServiceUnavailable("Too many clients are waiting for changes, retry later".to_string())
+            })?);
+        }
+        let recheck = if pending { Instant::now() + PENDING_RECHECK } else { deadline };
+        tokio::select! {
+            _ = signal.changed() => {}
+            _ = tokio::time::sleep_until(recheck.min(deadline)) => {}
+        }
+    }
+}
+
+/// Where the change with this cursor sits in the feed, as `(txid, seq)`;
+/// `None` once it has been pruned, since changes after it may be gone too.
+async fn cursor_position(state: &AppState, cursor: i64) -> Result<Option<(i64, i64)>, sqlx::Error> {
+    sqlx::query_as("SELECT txid, seq FROM todo_changes WHERE seq = $1")
+        .bind(cursor)
+        .fetch_optional(&state.db)
+        .await
+}
+
+/// Up to `limit + 1` settled changes after `position`, with the current state
+/// of their todos. The flag tells, when there are none, whether committed
+/// changes are waiting for an older transaction to finish.
+async fn changes_after(
+    state: &AppState,
+    (txid, seq): (i64, i64),
+    limit: u32,
+) -> Result<(Vec<TodoChange>, bool), sqlx::Error> {
+    let mut changes = sqlx::query_as::<_, TodoChange>(&format!(
+        r#"
+        SELECT seq, todo_id, op, changed_at
+        FROM todo_changes
+        WHERE (txid, seq) > ($1, $2) AND {}
+        ORDER BY txid, seq
+        LIMIT $3
+        "#,
+        SETTLED
+    ))
+    .bind(txid)
+    .bind(seq)
+    .bind(i64::from(limit) + 1)
+    .fetch_all(&state.db)
+    .await?;
+    if changes.is_empty() {
+        let pending = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM todo_changes WHERE (txid, seq) > ($1, $2))")
+            .bind(txid)
+            .bind(seq)
+            .fetch_one(&state.db)
+            .await?;
+        return Ok((changes, pending));
+    }
+
+    let ids = changes.iter().map(|change| change.todo_id).collect::<Vec<_>>();
+    let todos = sqlx::query_as::<_, Todo>(
+        r#"
+        SELECT id, title, description, completed, due_date, created_at, updated_at
+        FROM todos
+        WHERE id = ANY($1)
+        "#
+    )
+    .bind(&ids)
+    .fetch_all(&state.db)
+    .await?
+    .into_iter()
+    .map(|todo| (todo.id, todo))
+    .collect::<HashMap<_, _>>();
+
+    for change in &mut changes {
+        change.todo = todos.get(&change.todo_id).cloned();
+    }
+    Ok((changes, false))
+}
+
+/// Forwards `NOTIFY todo_changes` to the parked requests and prunes changes
+/// older than `CHANGES_RETENTION_DAYS`, until shutdown.
+pub async fn run_listener(state: Arc<AppState>) {
+    let mut delay = std::time::Duration::from_secs(1);
+    let mut listener = loop {
+        let connected = async {
+            let mut listener = PgListener::connect_with(&state.db).await?;
+            listener.listen(CHANNEL).await?;
+            Ok::<_, sqlx::Error>(listener)
+        };
+        match connected.await {
+            Ok(listener) => break listener,
+            Err(e) => {
+                warn!("Could not listen for todo changes, retrying in {:?}: {}", delay, e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(std::time::Duration::from_secs(30));
+            }
+        }
+    };
+    info!("👂 Listening for todo changes");
+
+    let mut prune = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        if state.shutting_down.load(Ordering::Acquire) {
+            return;
+        }
+        tokio::select! {
+            // recv() se reconecta solo; tras un corte se despierta igualmente
+            // porque las notificaciones perdidas no se reenvían
+            received = listener.recv() => {
+                if let Err(e) = received {
+                    warn!("Lost the todo changes listener connection: {}", e);
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+                state.changes.wake();
+            }
+            // Hasta que las migraciones terminen (modo lazy) la tabla puede no existir
+            _ = prune.tick(), if state.migrations_done.load(Ordering::Acquire) => {
+                if let Err(e) = prune_changes(&state).await {
+                    warn!("Pruning todo changes failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Deletes changes older than the retention period, always keeping the last
+/// one in feed order so the current cursor stays valid.
+async fn prune_changes(state: &AppState) -> Result<(), sqlx::Error> {
+    let cutoff = state.clock.now() - Duration::days(state.config.changes_retention_days.into());
+    let pruned = sqlx::query(
+        r#"
+        DELETE FROM todo_changes
+        WHERE changed_at < $1
+          AND (txid, seq) < (SELECT txid, seq FROM todo_changes ORDER BY txid DESC, seq DESC LIMIT 1)
+        "#
+    )
+    .bind(cutoff)
+    .execute(&state.db)
+    .await?
+    .rows_affected();
+
+    if pruned > 0 {
+        info!("🧹 Pruned {} todo changes older than {}", pruned, cutoff);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use sqlx::PgPool;
+    use uuid::Uuid;
+    use crate::{clock::MockClock, testing};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    async fn ops(db: &PgPool) -> Vec<String> {
+        sqlx::query_scalar("SELECT op FROM todo_changes ORDER BY seq").fetch_all(db).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn prunes_changes_older_than_the_retention_but_keeps_the_newest(db: PgPool) {
+        let clock = MockClock::new(now());
+        let state = testing::state_at(db.clone(), testing::config(&[("CHANGES_RETENTION_DAYS", "7")]), &clock);
+        let cutoff = now() - Duration::days(7);
+        for (op, changed_at) in [("created", cutoff - Duration::seconds(1)), ("updated", cutoff)] {
+            sqlx::query("INSERT INTO todo_changes (todo_id, op, changed_at) VALUES ($1, $2, $3)")
+                .bind(Uuid::new_v4())
+                .bind(op)
+                .bind(changed_at)
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+
+        super::prune_changes(&state).await.unwrap();
+        assert_eq!(ops(&db).await, ["updated"]);
+
+        // Ya fuera de la retención, pero es el cursor actual
+        clock.advance(Duration::days(1));
+        super::prune_changes(&state).await.unwrap();
+        assert_eq!(ops(&db).await, ["updated"]);
+    }
+
+    /// Polls the feed, returning the titles of the changed todos and the new
+    /// cursor. Expected changes are waited for, since transactions of tests
+    /// running in parallel also hold the watermark back for a moment.
+    async fn poll(state: &std::sync::Arc<crate::model::AppState>, since: i64, wait: u64) -> (Vec<String>, i64) {
+        let uri = format!("/api/v1/todos/changes?since={}&wait={}", since, wait);
+        let response = testing::send(state, testing::get(&uri)).await;
+        let feed = &testing::json(response).await["data"];
+        let titles = feed["changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|change| change["todo"]["title"].as_str().unwrap().to_string())
+            .collect();
+        (titles, feed["cursor"].as_i64().unwrap())
+    }
+
+    #[sqlx::test]
+    async fn writers_do_not_wait_for_each_other_and_no_change_is_skipped(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+
+        let mut older = db.begin().await.unwrap();
+        sqlx::query("INSERT INTO todos (title) VALUES ('older')").execute(&mut *older).await.unwrap();
+        // Antes el trigger bloqueaba aquí hasta el commit de la otra transacción
+        tokio::time::timeout(std::time::Duration::from_secs(5), testing::insert_todo(&db, "newer"))
+            .await
+            .expect("a writer waited for another one");
+
+        // "newer" ya está confirmado, pero devolverlo movería el cursor más
+        // allá del cambio de "older", que aún puede confirmarse
+        assert_eq!(poll(&state, 0, 0).await, (Vec::<String>::new(), 0));
+        let current = testing::json(testing::send(&state, testing::get("/api/v1/todos/changes")).await).await;
+        assert_eq!(current["data"]["cursor"], 0);
+
+        older.commit().await.unwrap();
+        let (titles, cursor) = poll(&state, 0, 10).await;
+        assert_eq!(titles, ["older", "newer"]);
+        assert_eq!(poll(&state, cursor, 0).await, (Vec::<String>::new(), cursor));
+    }
+
+    #[sqlx::test]
+    async fn changes_follow_transaction_order_rather_than_seq(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        testing::insert_todo(&db, "first").await;
+        let (titles, cursor) = poll(&state, 0, 10).await;
+        assert_eq!(titles, ["first"]);
+
+        // La transacción más antigua obtiene su seq después que la más nueva
+        let mut older = db.begin().await.unwrap();
+        sqlx::query("SELECT pg_current_xact_id()").execute(&mut *older).await.unwrap();
+        testing::insert_todo(&db, "committed first").await;
+        sqlx::query("INSERT INTO todos (title) VALUES ('committed last')").execute(&mut *older).await.unwrap();
+        assert_eq!(poll(&state, cursor, 0).await, (Vec::<String>::new(), cursor));
+
+        older.commit().await.unwrap();
+        let (titles, _) = poll(&state, cursor, 10).await;
+        assert_eq!(titles, ["committed last", "committed first"]);
+    }
+}
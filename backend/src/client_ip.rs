@@ -0,0 +1,273 @@
+//! Resolution of the client address when the service runs behind reverse
+//! proxies.
+//!
+//! `X-Forwarded-For` and `Forwarded` are only believed when the connection
+//! comes from one of `TRUSTED_PROXIES`: the chain is walked from the right,
+//! skipping trusted hops, and the first untrusted one is the client. From any
+//! other peer the headers are ignored, so a client cannot spoof its address.
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::FORWARDED, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use crate::model::AppState;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Address of the client that made the request, after the trusted proxies;
+/// stored in the request extensions by [`resolve_client_ip`].
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+/// Parses `TRUSTED_PROXIES`: comma-separated CIDRs, where a bare address is
+/// a single host.
+pub fn parse_trusted_proxies(value: &str) -> Result<Vec<IpNet>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("TRUSTED_PROXIES has an invalid CIDR {:?}", entry))
+        })
+        .collect()
+}
+
+/// Stores the [`ClientIp`] of every request. Requests without connection
+/// info (never the case under `axum::serve`) are left without one.
+pub async fn resolve_client_ip(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| peer.ip());
+    if let Some(peer) = peer {
+        let client = client_ip(peer, request.headers(), &state.config.trusted_proxies);
+        request.extensions_mut().insert(ClientIp(client));
+    }
+    next.run(request).await
+}
+
+/// `X-Forwarded-For` takes precedence over `Forwarded` when both are sent.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(&canonical(*ip)));
+    if !is_trusted(&peer) {
+        return canonical(peer);
+    }
+
+    let hops = if headers.contains_key(X_FORWARDED_FOR) {
+        header_list(headers, X_FORWARDED_FOR)
+            .into_iter()
+            .map(parse_hop)
+            .collect::<Vec<_>>()
+    } else {
+        header_list(headers, FORWARDED.as_str())
+            .into_iter()
+            .map(forwarded_for)
+            .collect()
+    };
+
+    // Un salto mal formado corta la cadena: lo que hay a su izquierda no es fiable
+    let mut client = peer;
+    for hop in hops.into_iter().rev() {
+        if !is_trusted(&client) {
+            break;
+        }
+        match hop {
+            Some(hop) => client = hop,
+            None => break,
+        }
+    }
+    canonical(client)
+}
+
+/// Comma-separated elements of every occurrence of a header, in order.
+fn header_list<'a>(headers: &'a HeaderMap, name: &str) -> Vec<&'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .map(str::trim)
+        .collect()
+}
+
+/// The `for=` node of one `Forwarded` element (RFC 7239).
+fn forwarded_for(element: &str) -> Option<IpAddr> {
+    element
+        .split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+        .and_then(|(_, node)| parse_hop(node.trim().trim_matches('"')))
+}
+
+/// Accepts `1.2.3.4`, `1.2.3.4:80`, `2001:db8::1` and `[2001:db8::1]:80`;
+/// `unknown`, obfuscated identifiers and garbage are `None`.
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    if let Ok(ip) = hop.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(address) = hop.parse::<SocketAddr>() {
+        return Some(address.ip());
+    }
+    hop.strip_prefix('[')?.strip_suffix(']')?.parse().ok()
+}
+
+/// IPv4 addresses mapped into IPv6 (`::ffff:10.0.0.2`, as seen on dual-stack
+/// sockets) compare as IPv4.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        extract::{ConnectInfo, Request},
+        http::{header::FORWARDED, HeaderMap, HeaderName, HeaderValue},
+        middleware,
+        routing::get,
+        Extension, Router,
+    };
+    use ipnet::IpNet;
+    use std::net::{IpAddr, SocketAddr};
+    use tower::ServiceExt;
+    use super::{client_ip, parse_trusted_proxies, ClientIp, X_FORWARDED_FOR};
+    use crate::testing;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn trusted() -> Vec<IpNet> {
+        parse_trusted_proxies("10.0.0.0/8, fd00::/8").unwrap()
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(HeaderName::from_bytes(name.as_bytes()).unwrap(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn resolve(peer: &str, pairs: &[(&str, &str)]) -> IpAddr {
+        client_ip(ip(peer), &headers(pairs), &trusted())
+    }
+
+    #[test]
+    fn parses_cidrs_and_bare_addresses() {
+        let proxies = parse_trusted_proxies(" 10.0.0.0/8 ,192.0.2.1,, 2001:db8::/32 ,::1").unwrap();
+        let expected = ["10.0.0.0/8", "192.0.2.1/32", "2001:db8::/32", "::1/128"].map(|net| net.parse::<IpNet>().unwrap());
+        assert_eq!(proxies, expected);
+        assert_eq!(parse_trusted_proxies("").unwrap(), []);
+        assert_eq!(
+            parse_trusted_proxies("10.0.0.0/8, 10.0.0.0/33").unwrap_err(),
+            "TRUSTED_PROXIES has an invalid CIDR \"10.0.0.0/33\""
+        );
+        assert!(parse_trusted_proxies("proxy.local").is_err());
+    }
+
+    #[test]
+    fn ignores_the_headers_from_untrusted_peers() {
+        let spoofed = [(X_FORWARDED_FOR, "198.51.100.1"), (FORWARDED.as_str(), "for=198.51.100.2")];
+        assert_eq!(resolve("203.0.113.9", &spoofed), ip("203.0.113.9"));
+        assert_eq!(resolve("2001:db8::9", &spoofed), ip("2001:db8::9"));
+        // Sin TRUSTED_PROXIES nadie es de confianza, ni siquiera la red interna
+        assert_eq!(client_ip(ip("10.0.0.2"), &headers(&spoofed), &[]), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn takes_the_rightmost_untrusted_hop() {
+        assert_eq!(resolve("10.0.0.2", &[(X_FORWARDED_FOR, "203.0.113.7")]), ip("203.0.113.7"));
+        // El cliente puede anteponer lo que quiera; cuenta lo que añadieron los proxies
+        assert_eq!(
+            resolve("10.0.0.2", &[(X_FORWARDED_FOR, "198.51.100.1, 203.0.113.7, 10.0.0.3")]),
+            ip("203.0.113.7")
+        );
+        // Varias cabeceras se leen como una sola lista, en orden
+        assert_eq!(
+            resolve("10.0.0.2", &[(X_FORWARDED_FOR, "198.51.100.1"), (X_FORWARDED_FOR, "203.0.113.7,10.0.0.3")]),
+            ip("203.0.113.7")
+        );
+        // Si todos los saltos son de confianza, el cliente es el primero
+        assert_eq!(resolve("10.0.0.2", &[(X_FORWARDED_FOR, "10.1.1.1, 10.0.0.3")]), ip("10.1.1.1"));
+        assert_eq!(resolve("10.0.0.2", &[]), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn stops_at_malformed_hops() {
+        assert_eq!(resolve("10.0.0.2", &[(X_FORWARDED_FOR, "")]), ip("10.0.0.2"));
+        assert_eq!(resolve("10.0.0.2", &[(X_FORWARDED_FOR, "not-an-ip")]), ip("10.0.0.2"));
+        assert_eq!(resolve("10.0.0.2", &[(X_FORWARDED_FOR, "203.0.113.7, unknown")]), ip("10.0.0.2"));
+        // Lo que hay a la izquierda de un salto roto no se cree
+        assert_eq!(resolve("10.0.0.2", &[(X_FORWARDED_FOR, "198.51.100.1, garbage, 10.0.0.3")]), ip("10.0.0.3"));
+        assert_eq!(resolve("10.0.0.2", &[(X_FORWARDED_FOR, "203.0.113.7/24")]), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn accepts_ports_and_ipv6_in_every_spelling() {
+        for (hop, client) in [
+            ("203.0.113.7:51234", "203.0.113.7"),
+            ("2001:db8::7", "2001:db8::7"),
+            ("[2001:db8::7]", "2001:db8::7"),
+            ("[2001:db8::7]:51234", "2001:db8::7"),
+            ("::ffff:203.0.113.7", "203.0.113.7"),
+        ] {
+            assert_eq!(resolve("10.0.0.2", &[(X_FORWARDED_FOR, hop)]), ip(client), "{}", hop);
+        }
+        // Proxies en IPv6 y direcciones IPv4 mapeadas en un socket dual
+        assert_eq!(resolve("fd00::2", &[(X_FORWARDED_FOR, "203.0.113.7, fd00::3")]), ip("203.0.113.7"));
+        assert_eq!(resolve("::ffff:10.0.0.2", &[(X_FORWARDED_FOR, "::ffff:10.0.0.3")]), ip("10.0.0.3"));
+        assert_eq!(resolve("::ffff:203.0.113.9", &[(X_FORWARDED_FOR, "198.51.100.1")]), ip("203.0.113.9"));
+    }
+
+    #[test]
+    fn reads_forwarded_when_there_is_no_x_forwarded_for() {
+        let forwarded = |value: &str| resolve("10.0.0.2", &[(FORWARDED.as_str(), value)]);
+        assert_eq!(forwarded("for=203.0.113.7"), ip("203.0.113.7"));
+        assert_eq!(forwarded("For=\"[2001:db8::7]:4711\";proto=https"), ip("2001:db8::7"));
+        assert_eq!(forwarded("proto=https;by=10.0.0.2; for=203.0.113.7"), ip("203.0.113.7"));
+        assert_eq!(forwarded("for=198.51.100.1, for=203.0.113.7, for=10.0.0.3"), ip("203.0.113.7"));
+        assert_eq!(forwarded("for=unknown"), ip("10.0.0.2"));
+        assert_eq!(forwarded("for=_hidden, for=10.0.0.3"), ip("10.0.0.3"));
+        assert_eq!(forwarded("proto=https"), ip("10.0.0.2"));
+
+        let both = [(FORWARDED.as_str(), "for=198.51.100.2"), (X_FORWARDED_FOR, "203.0.113.7")];
+        assert_eq!(resolve("10.0.0.2", &both), ip("203.0.113.7"));
+    }
+
+    #[tokio::test]
+    async fn stores_the_resolved_client_for_the_rest_of_the_stack() {
+        let state = testing::state(
+            sqlx::PgPool::connect_lazy("postgres://test@localhost/test").unwrap(),
+            testing::config(&[("TRUSTED_PROXIES", "10.0.0.0/8")]),
+        );
+        let app = Router::new()
+            .route("/", get(|Extension(ClientIp(client)): Extension<ClientIp>| async move { client.to_string() }))
+            .layer(middleware::from_fn_with_state(state, super::resolve_client_ip));
+        let send = |peer: &str, forwarded_for: &str| {
+            let mut request = Request::builder().uri("/").header(X_FORWARDED_FOR, forwarded_for).body(Body::empty()).unwrap();
+            request.extensions_mut().insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+            let app = app.clone();
+            async move { String::from_utf8(testing::body(app.oneshot(request).await.unwrap()).await).unwrap() }
+        };
+
+        assert_eq!(send("10.0.0.2:40000", "203.0.113.7").await, "203.0.113.7");
+        assert_eq!(send("198.51.100.1:40000", "203.0.113.7").await, "198.51.100.1");
+    }
+}
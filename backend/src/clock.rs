@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+
+/// Source of the current time for business rules (due-date checks, natural
+/// language dates, export names). Handlers read it once per request and pass
+/// the instant down, so one request never sees two different "nows".
+///
+/// Bookkeeping timestamps (`created_at`, `updated_at`) stay with the database
+/// defaults and trigger.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that stands still until a test sets or advances it. Clones share
+/// the same time, so a test can keep one and give another to the state.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct MockClock(std::sync::Arc<std::sync::Mutex<DateTime<Utc>>>);
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        MockClock(std::sync::Arc::new(std::sync::Mutex::new(now)))
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use super::{Clock, MockClock};
+
+    #[test]
+    fn mock_clock_only_moves_when_told_and_clones_share_the_time() {
+        let start = Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap();
+        let clock = MockClock::new(start);
+        let shared = clock.clone();
+        assert_eq!(shared.now(), start);
+
+        clock.advance(Duration::minutes(90));
+        assert_eq!(shared.now(), start + Duration::minutes(90));
+
+        let later = Utc.with_ymd_and_hms(2031, 6, 15, 0, 0, 0).unwrap();
+        shared.set(later);
+        assert_eq!(clock.now(), later);
+    }
+}
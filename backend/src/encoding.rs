@@ -0,0 +1,243 @@
+use axum::{
+    extract::{Request, State},
+    http::header::{CONTENT_ENCODING, CONTENT_TYPE},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use crate::error::AppError;
+
+/// `Content-Encoding` values accepted on request bodies, matching the
+/// decoders enabled on `RequestDecompressionLayer`.
+const SUPPORTED_ENCODINGS: &[&str] = &["gzip", "zstd", "identity"];
+
+/// Answers requests with an unsupported `Content-Encoding` with a 415 in the
+/// usual envelope before they reach the decompression layer.
+///
+/// Bodies are decompressed before the extractors read them, so the body size
+/// limits apply to the decompressed size and a small compressed bomb cannot
+/// expand past them.
+pub async fn reject_unsupported_encoding(request: Request, next: Next) -> Response {
+    if let Some(encoding) = request.headers().get(CONTENT_ENCODING) {
+        let supported = encoding
+            .to_str()
+            .is_ok_and(|encoding| SUPPORTED_ENCODINGS.contains(&encoding));
+        if !supported {
+            return AppError::UnsupportedMediaType(format!(
+                "Unsupported Content-Encoding {:?}, expected one of: {}",
+                String::from_utf8_lossy(encoding.as_bytes()),
+                SUPPORTED_ENCODINGS.join(", ")
+            ))
+            .into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Media types of the JSON write endpoints.
+pub const JSON: &[&str] = &["application/json"];
+/// `POST /api/v1/todos` also takes one title per line.
+pub const JSON_OR_PLAIN_TEXT: &[&str] = &["application/json", "text/plain"];
+/// `POST /api/v1/quick-add` takes a single line.
+pub const PLAIN_TEXT: &[&str] = &["text/plain"];
+/// `PATCH /api/v1/todos/:id` also takes an RFC 6902 JSON Patch.
+pub const JSON_OR_JSON_PATCH: &[&str] = &["application/json", "application/json-patch+json"];
+
+/// Answers with a 415 in the usual envelope when the `Content-Type` of a
+/// write request is missing or not one of `accepted` (parameters such as
+/// `charset` are ignored), before the body is read. Installed per route with
+/// `from_fn_with_state`; routes taking calendars or form posts do not use it.
+pub async fn require_content_type(
+    State(accepted): State<&'static [&'static str]>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let content_type = request.headers().get(CONTENT_TYPE);
+    let mime = content_type
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(str::trim);
+    if mime.is_some_and(|mime| accepted.iter().any(|accepted| accepted.eq_ignore_ascii_case(mime))) {
+        return next.run(request).await;
+    }
+
+    let problem = match content_type {
+        Some(value) => format!("Unsupported Content-Type {:?}", String::from_utf8_lossy(value.as_bytes())),
+        None => "Missing Content-Type".to_string(),
+    };
+    AppError::UnsupportedMediaType(format!("{}, expected one of: {}", problem, accepted.join(", "))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{header::CONTENT_ENCODING, Method, StatusCode}};
+    use flate2::{write::GzEncoder, Compression};
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use std::io::Write;
+    use crate::testing;
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn bulk_create(body: Vec<u8>, encoding: Option<&str>) -> axum::extract::Request {
+        let mut request = testing::request(Method::POST, "/api/v1/todos", Some("text/plain"), Body::from(body));
+        if let Some(encoding) = encoding {
+            request.headers_mut().insert(CONTENT_ENCODING, encoding.parse().unwrap());
+        }
+        request
+    }
+
+    /// The created todos without what the database assigns.
+    fn contents(body: &Value) -> Vec<Value> {
+        body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|todo| json!([todo["title"], todo["description"], todo["completed"], todo["due_date"]]))
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn compressed_bulk_creates_match_the_uncompressed_one(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let text = (1..=100).map(|n| format!("Todo número {}\n", n)).collect::<String>();
+
+        let plain = testing::json(testing::send(&state, bulk_create(text.clone().into_bytes(), None)).await).await;
+        let gzipped = bulk_create(gzip(text.as_bytes()), Some("gzip"));
+        let gzipped = testing::send(&state, gzipped).await;
+        assert_eq!(gzipped.status(), StatusCode::CREATED);
+        let gzipped = testing::json(gzipped).await;
+        let zstd = bulk_create(zstd::encode_all(text.as_bytes(), 0).unwrap(), Some("zstd"));
+        let zstd = testing::json(testing::send(&state, zstd).await).await;
+
+        assert_eq!(contents(&plain).len(), 100);
+        assert_eq!(contents(&gzipped), contents(&plain));
+        assert_eq!(contents(&zstd), contents(&plain));
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM todos").fetch_one(&db).await.unwrap();
+        assert_eq!(count, 300);
+    }
+
+    #[sqlx::test]
+    async fn the_body_limit_applies_to_the_decompressed_size(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        // Unos pocos KB comprimidos que se expanden a 4 MB
+        let bomb = gzip(&vec![b'\n'; 4 * 1024 * 1024]);
+        assert!(bomb.len() < 16 * 1024);
+
+        let response = testing::send(&state, bulk_create(bomb, Some("gzip"))).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(testing::json(response).await["status"], "error");
+    }
+
+    #[sqlx::test]
+    async fn unsupported_encodings_get_a_415_in_the_envelope(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+
+        let response = testing::send(&state, bulk_create(b"Buy milk".to_vec(), Some("br"))).await;
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let body = testing::json(response).await;
+        assert_eq!(body["error"], "Unsupported Content-Encoding \"br\", expected one of: gzip, zstd, identity");
+
+        let response = testing::send(&state, bulk_create(b"Buy milk".to_vec(), Some("identity"))).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+
+    /// A body each write endpoint accepts with `content_type`.
+    fn valid_body(uri: &str, content_type: &str) -> String {
+        match (uri, content_type.split(';').next().unwrap().trim().to_lowercase().as_str()) {
+            (_, "text/plain") => "Buy milk".to_string(),
+            (_, "application/json-patch+json") => r#"[{"op": "replace", "path": "/title", "value": "Buy milk"}]"#.to_string(),
+            ("/api/v1/todos/import/google-tasks", _) => json!({"items": []}).to_string(),
+            _ => json!({"title": "Buy milk", "description": null, "completed": false, "due_date": null}).to_string(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn write_endpoints_take_only_their_content_types(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[("FEATURES", "quick-add")]));
+        let id = testing::insert_todo(&db, "Existing").await;
+        let item = format!("/api/v1/todos/{}", id);
+        let endpoints = [
+            (Method::POST, "/api/v1/todos", super::JSON_OR_PLAIN_TEXT),
+            (Method::PUT, item.as_str(), super::JSON),
+            (Method::PATCH, item.as_str(), super::JSON_OR_JSON_PATCH),
+            (Method::POST, "/api/v1/todos/import/google-tasks", super::JSON),
+            (Method::POST, "/api/v1/quick-add", super::PLAIN_TEXT),
+        ];
+        let candidates = [
+            "application/json",
+            "application/json; charset=utf-8",
+            "Application/JSON;charset=UTF-8",
+            "text/plain",
+            "text/plain; charset=utf-8",
+            "application/json-patch+json",
+            "application/x-www-form-urlencoded",
+            "multipart/form-data; boundary=x",
+            "application/xml",
+            "text/json",
+            "application/jsonx",
+            "application/msgpack",
+            "",
+        ];
+
+        for (method, uri, accepted) in endpoints {
+            for content_type in candidates {
+                let mime = content_type.split(';').next().unwrap().trim().to_lowercase();
+                let body = Body::from(valid_body(uri, content_type));
+                let request = testing::request(method.clone(), uri, Some(content_type).filter(|value| !value.is_empty()), body);
+                let response = testing::send(&state, request).await;
+                let status = response.status();
+                let body = testing::json(response).await;
+                if accepted.contains(&mime.as_str()) {
+                    assert!(status.is_success(), "{} {} as {:?}: {} {}", method, uri, content_type, status, body);
+                    continue;
+                }
+
+                assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{} {} as {:?}", method, uri, content_type);
+                let problem = match content_type {
+                    "" => "Missing Content-Type".to_string(),
+                    content_type => format!("Unsupported Content-Type {:?}", content_type),
+                };
+                let expected = format!("{}, expected one of: {}", problem, accepted.join(", "));
+                assert_eq!(body, json!({"status": "error", "data": null, "error": expected}), "{} {}", method, uri);
+            }
+        }
+    }
+
+    #[sqlx::test]
+    async fn wrong_content_types_are_rejected_before_the_body_is_read(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        // Muy por encima del límite: leerlo daría un 413 en lugar del 415
+        let upload = vec![b'a'; 16 * 1024 * 1024];
+
+        let request = testing::request(Method::POST, "/api/v1/todos", Some("application/octet-stream"), Body::from(upload));
+        let response = testing::send(&state, request).await;
+
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(
+            testing::json(response).await["error"],
+            "Unsupported Content-Type \"application/octet-stream\", expected one of: application/json, text/plain"
+        );
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM todos").fetch_one(&db).await.unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn write_endpoints_document_the_415() {
+        let features = crate::features::StaticFeatures::new(&[crate::features::QUICK_ADD.to_string()]);
+        let openapi = serde_json::to_value(crate::openapi(&testing::config(&[]), &features)).unwrap();
+        for (path, method) in [
+            ("/api/v1/todos", "post"),
+            ("/api/v1/todos/{id}", "put"),
+            ("/api/v1/todos/{id}", "patch"),
+            ("/api/v1/todos/import/google-tasks", "post"),
+            ("/api/v1/quick-add", "post"),
+        ] {
+            let responses = &openapi["paths"][path][method]["responses"];
+            assert!(responses["415"].is_object(), "{} {}: {}", method, path, responses);
+        }
+    }
+}
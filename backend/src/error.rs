@@ -5,20 +5,35 @@ use crate::response::ApiResponse;
 pub enum AppError {
     Database(sqlx::Error),
     NotFound,
+    Unauthorized(String),
     ValidationError(String),
-    #[allow(dead_code)]
+    Conflict(String),
+    UnprocessableEntity(String),
+    UnsupportedMediaType(String),
+    PayloadTooLarge(String),
+    Gone(String),
+    ServiceUnavailable(String),
     InternalError(String),
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
+        // Los error! de abajo se envían a Sentry a través de la capa de tracing;
+        // los 4xx no se registran como error y por tanto nunca se reportan
         let (status, message) = match self {
             AppError::Database(e) => {
                 tracing::error!("Database error: {}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Database error occurred".to_string())
             }
             AppError::NotFound => (StatusCode::NOT_FOUND, "Resource not found".to_string()),
+            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
             AppError::ValidationError(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::UnprocessableEntity(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg),
+            AppError::UnsupportedMediaType(msg) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg),
+            AppError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg),
+            AppError::Gone(msg) => (StatusCode::GONE, msg),
+            AppError::ServiceUnavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
             AppError::InternalError(msg) => {
                 tracing::error!("Internal error: {}", msg);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
@@ -39,4 +54,20 @@ impl From<validator::ValidationErrors> for AppError {
     fn from(err: validator::ValidationErrors) -> Self {
         AppError::ValidationError(format!("Validation failed: {}", err))
     }
-}
\ No newline at end of file
+}
+
+impl From<axum::extract::rejection::QueryRejection> for AppError {
+    fn from(err: axum::extract::rejection::QueryRejection) -> Self {
+        AppError::ValidationError(err.body_text())
+    }
+}
+
+impl From<axum::extract::rejection::StringRejection> for AppError {
+    fn from(err: axum::extract::rejection::StringRejection) -> Self {
+        if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            AppError::PayloadTooLarge(err.body_text())
+        } else {
+            AppError::ValidationError(err.body_text())
+        }
+    }
+}
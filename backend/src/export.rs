@@ -0,0 +1,150 @@
+use axum::{
+    extract::{Query, State},
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use std::sync::Arc;
+use tracing::info;
+use crate::{
+    model::{AppState, Todo},
+    handler::FilterQuery,
+    error::AppError
+};
+
+const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+const HEADERS: [&str; 7] = [
+    "ID",
+    "Title",
+    "Description",
+    "Completed",
+    "Due date (UTC)",
+    "Created at (UTC)",
+    "Updated at (UTC)",
+];
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/export.xlsx",
+    params(FilterQuery),
+    responses(
+        (status = 200, description = "Todos matching the filter exported as an Excel workbook, newest first", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (status = 400, description = "Invalid filter expression, or more matching todos than EXPORT_MAX_ROWS", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn export_todos_xlsx(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FilterQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let max_rows = state.config.export_max_rows;
+    let filter = query.expr()?;
+
+    // Se pide una fila extra para detectar si se supera el límite
+    let todos = state.todos.list(filter.as_ref(), max_rows as i64 + 1, 0).await?;
+
+    if todos.len() > max_rows {
+        return Err(AppError::ValidationError(format!(
+            "Export is limited to {} todos, narrow it down with a filter",
+            max_rows
+        )));
+    }
+
+    let workbook = build_workbook(&todos)
+        .map_err(|e| AppError::InternalError(format!("Failed to build xlsx export: {}", e)))?;
+
+    info!("Exported {} todos to xlsx", todos.len());
+    let filename = format!("todos-{}.xlsx", state.clock.now().format("%Y-%m-%d"));
+    Ok((
+        [
+            (CONTENT_TYPE, XLSX_CONTENT_TYPE.to_string()),
+            (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        workbook,
+    ))
+}
+
+fn build_workbook(todos: &[Todo]) -> Result<Vec<u8>, XlsxError> {
+    let header_format = Format::new().set_bold();
+    let datetime_format = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet().set_name("Todos")?;
+
+    worksheet.write_row_with_format(0, 0, HEADERS, &header_format)?;
+    worksheet.set_column_width(0, 38)?;
+    worksheet.set_column_width(1, 40)?;
+    worksheet.set_column_width(2, 60)?;
+    for col in 4..=6 {
+        worksheet.set_column_width(col, 20)?;
+    }
+
+    for (todo, row) in todos.iter().zip(1u32..) {
+        worksheet.write_string(row, 0, todo.id.to_string())?;
+        worksheet.write_string(row, 1, &todo.title)?;
+        if let Some(description) = &todo.description {
+            worksheet.write_string(row, 2, description)?;
+        }
+        worksheet.write_boolean(row, 3, todo.completed)?;
+        if let Some(due_date) = todo.due_date {
+            write_datetime(worksheet, row, 4, due_date, &datetime_format)?;
+        }
+        write_datetime(worksheet, row, 5, todo.created_at, &datetime_format)?;
+        write_datetime(worksheet, row, 6, todo.updated_at, &datetime_format)?;
+    }
+
+    worksheet.set_freeze_panes(1, 0)?;
+    worksheet.autofilter(0, 0, todos.len() as u32, HEADERS.len() as u16 - 1)?;
+
+    workbook.save_to_buffer()
+}
+
+fn write_datetime(
+    worksheet: &mut rust_xlsxwriter::Worksheet,
+    row: u32,
+    col: u16,
+    value: DateTime<Utc>,
+    format: &Format,
+) -> Result<(), XlsxError> {
+    worksheet.write_datetime_with_format(row, col, value.naive_utc(), format)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header::CONTENT_TYPE, StatusCode};
+    use sqlx::PgPool;
+    use crate::testing;
+
+    #[sqlx::test]
+    async fn exports_only_the_todos_matching_the_filter(db: PgPool) {
+        // Con un máximo de una fila, solo un filtro que deje una pasa el límite
+        let state = testing::state(db.clone(), testing::config(&[("EXPORT_MAX_ROWS", "1")]));
+        testing::insert_todo(&db, "Buy milk").await;
+        testing::insert_todo(&db, "Call mom").await;
+
+        let all = testing::send(&state, testing::get("/api/v1/todos/export.xlsx")).await;
+        assert_eq!(all.status(), StatusCode::BAD_REQUEST);
+
+        let filtered = testing::send(&state, testing::get("/api/v1/todos/export.xlsx?filter=title:milk")).await;
+        assert_eq!(filtered.status(), StatusCode::OK);
+        assert_eq!(filtered.headers()[CONTENT_TYPE], super::XLSX_CONTENT_TYPE);
+        assert!(testing::body(filtered).await.starts_with(b"PK"));
+
+        let blank = testing::send(&state, testing::get("/api/v1/todos/export.xlsx?filter=%20")).await;
+        assert_eq!(blank.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test]
+    async fn rejects_an_invalid_filter(db: PgPool) {
+        let state = testing::state(db, testing::config(&[]));
+
+        let response = testing::send(&state, testing::get("/api/v1/todos/export.xlsx?filter=title:milk%20AND")).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = testing::json(response).await;
+        assert!(body["error"].as_str().unwrap().contains("column"), "{}", body);
+    }
+}
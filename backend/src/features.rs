@@ -0,0 +1,182 @@
+//! Named feature flags for experimental endpoints and behavior.
+//!
+//! Flags come from `FEATURES` (comma separated) and are fixed until restart.
+//! Internal testers can change them for a single request with the
+//! `X-Feature-Overrides` header (`sync,-graphql` enables `sync` and disables
+//! `graphql`), which is only honored when `X-Feature-Overrides-Signature`
+//! carries its hex HMAC-SHA256 under `FEATURE_OVERRIDE_KEY`. Flag names are
+//! case-insensitive.
+
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use std::{collections::BTreeSet, sync::Arc};
+use tracing::warn;
+use crate::{model::AppState, error::AppError, signature};
+
+const OVERRIDE_HEADER: &str = "x-feature-overrides";
+const OVERRIDE_SIGNATURE_HEADER: &str = "x-feature-overrides-signature";
+
+/// Mounts `POST /api/v1/quick-add`, which takes a todo as a single line of
+/// text.
+pub const QUICK_ADD: &str = "quick-add";
+
+/// Where flags are looked up. Lookups are synchronous so they can be used
+/// while building the router; a dynamic (e.g. database-backed) source is
+/// expected to serve them from a snapshot it refreshes in the background.
+pub trait FeatureSource: Send + Sync {
+    fn is_enabled(&self, flag: &str) -> bool;
+    fn enabled(&self) -> BTreeSet<String>;
+}
+
+/// Flags read once from the configuration.
+pub struct StaticFeatures(BTreeSet<String>);
+
+impl StaticFeatures {
+    pub fn new(flags: &[String]) -> Self {
+        StaticFeatures(flags.iter().cloned().collect())
+    }
+}
+
+impl FeatureSource for StaticFeatures {
+    fn is_enabled(&self, flag: &str) -> bool {
+        self.0.contains(flag)
+    }
+
+    fn enabled(&self) -> BTreeSet<String> {
+        self.0.clone()
+    }
+}
+
+/// Flags in effect for the current request, overrides included. Use this in
+/// handlers instead of `AppState::features` for behavioral toggles.
+pub struct RequestFeatures(BTreeSet<String>);
+
+impl RequestFeatures {
+    pub fn is_enabled(&self, flag: &str) -> bool {
+        self.0.contains(flag)
+    }
+
+    pub fn enabled(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for RequestFeatures {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let mut flags = state.features.enabled();
+        let Some(overrides) = parts.headers.get(OVERRIDE_HEADER) else {
+            return Ok(RequestFeatures(flags));
+        };
+
+        let overrides = overrides.to_str().unwrap_or_default();
+        let signature = parts
+            .headers
+            .get(OVERRIDE_SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok());
+        let verified = match (state.config.feature_override_key.as_deref(), signature) {
+            (Some(key), Some(signature)) => signature::verify_hex(key.as_bytes(), &[overrides.as_bytes()], signature),
+            _ => false,
+        };
+        if !verified {
+            warn!("Rejected request with unsigned or badly signed feature overrides");
+            return Err(AppError::Unauthorized("Invalid feature override signature".to_string()));
+        }
+
+        // Como FEATURES, sin distinguir mayúsculas
+        let overrides = overrides.to_lowercase();
+        for flag in overrides.split(',').map(str::trim).filter(|flag| !flag.is_empty()) {
+            match flag.strip_prefix('-') {
+                Some(flag) => flags.remove(flag),
+                None => flags.insert(flag.trim_start_matches('+').to_string()),
+            };
+        }
+        Ok(RequestFeatures(flags))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, extract::Request, http::{Method, StatusCode}};
+    use sqlx::PgPool;
+    use crate::{signature, testing};
+
+    const KEY: &str = "test-override-key";
+
+    fn quick_add(text: &str) -> Request {
+        testing::request(Method::POST, "/api/v1/quick-add?tz=Europe/Madrid", Some("text/plain"), Body::from(text.to_string()))
+    }
+
+    fn with_overrides(mut request: Request, overrides: &str, key: &str) -> Request {
+        let signature = hex::encode(signature::sign(key.as_bytes(), &[overrides.as_bytes()]));
+        let headers = request.headers_mut();
+        headers.insert(super::OVERRIDE_HEADER, overrides.parse().unwrap());
+        headers.insert(super::OVERRIDE_SIGNATURE_HEADER, signature.parse().unwrap());
+        request
+    }
+
+    #[sqlx::test]
+    async fn quick_add_is_only_mounted_with_its_flag(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let response = testing::send(&state, quick_add("Buy milk")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let openapi = testing::json(testing::send(&state, testing::get("/api-docs/openapi.json")).await).await;
+        assert!(openapi["paths"].get("/api/v1/quick-add").is_none());
+
+        let state = testing::state(db, testing::config(&[("FEATURES", "Quick-Add")]));
+        let openapi = testing::json(testing::send(&state, testing::get("/api-docs/openapi.json")).await).await;
+        assert!(openapi["paths"].get("/api/v1/quick-add").is_some());
+    }
+
+    #[sqlx::test]
+    async fn quick_add_takes_a_title_and_a_due_date(db: PgPool) {
+        let state = testing::state(db, testing::config(&[("FEATURES", "quick-add")]));
+
+        let response = testing::send(&state, quick_add("Call the plumber @ 2030-06-01T10:00:00+02:00\n")).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let todo = &testing::json(response).await["data"];
+        assert_eq!(todo["title"], "Call the plumber");
+        assert_eq!(todo["due_date"], "2030-06-01T08:00:00.000Z");
+
+        let response = testing::send(&state, quick_add("Email @ home")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = testing::send(&state, quick_add("One\nTwo")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let request = testing::json_request(Method::POST, "/api/v1/quick-add", &serde_json::json!({"title": "Buy milk"}));
+        assert_eq!(testing::send(&state, request).await.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[sqlx::test]
+    async fn signed_overrides_toggle_flags_for_one_request_regardless_of_case(db: PgPool) {
+        let config = testing::config(&[("FEATURES", "quick-add,graphql"), ("FEATURE_OVERRIDE_KEY", KEY)]);
+        let state = testing::state(db, config);
+
+        let request = with_overrides(testing::get("/api/v1/info"), "SYNC, -GraphQL", KEY);
+        let info = testing::json(testing::send(&state, request).await).await;
+        assert_eq!(info["data"]["flags"], serde_json::json!(["quick-add", "sync"]));
+
+        let request = with_overrides(quick_add("Buy milk"), "-Quick-Add", KEY);
+        assert_eq!(testing::send(&state, request).await.status(), StatusCode::NOT_FOUND);
+
+        let info = testing::json(testing::send(&state, testing::get("/api/v1/info")).await).await;
+        assert_eq!(info["data"]["flags"], serde_json::json!(["graphql", "quick-add"]));
+    }
+
+    #[sqlx::test]
+    async fn rejects_unsigned_or_badly_signed_overrides(db: PgPool) {
+        let config = testing::config(&[("FEATURES", "graphql"), ("FEATURE_OVERRIDE_KEY", KEY)]);
+        let state = testing::state(db, config);
+
+        let request = with_overrides(testing::get("/api/v1/info"), "sync", "wrong-key");
+        assert_eq!(testing::send(&state, request).await.status(), StatusCode::UNAUTHORIZED);
+
+        let mut request = testing::get("/api/v1/info");
+        request.headers_mut().insert(super::OVERRIDE_HEADER, "sync".parse().unwrap());
+        let response = testing::send(&state, request).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = testing::json(response).await;
+        assert_eq!(body["error"], "Invalid feature override signature");
+    }
+}
@@ -0,0 +1,500 @@
+//! The `filter` expression language of the todo list endpoint.
+//!
+//! ```text
+//! completed:false AND (title:milk OR description:"corner shop") AND due<2024-06-01
+//! ```
+//!
+//! - Conditions are `field`, operator, value. Values with spaces or
+//!   parentheses go in double quotes (`\"` escapes a quote).
+//! - `AND`, `OR` and `NOT` are case-insensitive; `NOT` binds tighter than
+//!   `AND`, which binds tighter than `OR`. Parentheses group.
+//! - `completed:true|false`.
+//! - `title:` and `description:` match text anywhere, case-insensitively.
+//! - `due`, `created` and `updated` take `:`, `<`, `<=`, `>` or `>=` with an
+//!   RFC 3339 timestamp or a `YYYY-MM-DD` date. A date stands for the whole
+//!   UTC day, so `due:2024-06-01` is any time that day and `due<=2024-06-01`
+//!   includes it. `due:none` matches todos without a due date.
+//!
+//! Fields and operators map to fixed SQL fragments and every value is a bind
+//! parameter, so user input never reaches the SQL text.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use sqlx::{Postgres, QueryBuilder};
+use std::fmt;
+use crate::timestamp;
+
+const MAX_LENGTH: usize = 2000;
+const MAX_DEPTH: usize = 32;
+
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Condition(Condition),
+}
+
+pub enum Condition {
+    Completed(bool),
+    Contains(TextField, String),
+    Compare(TimeField, Comparison, DateTime<Utc>),
+    Missing(TimeField),
+}
+
+#[derive(Clone, Copy)]
+pub enum TextField {
+    Title,
+    Description,
+}
+
+#[derive(Clone, Copy)]
+pub enum TimeField {
+    Due,
+    Created,
+    Updated,
+}
+
+#[derive(Clone, Copy)]
+pub enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl TextField {
+    fn column(self) -> &'static str {
+        match self {
+            TextField::Title => "title",
+            TextField::Description => "description",
+        }
+    }
+}
+
+impl TimeField {
+    fn column(self) -> &'static str {
+        match self {
+            TimeField::Due => "due_date",
+            TimeField::Created => "created_at",
+            TimeField::Updated => "updated_at",
+        }
+    }
+}
+
+impl Comparison {
+    fn sql(self) -> &'static str {
+        match self {
+            Comparison::Equal => " = ",
+            Comparison::Less => " < ",
+            Comparison::LessOrEqual => " <= ",
+            Comparison::Greater => " > ",
+            Comparison::GreaterOrEqual => " >= ",
+        }
+    }
+}
+
+/// A syntax or validation error, `column` counting characters from 1.
+pub struct FilterError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid filter at column {}: {}", self.column, self.message)
+    }
+}
+
+pub fn parse(input: &str) -> Result<Expr, FilterError> {
+    if input.chars().count() > MAX_LENGTH {
+        return Err(FilterError {
+            column: MAX_LENGTH + 1,
+            message: format!("filter must be at most {} characters", MAX_LENGTH),
+        });
+    }
+
+    let mut parser = Parser { input, pos: 0, depth: 0 };
+    let expr = parser.parse_or()?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(expr),
+        Some(')') => Err(parser.error("unmatched ')'")),
+        Some(_) => Err(parser.error("expected AND, OR or the end of the filter")),
+    }
+}
+
+/// Appends `expr` as a SQL boolean expression, binding every value.
+pub fn push_sql(expr: &Expr, query: &mut QueryBuilder<'_, Postgres>) {
+    match expr {
+        Expr::And(left, right) | Expr::Or(left, right) => {
+            let operator = if matches!(expr, Expr::And(..)) { " AND " } else { " OR " };
+            query.push("(");
+            push_sql(left, query);
+            query.push(operator);
+            push_sql(right, query);
+            query.push(")");
+        }
+        Expr::Not(inner) => {
+            // COALESCE para que NOT sobre una columna NULL también coincida
+            query.push("NOT COALESCE(");
+            push_sql(inner, query);
+            query.push(", FALSE)");
+        }
+        Expr::Condition(Condition::Completed(completed)) => {
+            query.push("completed = ").push_bind(*completed);
+        }
+        Expr::Condition(Condition::Contains(field, text)) => {
+            let pattern = format!("%{}%", text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+            query.push(field.column()).push(" ILIKE ").push_bind(pattern);
+        }
+        Expr::Condition(Condition::Compare(field, comparison, value)) => {
+            query.push(field.column()).push(comparison.sql()).push_bind(*value);
+        }
+        Expr::Condition(Condition::Missing(field)) => {
+            query.push(field.column()).push(" IS NULL");
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// Byte offset of the next character.
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn parse_or(&mut self) -> Result<Expr, FilterError> {
+        let mut left = self.parse_and()?;
+        while self.keyword("OR") {
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, FilterError> {
+        let mut left = self.parse_unary()?;
+        while self.keyword("AND") {
+            let right = self.parse_unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, FilterError> {
+        self.skip_whitespace();
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(&format!("expressions can be nested at most {} levels deep", MAX_DEPTH)));
+        }
+
+        if self.keyword("NOT") {
+            self.depth += 1;
+            let inner = self.parse_unary()?;
+            self.depth -= 1;
+            return Ok(Expr::Not(Box::new(inner)));
+        }
+
+        if self.peek() == Some('(') {
+            self.bump();
+            self.depth += 1;
+            let inner = self.parse_or()?;
+            self.depth -= 1;
+            self.skip_whitespace();
+            if self.peek() != Some(')') {
+                return Err(self.error("expected ')'"));
+            }
+            self.bump();
+            return Ok(inner);
+        }
+
+        self.parse_condition()
+    }
+
+    fn parse_condition(&mut self) -> Result<Expr, FilterError> {
+        let field_start = self.pos;
+        let field = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_').to_ascii_lowercase();
+        if field.is_empty() {
+            return Err(match self.peek() {
+                None => self.error("expected a condition"),
+                Some(c) => self.error(&format!("expected a field name, found '{}'", c)),
+            });
+        }
+        if matches!(field.as_str(), "and" | "or") {
+            return Err(self.error_at(field_start, &format!("expected a condition, found '{}'", field.to_uppercase())));
+        }
+
+        let operator_start = self.pos;
+        let operator = self.take_while(|c| matches!(c, ':' | '<' | '>' | '=')).to_string();
+        let comparison = match operator.as_str() {
+            ":" => Comparison::Equal,
+            "<" => Comparison::Less,
+            "<=" => Comparison::LessOrEqual,
+            ">" => Comparison::Greater,
+            ">=" => Comparison::GreaterOrEqual,
+            "" => return Err(self.error(&format!("expected ':', '<', '<=', '>' or '>=' after '{}'", field))),
+            other => return Err(self.error_at(operator_start, &format!("unknown operator '{}'", other))),
+        };
+
+        let value_start = self.pos;
+        let value = self.parse_value()?;
+        let invalid = |parser: &Self, message: &str| Err(parser.error_at(value_start, message));
+
+        match field.as_str() {
+            "completed" => match (comparison, value.to_ascii_lowercase().as_str()) {
+                (Comparison::Equal, "true") => Ok(Expr::Condition(Condition::Completed(true))),
+                (Comparison::Equal, "false") => Ok(Expr::Condition(Condition::Completed(false))),
+                (Comparison::Equal, _) => invalid(self, "completed must be true or false"),
+                _ => Err(self.error_at(operator_start, "completed only supports ':'")),
+            },
+            "title" | "description" => {
+                let field = if field == "title" { TextField::Title } else { TextField::Description };
+                match comparison {
+                    Comparison::Equal => Ok(Expr::Condition(Condition::Contains(field, value))),
+                    _ => Err(self.error_at(operator_start, &format!("{} only supports ':'", field.column()))),
+                }
+            }
+            "due" | "created" | "updated" => {
+                let field = match field.as_str() {
+                    "due" => TimeField::Due,
+                    "created" => TimeField::Created,
+                    _ => TimeField::Updated,
+                };
+                if value.eq_ignore_ascii_case("none") {
+                    return match (field, comparison) {
+                        (TimeField::Due, Comparison::Equal) => Ok(Expr::Condition(Condition::Missing(field))),
+                        (TimeField::Due, _) => Err(self.error_at(operator_start, "'none' only works with ':'")),
+                        _ => invalid(self, &format!("{} is never empty", field.column())),
+                    };
+                }
+                time_condition(field, comparison, &value).map_err(|message| self.error_at(value_start, &message))
+            }
+            _ => Err(self.error_at(
+                field_start,
+                &format!("unknown field '{}', expected completed, title, description, due, created or updated", field),
+            )),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<String, FilterError> {
+        if self.peek() != Some('"') {
+            let value = self.take_while(|c| !c.is_whitespace() && c != '(' && c != ')');
+            if value.is_empty() {
+                return Err(self.error("expected a value"));
+            }
+            return Ok(value.to_string());
+        }
+
+        let start = self.pos;
+        self.bump();
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error_at(start, "unterminated quoted value")),
+                Some('"') => return Ok(value),
+                Some('\\') if self.peek() == Some('"') => {
+                    self.bump();
+                    value.push('"');
+                }
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    /// Consumes `word` (case-insensitive) if it comes next as a whole word.
+    fn keyword(&mut self, word: &str) -> bool {
+        self.skip_whitespace();
+        let rest = &self.input[self.pos..];
+        let matches = rest.len() >= word.len()
+            && rest.is_char_boundary(word.len())
+            && rest[..word.len()].eq_ignore_ascii_case(word)
+            && rest[word.len()..]
+                .chars()
+                .next()
+                .is_none_or(|c| c.is_whitespace() || c == '(' || c == ')');
+        if matches {
+            self.pos += word.len();
+        }
+        matches
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> FilterError {
+        self.error_at(self.pos, message)
+    }
+
+    fn error_at(&self, pos: usize, message: &str) -> FilterError {
+        FilterError {
+            column: self.input[..pos].chars().count() + 1,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Builds a timestamp comparison; a bare date covers its whole UTC day.
+fn time_condition(field: TimeField, comparison: Comparison, value: &str) -> Result<Expr, String> {
+    let compare = |comparison, instant| Expr::Condition(Condition::Compare(field, comparison, instant));
+
+    if let Some(instant) = timestamp::parse(value) {
+        return Ok(compare(comparison, instant));
+    }
+
+    let start = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|start| start.and_utc())
+        .ok_or_else(|| format!("expected a YYYY-MM-DD date or an RFC 3339 timestamp, found \"{}\"", value))?;
+    // El último día representable no tiene final
+    let end = || {
+        start
+            .checked_add_signed(Duration::days(1))
+            .ok_or_else(|| format!("date \"{}\" is out of range", value))
+    };
+    Ok(match comparison {
+        Comparison::Less => compare(Comparison::Less, start),
+        Comparison::LessOrEqual => compare(Comparison::Less, end()?),
+        Comparison::Greater => compare(Comparison::GreaterOrEqual, end()?),
+        Comparison::GreaterOrEqual => compare(Comparison::GreaterOrEqual, start),
+        Comparison::Equal => Expr::And(
+            Box::new(compare(Comparison::GreaterOrEqual, start)),
+            Box::new(compare(Comparison::Less, end()?)),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Postgres, QueryBuilder};
+    use super::{parse, push_sql, MAX_DEPTH, MAX_LENGTH};
+
+    /// The SQL a filter turns into, with its binds as `$n`.
+    fn sql(input: &str) -> String {
+        let expr = parse(input).unwrap_or_else(|e| panic!("{}: {}", input, e));
+        let mut query = QueryBuilder::<Postgres>::new("");
+        push_sql(&expr, &mut query);
+        query.sql().to_string()
+    }
+
+    fn error(input: &str) -> String {
+        match parse(input) {
+            Ok(_) => panic!("{} should not parse", input),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn not_binds_tighter_than_and_which_binds_tighter_than_or() {
+        assert_eq!(
+            sql("completed:true OR title:a AND NOT description:b"),
+            "(completed = $1 OR (title ILIKE $2 AND NOT COALESCE(description ILIKE $3, FALSE)))"
+        );
+        assert_eq!(
+            sql("(completed:true or title:a) and not (due:none)"),
+            "((completed = $1 OR title ILIKE $2) AND NOT COALESCE(due_date IS NULL, FALSE))"
+        );
+        assert_eq!(sql("title:a OR title:b OR title:c"), "((title ILIKE $1 OR title ILIKE $2) OR title ILIKE $3)");
+        // Una palabra clave solo cuenta como palabra entera
+        assert_eq!(sql("title:ORANGE AND title:\"a OR b\""), "(title ILIKE $1 AND title ILIKE $2)");
+    }
+
+    #[test]
+    fn dates_cover_their_whole_utc_day() {
+        assert_eq!(sql("due:2024-06-01"), "(due_date >= $1 AND due_date < $2)");
+        assert_eq!(sql("due<=2024-06-01"), "due_date < $1");
+        assert_eq!(sql("created>2024-06-01"), "created_at >= $1");
+        assert_eq!(sql("updated>=2024-06-01T10:00:00Z"), "updated_at >= $1");
+    }
+
+    #[test]
+    fn reports_the_last_representable_day_instead_of_overflowing() {
+        let max = chrono::NaiveDate::MAX.format("%Y-%m-%d").to_string();
+        for input in [format!("due:{}", max), format!("due<={}", max), format!("due>{}", max)] {
+            let column = input.find(&max).unwrap() + 1;
+            assert_eq!(error(&input), format!("Invalid filter at column {}: date \"{}\" is out of range", column, max));
+        }
+        assert_eq!(sql(&format!("due<{}", max)), "due_date < $1");
+        assert_eq!(sql(&format!("due>={}", max)), "due_date >= $1");
+    }
+
+    #[test]
+    fn limits_nesting_depth() {
+        // La condición más interna cuenta como un nivel más
+        let nested = |parentheses: usize| format!("{}completed:true{}", "(".repeat(parentheses), ")".repeat(parentheses));
+        assert!(parse(&nested(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(
+            error(&nested(MAX_DEPTH)),
+            format!("Invalid filter at column {}: expressions can be nested at most {} levels deep", MAX_DEPTH + 1, MAX_DEPTH)
+        );
+        assert!(parse(&format!("{}completed:true", "NOT ".repeat(MAX_DEPTH - 1))).is_ok());
+        assert!(error(&format!("{}completed:true", "NOT ".repeat(MAX_DEPTH))).contains("nested at most"));
+        assert!(error(&"x".repeat(MAX_LENGTH + 1)).starts_with(&format!("Invalid filter at column {}:", MAX_LENGTH + 1)));
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_column() {
+        let cases = [
+            ("", "column 1: expected a condition"),
+            ("completed:maybe", "column 11: completed must be true or false"),
+            ("completed<true", "column 10: completed only supports ':'"),
+            ("títle:a AND prio:1", "column 2: expected ':', '<', '<=', '>' or '>=' after 't'"),
+            ("title:a AND prio:1", "column 13: unknown field 'prio'"),
+            ("due=>2024-01-01", "column 4: unknown operator '=>'"),
+            ("due:tomorrow", "column 5: expected a YYYY-MM-DD date or an RFC 3339 timestamp, found \"tomorrow\""),
+            ("created:none", "column 9: created_at is never empty"),
+            ("due<none", "column 4: 'none' only works with ':'"),
+            ("title:\"open", "column 7: unterminated quoted value"),
+            ("(title:a", "column 9: expected ')'"),
+            ("title:a)", "column 8: unmatched ')'"),
+            ("title:a title:b", "column 9: expected AND, OR or the end of the filter"),
+            ("title:a AND OR title:b", "column 13: expected a condition, found 'OR'"),
+            ("ñ:a", "column 1: expected a field name, found 'ñ'"),
+        ];
+        for (input, expected) in cases {
+            let error = error(input);
+            assert!(error.starts_with(&format!("Invalid filter at {}", expected)), "{}: {}", input, error);
+        }
+    }
+
+    #[test]
+    fn random_input_never_panics() {
+        use std::hash::{BuildHasher, RandomState};
+
+        const ALPHABET: &[&str] = &[
+            "(", ")", " ", "\"", "\\", ":", "<", ">", "=", "AND", "OR", "NOT", "completed", "title", "due",
+            "created", "none", "true", "2024-06-01", "+262142-12-31", "-262143-01-01", "T10:00:00Z", "ñ", "é", "9",
+        ];
+        let random = RandomState::new();
+        for seed in 0u64..5000 {
+            let mut input = String::new();
+            let mut state = random.hash_one(seed);
+            for _ in 0..(state % 24) {
+                state = random.hash_one(state);
+                input.push_str(ALPHABET[(state % ALPHABET.len() as u64) as usize]);
+            }
+            if let Ok(expr) = parse(&input) {
+                push_sql(&expr, &mut QueryBuilder::<Postgres>::new(""));
+            }
+        }
+    }
+}
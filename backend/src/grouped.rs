@@ -0,0 +1,382 @@
+//! Todos bucketed server-side for board-style clients, computed in a single
+//! query with window functions.
+
+use axum::{
+    extract::{rejection::QueryRejection, Json, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Postgres, QueryBuilder, Row};
+use std::sync::Arc;
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+use crate::{
+    model::{AppState, Todo},
+    response::ApiResponse,
+    handler::FilterQuery,
+    error::AppError,
+    filter
+};
+
+/// Due buckets in the order the groups are returned.
+const DUE_BUCKETS: [&str; 5] = ["overdue", "today", "this_week", "later", "no_date"];
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    /// overdue, today, this_week (until Monday), later and no_date
+    DueBucket,
+}
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GroupedQuery {
+    #[param(inline)]
+    /// What to group the todos by
+    by: GroupBy,
+    #[schema(example = 5)]
+    /// Todos returned per group (default PAGINATION_DEFAULT_LIMIT, max PAGINATION_MAX_LIMIT)
+    per_group: Option<u32>,
+    #[schema(example = "Europe/Madrid")]
+    /// IANA timezone that decides where today and this week end (default UTC)
+    tz: Option<String>,
+    #[schema(example = "overdue")]
+    /// Only return this group, to fetch more of it with `after`
+    group: Option<String>,
+    #[schema(example = 5)]
+    /// `next_cursor` of the group to continue from; requires `group`
+    after: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[schema(example = json!({
+    "key": "overdue",
+    "count": 7,
+    "todos": [{
+        "id": "550e8400-e29b-41d4-a716-446655440000",
+        "title": "Buy groceries",
+        "description": null,
+        "completed": false,
+        "due_date": "2030-01-01T18:00:00.000Z",
+        "created_at": "2023-01-01T00:00:00.000Z",
+        "updated_at": "2023-01-01T00:00:00.000Z"
+    }],
+    "next_cursor": 1
+}))]
+pub struct TodoGroup {
+    #[schema(example = "overdue")]
+    pub key: String,
+    /// Todos in the whole group, not only in this response
+    #[schema(example = 7)]
+    pub count: i64,
+    /// Soonest due first, then newest
+    pub todos: Vec<Todo>,
+    /// Pass as `after` (with `group`) to get the next todos of this group;
+    /// absent when there are no more
+    #[schema(example = 1)]
+    pub next_cursor: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/grouped",
+    params(GroupedQuery, FilterQuery),
+    responses(
+        (status = 200, description = "Every group in order, empty ones included, with the first todos of each", body = ApiResponseVecTodoGroup),
+        (status = 400, description = "Unknown `by` or group, unknown timezone or invalid filter", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn get_grouped(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<GroupedQuery>, QueryRejection>,
+    Query(filter_query): Query<FilterQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(query) = query?;
+    let GroupBy::DueBucket = query.by;
+    let per_group = query
+        .per_group
+        .unwrap_or(state.config.pagination_default_limit)
+        .clamp(1, state.config.pagination_max_limit);
+    let tz: Tz = match query.tz.as_deref() {
+        None => Tz::UTC,
+        Some(tz) => tz
+            .parse()
+            .map_err(|_| AppError::ValidationError(format!("Unknown timezone \"{}\"", tz)))?,
+    };
+    let keys = match query.group.as_deref() {
+        None => DUE_BUCKETS.as_slice(),
+        Some(group) => {
+            let index = DUE_BUCKETS.iter().position(|key| *key == group).ok_or_else(|| {
+                AppError::ValidationError(format!(
+                    "Unknown group \"{}\", expected one of {}",
+                    group,
+                    DUE_BUCKETS.join(", ")
+                ))
+            })?;
+            &DUE_BUCKETS[index..=index]
+        }
+    };
+    let after = match (query.after, &query.group) {
+        (Some(_), None) => {
+            return Err(AppError::ValidationError("after requires group".to_string()));
+        }
+        (after, _) => after.unwrap_or(0).max(0),
+    };
+    let filter = filter_query.expr()?;
+
+    let now = state.clock.now();
+    let (tomorrow, next_week) = local_boundaries(now, tz);
+
+    let mut sql = QueryBuilder::<Postgres>::new(
+        r#"
+        WITH bucketed AS (
+            SELECT id, title, description, completed, due_date, created_at, updated_at,
+                CASE
+                    WHEN due_date IS NULL THEN 'no_date'
+                    WHEN due_date < "#,
+    );
+    sql.push_bind(now)
+        .push(" THEN 'overdue' WHEN due_date < ")
+        .push_bind(tomorrow)
+        .push(" THEN 'today' WHEN due_date < ")
+        .push_bind(next_week)
+        .push(
+            r#" THEN 'this_week'
+                    ELSE 'later'
+                END AS group_key
+            FROM todos"#,
+        );
+    if let Some(filter) = &filter {
+        sql.push(" WHERE ");
+        filter::push_sql(filter, &mut sql);
+    }
+    sql.push(
+        r#"
+        ),
+        ranked AS (
+            SELECT *,
+                row_number() OVER (PARTITION BY group_key ORDER BY due_date NULLS LAST, created_at DESC, id) AS position,
+                count(*) OVER (PARTITION BY group_key) AS total
+            FROM bucketed
+            WHERE group_key = ANY("#,
+    );
+    sql.push_bind(keys.iter().map(|key| key.to_string()).collect::<Vec<_>>())
+        .push(
+            r#")
+        )
+        SELECT * FROM ranked
+        WHERE (position > "#,
+        )
+        .push_bind(after)
+        .push(" AND position <= ")
+        .push_bind(after + i64::from(per_group))
+        // La última fila de cada grupo da su total aunque quede fuera de la página
+        .push(") OR position = total ORDER BY position");
+
+    let rows = sql.build().fetch_all(&state.db).await?;
+
+    let mut groups = keys
+        .iter()
+        .map(|key| TodoGroup { key: key.to_string(), count: 0, todos: Vec::new(), next_cursor: None })
+        .collect::<Vec<_>>();
+    for row in rows {
+        let key: String = row.try_get("group_key")?;
+        let position: i64 = row.try_get("position")?;
+        let Some(group) = groups.iter_mut().find(|group| group.key == key) else {
+            continue;
+        };
+        group.count = row.try_get("total")?;
+        if position > after && position <= after + i64::from(per_group) {
+            group.todos.push(Todo::from_row(&row)?);
+        }
+    }
+    for group in &mut groups {
+        let seen = after + group.todos.len() as i64;
+        group.next_cursor = (!group.todos.is_empty() && seen < group.count).then_some(seen);
+    }
+
+    info!("Retrieved {} groups of todos by due bucket (per group: {})", groups.len(), per_group);
+    Ok((StatusCode::OK, Json(ApiResponse::success(groups))))
+}
+
+/// Start of tomorrow and of next Monday in `tz`, as UTC instants.
+fn local_boundaries(now: DateTime<Utc>, tz: Tz) -> (DateTime<Utc>, DateTime<Utc>) {
+    let today = now.with_timezone(&tz).date_naive();
+    let tomorrow = today + Duration::days(1);
+    let next_week = today + Duration::days(7 - i64::from(today.weekday().num_days_from_monday()));
+    (start_of_day(tomorrow, tz), start_of_day(next_week, tz))
+}
+
+/// Local midnight, or the first instant of the day when a DST change skips it.
+fn start_of_day(date: NaiveDate, tz: Tz) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    (0..=2)
+        .find_map(|hours| tz.from_local_datetime(&(midnight + Duration::hours(hours))).earliest())
+        .map_or_else(|| midnight.and_utc(), |start| start.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use chrono::{DateTime, TimeZone, Utc};
+    use chrono_tz::Tz;
+    use serde_json::Value;
+    use sqlx::PgPool;
+    use crate::{clock::MockClock, testing};
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn this_week_ends_on_the_next_local_monday() {
+        // Martes, lunes y domingo
+        assert_eq!(
+            super::local_boundaries(utc("2030-01-01T12:00:00Z"), Tz::UTC),
+            (utc("2030-01-02T00:00:00Z"), utc("2030-01-07T00:00:00Z"))
+        );
+        assert_eq!(
+            super::local_boundaries(utc("2030-01-07T00:00:00Z"), Tz::UTC),
+            (utc("2030-01-08T00:00:00Z"), utc("2030-01-14T00:00:00Z"))
+        );
+        assert_eq!(
+            super::local_boundaries(utc("2030-01-06T23:59:59Z"), Tz::UTC),
+            (utc("2030-01-07T00:00:00Z"), utc("2030-01-07T00:00:00Z"))
+        );
+        // Las 04:00 UTC del lunes aún son domingo en Los Ángeles
+        assert_eq!(
+            super::local_boundaries(utc("2030-01-07T04:00:00Z"), chrono_tz::America::Los_Angeles),
+            (utc("2030-01-07T08:00:00Z"), utc("2030-01-07T08:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn days_without_a_midnight_start_at_their_first_instant() {
+        // Santiago adelanta la hora a medianoche: el día empieza a la 01:00 (-03)
+        let skipped = chrono::NaiveDate::from_ymd_opt(2024, 9, 8).unwrap();
+        assert_eq!(super::start_of_day(skipped, chrono_tz::America::Santiago), utc("2024-09-08T04:00:00Z"));
+        let normal = chrono::NaiveDate::from_ymd_opt(2024, 9, 9).unwrap();
+        assert_eq!(super::start_of_day(normal, chrono_tz::America::Santiago), utc("2024-09-09T03:00:00Z"));
+    }
+
+    async fn seed(db: &PgPool) {
+        let todos = [
+            ("Overdue A", false, Some("2029-12-31T00:00:00Z")),
+            ("Overdue B", false, Some("2030-01-01T11:00:00Z")),
+            ("Overdue C", false, Some("2030-01-01T11:59:59Z")),
+            ("Right now", false, Some("2030-01-01T12:00:00Z")),
+            ("Tonight", false, Some("2030-01-01T20:00:00Z")),
+            ("Done tonight", true, Some("2030-01-01T23:00:00Z")),
+            ("Early tomorrow", false, Some("2030-01-02T05:00:00Z")),
+            ("Saturday", false, Some("2030-01-05T10:00:00Z")),
+            ("Next Monday", false, Some("2030-01-07T00:00:00Z")),
+            ("Someday", false, None),
+        ];
+        for (title, completed, due_date) in todos {
+            sqlx::query("INSERT INTO todos (title, completed, due_date) VALUES ($1, $2, $3)")
+                .bind(title)
+                .bind(completed)
+                .bind(due_date.map(utc))
+                .execute(db)
+                .await
+                .unwrap();
+        }
+    }
+
+    /// Key, count, titles and next cursor of each group.
+    fn groups(body: &Value) -> Vec<(String, i64, Vec<String>, Option<i64>)> {
+        body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|group| {
+                let titles = group["todos"].as_array().unwrap().iter().map(|todo| todo["title"].as_str().unwrap().to_string());
+                (
+                    group["key"].as_str().unwrap().to_string(),
+                    group["count"].as_i64().unwrap(),
+                    titles.collect(),
+                    group["next_cursor"].as_i64(),
+                )
+            })
+            .collect()
+    }
+
+    fn group(key: &str, count: i64, titles: &[&str], next_cursor: Option<i64>) -> (String, i64, Vec<String>, Option<i64>) {
+        (key.to_string(), count, titles.iter().map(|title| title.to_string()).collect(), next_cursor)
+    }
+
+    #[sqlx::test]
+    async fn buckets_by_due_date_in_order_with_counts_and_per_group_cursors(db: PgPool) {
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap());
+        let state = testing::state_at(db.clone(), testing::config(&[]), &clock);
+        seed(&db).await;
+
+        let response = testing::send(&state, testing::get("/api/v1/todos/grouped?by=due_bucket&per_group=2")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(groups(&testing::json(response).await), [
+            group("overdue", 3, &["Overdue A", "Overdue B"], Some(2)),
+            group("today", 3, &["Right now", "Tonight"], Some(2)),
+            group("this_week", 2, &["Early tomorrow", "Saturday"], None),
+            group("later", 1, &["Next Monday"], None),
+            group("no_date", 1, &["Someday"], None),
+        ]);
+
+        let more = testing::get("/api/v1/todos/grouped?by=due_bucket&per_group=2&group=overdue&after=2");
+        assert_eq!(groups(&testing::json(testing::send(&state, more).await).await), [
+            group("overdue", 3, &["Overdue C"], None),
+        ]);
+        let past_the_end = testing::get("/api/v1/todos/grouped?by=due_bucket&group=later&after=5");
+        assert_eq!(groups(&testing::json(testing::send(&state, past_the_end).await).await), [
+            group("later", 1, &[], None),
+        ]);
+
+        let filtered = testing::get("/api/v1/todos/grouped?by=due_bucket&group=today&filter=completed:false");
+        assert_eq!(groups(&testing::json(testing::send(&state, filtered).await).await), [
+            group("today", 2, &["Right now", "Tonight"], None),
+        ]);
+    }
+
+    #[sqlx::test]
+    async fn today_and_this_week_end_at_local_midnight_in_tz(db: PgPool) {
+        let clock = MockClock::new(Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap());
+        let state = testing::state_at(db.clone(), testing::config(&[]), &clock);
+        seed(&db).await;
+
+        // En Los Ángeles son las 04:00 del martes: mañana empieza a las 08:00 UTC
+        let uri = "/api/v1/todos/grouped?by=due_bucket&tz=America/Los_Angeles";
+        assert_eq!(groups(&testing::json(testing::send(&state, testing::get(uri)).await).await), [
+            group("overdue", 3, &["Overdue A", "Overdue B", "Overdue C"], None),
+            group("today", 4, &["Right now", "Tonight", "Done tonight", "Early tomorrow"], None),
+            group("this_week", 2, &["Saturday", "Next Monday"], None),
+            group("later", 0, &[], None),
+            group("no_date", 1, &["Someday"], None),
+        ]);
+    }
+
+    #[sqlx::test]
+    async fn rejects_unknown_groupings_groups_and_timezones(db: PgPool) {
+        let state = testing::state(db, testing::config(&[]));
+
+        for (query, message) in [
+            ("by=priority", None),
+            ("", None),
+            ("by=due_bucket&group=soon", Some("Unknown group \"soon\", expected one of overdue, today, this_week, later, no_date")),
+            ("by=due_bucket&tz=Mars/Olympus", Some("Unknown timezone \"Mars/Olympus\"")),
+            ("by=due_bucket&after=2", Some("after requires group")),
+            ("by=due_bucket&filter=title:", None),
+        ] {
+            let response = testing::send(&state, testing::get(&format!("/api/v1/todos/grouped?{}", query))).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query);
+            let body = testing::json(response).await;
+            assert_eq!(body["status"], "error", "{}", query);
+            if let Some(message) = message {
+                assert_eq!(body["error"], message, "{}", query);
+            }
+        }
+    }
+}
@@ -1,32 +1,314 @@
 use axum::{
-    extract::{State, Path, Json, Query},
-    http::StatusCode,
-    response::IntoResponse,
+    async_trait,
+    extract::{rejection::QueryRejection, FromRequest, Request, State, Path, Json, Query},
+    http::{header::{CONTENT_TYPE, LOCATION}, StatusCode},
+    response::{Html, IntoResponse, Response},
 };
 use serde::Deserialize;
 use uuid::Uuid;
 use utoipa::{ToSchema, IntoParams};
-use validator::Validate;
-use tracing::info;
+use validator::{Validate, ValidationError, ValidationErrors};
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
 use crate::{
     model::Todo, 
     response::ApiResponse, 
     model::AppState,
-    error::AppError
+    config::Config,
+    error::AppError,
+    repository::TodoFields,
+    markdown,
+    natural_date,
+    filter::{self, Expr},
+    timestamp,
+    json_patch,
+    features::{self, RequestFeatures}
 };
-use std::sync::Arc;
+use chrono_tz::Tz;
+use std::sync::{atomic::Ordering, Arc};
 
+pub use crate::api::{CreateTodo, PatchTodo};
+
+const READINESS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Most todos a single `text/plain` create may contain.
+const PLAIN_CREATE_MAX_TODOS: usize = 100;
+
+impl CreateTodo {
+    /// Converts the request into the fields the service stores.
+    fn into_fields(self, now: DateTime<Utc>) -> Result<TodoFields, ValidationErrors> {
+        let due_date = resolve_due_date(self.due_date.as_deref(), self.tz.as_deref(), now)?;
+        Ok(TodoFields {
+            title: self.title,
+            description: self.description,
+            completed: self.completed.unwrap_or(false),
+            due_date,
+        })
+    }
+}
+
+/// Body of `POST /api/v1/todos`: a JSON [`CreateTodo`], or `text/plain`
+/// with one title per line.
+pub enum CreateBody {
+    Json(CreateTodo),
+    Plain(String),
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for CreateBody {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let plain = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/plain"));
+
+        if plain {
+            let text = String::from_request(request, state)
+                .await
+                .map_err(|rejection| AppError::from(rejection).into_response())?;
+            Ok(CreateBody::Plain(text))
+        } else {
+            let Json(todo) = Json::<CreateTodo>::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(CreateBody::Json(todo))
+        }
+    }
+}
+
+/// Turns each non-empty line of a `text/plain` create into a todo with that
+/// title, validated like a JSON create.
+fn plain_todos(text: &str, now: DateTime<Utc>) -> Result<Vec<TodoFields>, AppError> {
+    let lines = text
+        .lines()
+        .zip(1..)
+        .map(|(line, number)| (number, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return Err(AppError::ValidationError(
+            "The body has no todo titles, send one title per line".to_string(),
+        ));
+    }
+    if lines.len() > PLAIN_CREATE_MAX_TODOS {
+        return Err(AppError::ValidationError(format!(
+            "At most {} todos can be created at once, got {}",
+            PLAIN_CREATE_MAX_TODOS,
+            lines.len()
+        )));
+    }
+
+    lines
+        .into_iter()
+        .map(|(number, title)| {
+            let todo = CreateTodo {
+                title: title.to_string(),
+                description: None,
+                completed: None,
+                due_date: None,
+                tz: None,
+            };
+            todo.validate()
+                .and_then(|()| todo.into_fields(now))
+                .map_err(|e| AppError::ValidationError(format!("Validation failed on line {}: {}", number, e)))
+        })
+        .collect()
+}
+
+/// Adds the `text/plain` alternative to the request body of
+/// `POST /api/v1/todos`, which `#[utoipa::path]` can only give one schema.
+pub fn document_plain_create(openapi: &mut utoipa::openapi::OpenApi) {
+    use utoipa::openapi::{path::PathItemType, Content, ObjectBuilder, SchemaType};
+
+    let request_body = openapi
+        .paths
+        .paths
+        .get_mut("/api/v1/todos")
+        .and_then(|item| item.operations.get_mut(&PathItemType::Post))
+        .and_then(|operation| operation.request_body.as_mut());
+    if let Some(request_body) = request_body {
+        let schema = ObjectBuilder::new()
+            .schema_type(SchemaType::String)
+            .description(Some(format!(
+                "One todo title per line (up to {}), blank lines are ignored",
+                PLAIN_CREATE_MAX_TODOS
+            )))
+            .example(Some(serde_json::json!("Buy milk\nCall the plumber")));
+        request_body.content.insert("text/plain".to_string(), Content::new(schema));
+    }
+}
+
+/// Body of a PUT, which replaces the whole todo: every field must be present
+/// (`null` clears `description` and `due_date`) so that an omitted field is
+/// rejected instead of silently reset to its default.
 #[derive(Deserialize, ToSchema, Validate)]
 #[schema(example = json!({
     "title": "Buy groceries",
-    "completed": false
+    "description": null,
+    "completed": true,
+    "due_date": "2030-01-01T18:00:00Z"
 }))]
-pub struct CreateTodo {
+pub struct ReplaceTodo {
     #[validate(length(min = 1, max = 255, message = "Title must be between 1 and 255 characters"))]
-    #[schema(example = "Buy groceries")]
-    title: String,
-    #[schema(example = false)]
+    #[schema(value_type = String, required = true, example = "Buy groceries")]
+    title: Option<String>,
+    #[validate(length(max = 10000, message = "Description must be at most 10000 characters"))]
+    #[serde(default, deserialize_with = "crate::api::present")]
+    #[schema(value_type = Option<String>, required = true, example = "- [ ] Milk\n- [ ] Eggs")]
+    /// Markdown description, `null` for none
+    description: Option<Option<String>>,
+    #[schema(value_type = bool, required = true, example = true)]
     completed: Option<bool>,
+    #[serde(default, deserialize_with = "crate::api::present")]
+    #[schema(value_type = Option<String>, required = true, example = "2030-01-01T18:00:00Z")]
+    /// When the todo is due, `null` for never: an RFC 3339 timestamp, a date
+    /// (midnight in `tz`) or a phrase such as "tomorrow" or "in 3 days"
+    due_date: Option<Option<String>>,
+    #[schema(example = "Europe/Madrid")]
+    /// IANA timezone used to resolve dates and phrases (defaults to UTC)
+    tz: Option<String>,
+}
+
+impl ReplaceTodo {
+    /// Converts the request into the fields the service stores, with one
+    /// error per missing field.
+    fn into_fields(self, now: DateTime<Utc>) -> Result<TodoFields, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let present = [
+            ("title", self.title.is_some()),
+            ("description", self.description.is_some()),
+            ("completed", self.completed.is_some()),
+            ("due_date", self.due_date.is_some()),
+        ];
+        for (field, _) in present.iter().filter(|(_, present)| !present) {
+            let mut error = ValidationError::new("required");
+            error.message = Some(
+                format!(
+                    "{} is required because PUT replaces the whole todo, use PATCH to change only some fields",
+                    field
+                )
+                .into(),
+            );
+            errors.add(field, error);
+        }
+
+        match (self.title, self.description, self.completed, self.due_date) {
+            (Some(title), Some(description), Some(completed), Some(due_date)) => Ok(TodoFields {
+                title,
+                description,
+                completed,
+                due_date: resolve_due_date(due_date.as_deref(), self.tz.as_deref(), now)?,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+impl PatchTodo {
+    /// Applies the fields present in the request on top of `current`.
+    fn apply(self, current: Todo, now: DateTime<Utc>) -> Result<TodoFields, ValidationErrors> {
+        let due_date = match self.due_date {
+            Some(due_date) => resolve_due_date(due_date.as_deref(), self.tz.as_deref(), now)?,
+            None => current.due_date,
+        };
+        Ok(TodoFields {
+            title: self.title.unwrap_or(current.title),
+            description: self.description.unwrap_or(current.description),
+            completed: self.completed.unwrap_or(current.completed),
+            due_date,
+        })
+    }
+}
+
+/// Body of `PATCH /api/v1/todos/:id`: a merge-style JSON [`PatchTodo`], or
+/// an RFC 6902 JSON Patch sent as `application/json-patch+json`.
+pub enum PatchBody {
+    Merge(PatchTodo),
+    JsonPatch(Vec<serde_json::Value>),
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for PatchBody {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let json_patch = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case(json_patch::MEDIA_TYPE));
+
+        if json_patch {
+            let Json(operations) = Json::<Vec<serde_json::Value>>::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(PatchBody::JsonPatch(operations))
+        } else {
+            let Json(patch) = Json::<PatchTodo>::from_request(request, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(PatchBody::Merge(patch))
+        }
+    }
+}
+
+/// Applies a JSON Patch on top of `current` and validates the result like
+/// the body of a PUT.
+fn json_patch_fields(
+    operations: &[serde_json::Value],
+    current: Todo,
+    now: DateTime<Utc>,
+) -> Result<TodoFields, AppError> {
+    let mut document = json_patch::document(&current);
+    json_patch::apply(operations, &mut document)?;
+    let todo = serde_json::from_value::<ReplaceTodo>(serde_json::Value::Object(document))
+        .map_err(|e| AppError::ValidationError(format!("Validation failed: {}", e)))?;
+    todo.validate()?;
+    Ok(todo.into_fields(now)?)
+}
+
+/// Resolves a due date to a concrete instant, interpreting natural-language
+/// phrases in `tz`.
+fn resolve_due_date(
+    input: Option<&str>,
+    tz: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, ValidationErrors> {
+    let Some(input) = input else {
+        return Ok(None);
+    };
+    if let Some(due_date) = timestamp::parse(input) {
+        return Ok(Some(due_date));
+    }
+
+    let tz = match tz {
+        None => Tz::UTC,
+        Some(tz) => tz.parse().map_err(|_| {
+            field_error("tz", "unknown_timezone", format!("Unknown timezone \"{}\"", tz))
+        })?,
+    };
+
+    natural_date::parse(input, now, tz).map(Some).map_err(|reason| {
+        field_error(
+            "due_date",
+            "unparseable_due_date",
+            format!("Could not understand due date \"{}\": {}", input, reason),
+        )
+    })
+}
+
+pub(crate) fn field_error(field: &'static str, code: &'static str, message: String) -> ValidationErrors {
+    let mut error = ValidationError::new(code);
+    error.message = Some(message.into());
+
+    let mut errors = ValidationErrors::new();
+    errors.add(field, error);
+    errors
 }
 
 #[derive(Deserialize, ToSchema, IntoParams)]
@@ -35,10 +317,97 @@ pub struct PaginationQuery {
     /// Page number (starts from 1)
     page: Option<u32>,
     #[schema(example = 10)]
-    /// Number of items per page (max 100)
+    /// Number of items per page
     limit: Option<u32>,
 }
 
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct FilterQuery {
+    #[schema(example = "completed:false AND (title:milk OR title:eggs) AND due<2030-06-01")]
+    /// Filter expression over completed, title, description, due, created and
+    /// updated, combined with AND, OR, NOT and parentheses. Dates
+    /// (`YYYY-MM-DD`) cover the whole UTC day; `due:none` matches todos
+    /// without a due date
+    pub(crate) filter: Option<String>,
+}
+
+impl FilterQuery {
+    /// The parsed filter, `None` when it is absent or blank.
+    pub(crate) fn expr(&self) -> Result<Option<Expr>, AppError> {
+        self.filter
+            .as_deref()
+            .filter(|filter| !filter.trim().is_empty())
+            .map(filter::parse)
+            .transpose()
+            .map_err(|e| AppError::ValidationError(e.to_string()))
+    }
+}
+
+/// Endpoints taking [`PaginationQuery`], whose `limit` documentation is
+/// completed with the configured bounds when the OpenAPI document is built.
+const PAGINATED_PATHS: &[&str] = &["/api/v1/todos", "/api/v1/todos/archive"];
+
+impl PaginationQuery {
+    /// Returns `(page, limit, offset)` within `PAGINATION_DEFAULT_LIMIT` and
+    /// `PAGINATION_MAX_LIMIT`, rejecting offsets beyond `PAGINATION_MAX_OFFSET`.
+    pub(crate) fn resolve(&self, config: &Config) -> Result<(u32, u32, i64), AppError> {
+        let page = self.page.unwrap_or(1).max(1);
+        let limit = self
+            .limit
+            .unwrap_or(config.pagination_default_limit)
+            .clamp(1, config.pagination_max_limit);
+
+        // En i64 para que páginas enormes no desborden
+        let offset = (i64::from(page) - 1)
+            .checked_mul(i64::from(limit))
+            .filter(|offset| *offset <= config.pagination_max_offset);
+        match offset {
+            Some(offset) => Ok((page, limit, offset)),
+            None => Err(AppError::ValidationError(format!(
+                "Page {} is too deep: offsets are limited to {} items, use a filter (e.g. on created) to narrow the results instead",
+                page, config.pagination_max_offset
+            ))),
+        }
+    }
+}
+
+/// Writes the configured page size bounds into the `limit` descriptions of
+/// the generated OpenAPI document.
+pub fn document_pagination(openapi: &mut utoipa::openapi::OpenApi, config: &Config) {
+    use utoipa::openapi::{RefOr, Schema};
+
+    let description = format!(
+        "Number of items per page (default {}, max {})",
+        config.pagination_default_limit, config.pagination_max_limit
+    );
+
+    for path in PAGINATED_PATHS {
+        let Some(item) = openapi.paths.paths.get_mut(*path) else {
+            continue;
+        };
+        let parameters = item
+            .operations
+            .values_mut()
+            .filter_map(|operation| operation.parameters.as_mut())
+            .flatten();
+        for parameter in parameters.filter(|parameter| parameter.name == "limit") {
+            parameter.description = Some(description.clone());
+        }
+    }
+
+    let limit = openapi
+        .components
+        .as_mut()
+        .and_then(|components| components.schemas.get_mut("PaginationQuery"))
+        .and_then(|schema| match schema {
+            RefOr::T(Schema::Object(object)) => object.properties.get_mut("limit"),
+            _ => None,
+        });
+    if let Some(RefOr::T(Schema::Object(limit))) = limit {
+        limit.description = Some(description);
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/api/v1/health",
@@ -52,70 +421,185 @@ pub async fn health_check() -> impl IntoResponse {
     (StatusCode::OK, Json(ApiResponse::<String>::success("Service is healthy".to_string())))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/health/ready",
+    responses(
+        (status = 200, description = "Service is ready to serve requests", body = ApiResponseString),
+        (status = 503, description = "Draining, migrations pending or database unreachable", body = ApiResponseString)
+    ),
+    tag = "health"
+)]
+pub async fn readiness_check(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    match check_readiness(&state).await {
+        Ok(()) => (StatusCode::OK, Json(ApiResponse::<String>::success("Service is ready".to_string()))),
+        Err(reason) => (StatusCode::SERVICE_UNAVAILABLE, Json(ApiResponse::<String>::error(reason))),
+    }
+}
+
+/// Shared by the readiness endpoint and the heartbeat task.
+pub(crate) async fn check_readiness(state: &AppState) -> Result<(), &'static str> {
+    if state.draining.load(Ordering::Acquire) {
+        return Err("Service is draining");
+    }
+    if !state.migrations_done.load(Ordering::Acquire) {
+        return Err("Database migrations have not run yet");
+    }
+
+    let ping = tokio::time::timeout(READINESS_TIMEOUT, sqlx::query("SELECT 1").execute(&state.db)).await;
+    match ping {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => {
+            warn!("Readiness check failed: {}", e);
+            Err("Database is unreachable")
+        }
+        Err(_) => {
+            warn!("Readiness check timed out after {:?}", READINESS_TIMEOUT);
+            Err("Database is unreachable")
+        }
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/api/v1/todos",
     request_body = CreateTodo,
     responses(
-        (status = 201, description = "Todo created successfully", body = ApiResponseTodo),
-        (status = 400, description = "Invalid input", body = ApiResponseString),
+        (status = 201, description = "Todo created successfully; a text/plain body with several lines creates them all and returns the list (ApiResponseVecTodo)", body = ApiResponseTodo,
+            headers(("Location" = String, description = "URL of the created todo, when only one was created"))),
+        (status = 400, description = "Invalid input, or a text/plain body without titles", body = ApiResponseString),
+        (status = 413, description = "Body too large", body = ApiResponseString),
+        (status = 415, description = "Content-Type is neither application/json nor text/plain", body = ApiResponseString),
         (status = 500, description = "Database error", body = ApiResponseString)
     ),
     tag = "todos"
 )]
 pub async fn create_todo(
     State(state): State<Arc<AppState>>,
-    Json(todo): Json<CreateTodo>,
-) -> Result<impl IntoResponse, AppError> {
+    body: CreateBody,
+) -> Result<Response, AppError> {
+    let now = state.clock.now();
+    let todo = match body {
+        CreateBody::Json(todo) => todo,
+        CreateBody::Plain(text) => {
+            let fields = plain_todos(&text, now)?;
+            let result = state.todos.create_many(now, fields).await?;
+
+            info!("Created {} todos from plain text", result.len());
+            // Una sola línea responde como un create normal
+            return Ok(match <[Todo; 1]>::try_from(result) {
+                Ok([todo]) => {
+                    let location = todo_location(&state, todo.id);
+                    (StatusCode::CREATED, [(LOCATION, location)], Json(ApiResponse::success(todo))).into_response()
+                }
+                Err(todos) => (StatusCode::CREATED, Json(ApiResponse::success(todos))).into_response(),
+            });
+        }
+    };
+
     // Validar entrada
     todo.validate()?;
+    let fields = todo.into_fields(now)?;
 
-    let result = sqlx::query_as::<_, Todo>(
-        r#"
-        INSERT INTO todos (title, completed)
-        VALUES ($1, $2)
-        RETURNING id, title, completed, created_at, updated_at
-        "#
-    )
-    .bind(&todo.title)
-    .bind(todo.completed.unwrap_or(false))
-    .fetch_one(&state.db)
-    .await?;
+    let result = state.todos.create(now, fields).await?;
 
     info!("Todo created successfully with id: {}", result.id);
-    Ok((StatusCode::CREATED, Json(ApiResponse::success(result))))
+    let location = todo_location(&state, result.id);
+    Ok((StatusCode::CREATED, [(LOCATION, location)], Json(ApiResponse::success(result))).into_response())
+}
+
+/// Absolute path of a todo, under the BASE_PATH the service is mounted at.
+fn todo_location(state: &AppState, id: Uuid) -> String {
+    format!("{}/api/v1/todos/{}", state.config.base_path, id)
+}
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct QuickAddQuery {
+    #[schema(example = "Europe/Madrid")]
+    /// IANA timezone for a natural-language due date (default UTC)
+    tz: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/quick-add",
+    params(QuickAddQuery),
+    request_body(
+        content = String,
+        content_type = "text/plain",
+        description = "The title, optionally followed by ` @ ` and a due date such as `tomorrow at 17:00`",
+        example = json!("Call the plumber @ tomorrow at 17:00")
+    ),
+    responses(
+        (status = 201, description = "Todo created successfully", body = ApiResponseTodo,
+            headers(("Location" = String, description = "URL of the created todo"))),
+        (status = 400, description = "Invalid title or due date, or more than one line", body = ApiResponseString),
+        (status = 404, description = "The quick-add flag is disabled for this request", body = ApiResponseString),
+        (status = 415, description = "Content-Type is not text/plain", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn quick_add(
+    State(state): State<Arc<AppState>>,
+    features: RequestFeatures,
+    Query(query): Query<QuickAddQuery>,
+    text: String,
+) -> Result<impl IntoResponse, AppError> {
+    // La ruta solo se monta con el flag al arrancar; un override puede apagarla
+    if !features.is_enabled(features::QUICK_ADD) {
+        return Err(AppError::NotFound);
+    }
+
+    let text = text.trim();
+    if text.lines().count() > 1 {
+        return Err(AppError::ValidationError(
+            "Quick add takes a single line, use POST /api/v1/todos for several".to_string(),
+        ));
+    }
+    let (title, due_date) = match text.rsplit_once(" @ ") {
+        Some((title, due_date)) => (title.trim(), Some(due_date.trim().to_string())),
+        None => (text, None),
+    };
+    let todo = CreateTodo {
+        title: title.to_string(),
+        description: None,
+        completed: None,
+        due_date,
+        tz: query.tz,
+    };
+
+    let now = state.clock.now();
+    todo.validate()?;
+    let fields = todo.into_fields(now)?;
+    let result = state.todos.create(now, fields).await?;
+
+    info!("Todo quick-added with id: {}", result.id);
+    let location = todo_location(&state, result.id);
+    Ok((StatusCode::CREATED, [(LOCATION, location)], Json(ApiResponse::success(result))))
 }
 
 #[utoipa::path(
     get,
     path = "/api/v1/todos",
-    params(PaginationQuery),
+    params(PaginationQuery, FilterQuery),
     responses(
         (status = 200, description = "List of todos retrieved successfully", body = ApiResponseVecTodo),
+        (status = 400, description = "Invalid pagination or filter expression (with the column of the problem)", body = ApiResponseString),
         (status = 500, description = "Database error", body = ApiResponseString)
     ),
     tag = "todos"
 )]
 pub async fn get_todos(
     State(state): State<Arc<AppState>>,
-    Query(pagination): Query<PaginationQuery>,
+    pagination: Result<Query<PaginationQuery>, QueryRejection>,
+    Query(query): Query<FilterQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    let page = pagination.page.unwrap_or(1).max(1);
-    let limit = pagination.limit.unwrap_or(10).min(100).max(1);
-    let offset = (page - 1) * limit;
-
-    let todos = sqlx::query_as::<_, Todo>(
-        r#"
-        SELECT id, title, completed, created_at, updated_at
-        FROM todos
-        ORDER BY created_at DESC
-        LIMIT $1 OFFSET $2
-        "#
-    )
-    .bind(limit as i64)
-    .bind(offset as i64)
-    .fetch_all(&state.db)
-    .await?;
+    let Query(pagination) = pagination?;
+    let (page, limit, offset) = pagination.resolve(&state.config)?;
+    let filter = query.expr()?;
+
+    let todos = state.todos.list(filter.as_ref(), limit.into(), offset).await?;
 
     info!("Retrieved {} todos (page: {}, limit: {})", todos.len(), page, limit);
     Ok((StatusCode::OK, Json(ApiResponse::success(todos))))
@@ -138,16 +622,7 @@ pub async fn get_todo(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    let todo = sqlx::query_as::<_, Todo>(
-        r#"
-        SELECT id, title, completed, created_at, updated_at
-        FROM todos
-        WHERE id = $1
-        "#
-    )
-    .bind(id)
-    .fetch_optional(&state.db)
-    .await?;
+    let todo = state.todos.get(id).await?;
 
     match todo {
         Some(todo) => {
@@ -161,17 +636,49 @@ pub async fn get_todo(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/{id}/description.html",
+    params(
+        ("id" = Uuid, Path, description = "Todo ID")
+    ),
+    responses(
+        (status = 200, description = "Description rendered from Markdown to sanitized HTML (tables and task-list checkboxes enabled, raw HTML and unsafe links stripped)", body = String, content_type = "text/html"),
+        (status = 404, description = "Todo not found", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn get_todo_description_html(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AppError> {
+    match state.todos.get(id).await? {
+        Some(Todo { description, .. }) => {
+            info!("Rendering description for todo with id: {}", id);
+            Ok(Html(markdown::render_html(description.as_deref().unwrap_or_default())))
+        }
+        None => {
+            info!("Todo not found for description rendering with id: {}", id);
+            Err(AppError::NotFound)
+        }
+    }
+}
+
 #[utoipa::path(
     put,
     path = "/api/v1/todos/{id}",
     params(
         ("id" = Uuid, Path, description = "Todo ID")
     ),
-    request_body = CreateTodo,
+    request_body = ReplaceTodo,
     responses(
-        (status = 200, description = "Todo updated successfully", body = ApiResponseTodo),
-        (status = 404, description = "Todo not found", body = ApiResponseString),
-        (status = 400, description = "Invalid input", body = ApiResponseString),
+        (status = 200, description = "Todo replaced successfully", body = ApiResponseTodo),
+        (status = 201, description = "Todo created with this id (only with PUT_CREATES=true)", body = ApiResponseTodo,
+            headers(("Location" = String, description = "URL of the created todo"))),
+        (status = 404, description = "Todo not found (never with PUT_CREATES=true)", body = ApiResponseString),
+        (status = 400, description = "Invalid input or a field is missing (use PATCH for partial updates)", body = ApiResponseString),
+        (status = 415, description = "Content-Type is not application/json", body = ApiResponseString),
         (status = 500, description = "Database error", body = ApiResponseString)
     ),
     tag = "todos"
@@ -179,29 +686,30 @@ pub async fn get_todo(
 pub async fn update_todo(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
-    Json(todo): Json<CreateTodo>,
-) -> Result<impl IntoResponse, AppError> {
+    Json(todo): Json<ReplaceTodo>,
+) -> Result<Response, AppError> {
     // Validar entrada
     todo.validate()?;
+    let now = state.clock.now();
+    let fields = todo.into_fields(now)?;
 
-    let updated_todo = sqlx::query_as::<_, Todo>(
-        r#"
-        UPDATE todos 
-        SET title = $1, completed = $2, updated_at = NOW()
-        WHERE id = $3
-        RETURNING id, title, completed, created_at, updated_at
-        "#
-    )
-    .bind(&todo.title)
-    .bind(todo.completed.unwrap_or(false))
-    .bind(id)
-    .fetch_optional(&state.db)
-    .await?;
+    if state.config.put_creates {
+        let (todo, created) = state.todos.upsert(id, now, fields).await?;
+        if created {
+            info!("Todo created by PUT with id: {}", id);
+            let location = todo_location(&state, id);
+            return Ok((StatusCode::CREATED, [(LOCATION, location)], Json(ApiResponse::success(todo))).into_response());
+        }
+        info!("Todo updated successfully with id: {}", id);
+        return Ok((StatusCode::OK, Json(ApiResponse::success(todo))).into_response());
+    }
+
+    let updated_todo = state.todos.update(id, now, fields).await?;
 
     match updated_todo {
         Some(todo) => {
             info!("Todo updated successfully with id: {}", id);
-            Ok((StatusCode::OK, Json(ApiResponse::success(todo))))
+            Ok((StatusCode::OK, Json(ApiResponse::success(todo))).into_response())
         }
         None => {
             info!("Todo not found for update with id: {}", id);
@@ -210,6 +718,61 @@ pub async fn update_todo(
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/api/v1/todos/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Todo ID")
+    ),
+    request_body(
+        content = PatchTodo,
+        description = "Fields to change as `application/json`, or an RFC 6902 JSON Patch as `application/json-patch+json`"
+    ),
+    responses(
+        (status = 200, description = "Todo updated successfully", body = ApiResponseTodo),
+        (status = 404, description = "Todo not found", body = ApiResponseString),
+        (status = 400, description = "Invalid input", body = ApiResponseString),
+        (status = 409, description = "A JSON Patch `test` operation failed", body = ApiResponseString),
+        (status = 415, description = "Content-Type is not application/json or application/json-patch+json", body = ApiResponseString),
+        (status = 422, description = "A JSON Patch operation is malformed or targets a read-only or unknown field", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn patch_todo(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    body: PatchBody,
+) -> Result<impl IntoResponse, AppError> {
+    let now = state.clock.now();
+    let updated_todo = match body {
+        PatchBody::Merge(patch) => {
+            // Validar entrada
+            patch.validate()?;
+            let current = state.todos.get(id).await?.ok_or(AppError::NotFound)?;
+            let fields = patch.apply(current, now)?;
+            state.todos.update(id, now, fields).await?
+        }
+        PatchBody::JsonPatch(operations) => {
+            state
+                .todos
+                .update_with(id, now, |current| json_patch_fields(&operations, current, now))
+                .await?
+        }
+    };
+
+    match updated_todo {
+        Some(todo) => {
+            info!("Todo patched successfully with id: {}", id);
+            Ok((StatusCode::OK, Json(ApiResponse::success(todo))))
+        }
+        None => {
+            info!("Todo not found for patch with id: {}", id);
+            Err(AppError::NotFound)
+        }
+    }
+}
+
 #[utoipa::path(
     delete,
     path = "/api/v1/todos/{id}",
@@ -227,21 +790,338 @@ pub async fn delete_todo(
     State(state): State<Arc<AppState>>,
     Path(id): Path<Uuid>,
 ) -> Result<impl IntoResponse, AppError> {
-    let result = sqlx::query(
-        r#"
-        DELETE FROM todos 
-        WHERE id = $1
-        "#
-    )
-    .bind(id)
-    .execute(&state.db)
-    .await?;
-
-    if result.rows_affected() > 0 {
+    if state.todos.delete(id).await? {
         info!("Todo deleted successfully with id: {}", id);
         Ok((StatusCode::OK, Json(ApiResponse::<String>::success("Todo deleted successfully".to_string()))))
     } else {
         info!("Todo not found for deletion with id: {}", id);
         Err(AppError::NotFound)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::{clock::MockClock, error::AppError, testing};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    fn create(body: serde_json::Value) -> axum::extract::Request {
+        testing::json_request(Method::POST, "/api/v1/todos", &body)
+    }
+
+    #[sqlx::test]
+    async fn due_dates_are_checked_against_the_clock_with_the_skew_tolerance(db: PgPool) {
+        let clock = MockClock::new(now());
+        let config = testing::config(&[("DUE_DATE_MUST_BE_FUTURE", "true"), ("DUE_DATE_SKEW_TOLERANCE_SECS", "60")]);
+        let state = testing::state_at(db, config, &clock);
+
+        let at_tolerance = create(json!({"title": "Just now", "due_date": "2030-01-01T11:59:00Z"}));
+        assert_eq!(testing::send(&state, at_tolerance).await.status(), StatusCode::CREATED);
+        let past_tolerance = create(json!({"title": "Too late", "due_date": "2030-01-01T11:58:59Z"}));
+        assert_eq!(testing::send(&state, past_tolerance).await.status(), StatusCode::BAD_REQUEST);
+
+        // Lo que era futuro deja de serlo cuando el reloj avanza
+        let soon = json!({"title": "Soon", "due_date": "2030-01-01T12:30:00Z"});
+        clock.advance(Duration::hours(1));
+        let response = testing::send(&state, create(soon)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = testing::json(response).await;
+        assert!(body["error"].as_str().unwrap().contains("2030-01-01T12:59:00Z"), "{}", body);
+
+        let response = testing::send(&state, create(json!({"title": "Later", "due_date": "in 2 days"}))).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(testing::json(response).await["data"]["due_date"], "2030-01-03T13:00:00.000Z");
+    }
+
+    fn page(page: &str, limit: &str) -> super::PaginationQuery {
+        super::PaginationQuery { page: page.parse().ok(), limit: limit.parse().ok() }
+    }
+
+    #[test]
+    fn pagination_offsets_do_not_overflow_and_stop_at_the_maximum() {
+        let config = testing::config(&[("PAGINATION_MAX_LIMIT", "100"), ("PAGINATION_MAX_OFFSET", "100000")]);
+
+        assert_eq!(page("1001", "100").resolve(&config).unwrap(), (1001, 100, 100_000));
+        for (page_number, limit) in [("1002", "100"), ("42949673", "100"), ("4294967295", "100"), ("4294967295", "4294967295")] {
+            let Err(AppError::ValidationError(message)) = page(page_number, limit).resolve(&config) else {
+                panic!("page {} with limit {} should be rejected", page_number, limit);
+            };
+            assert!(message.contains("offsets are limited to 100000 items"), "{}", message);
+        }
+        // page=0 es la primera página y limit se acota
+        assert_eq!(page("0", "0").resolve(&config).unwrap(), (1, 1, 0));
+        assert_eq!(page("2", "4294967295").resolve(&config).unwrap(), (2, 100, 100));
+    }
+
+    #[sqlx::test]
+    async fn too_deep_or_unrepresentable_pages_get_a_400_in_the_envelope(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[("PAGINATION_MAX_OFFSET", "20")]));
+        testing::insert_todo(&db, "Only").await;
+
+        let response = testing::send(&state, testing::get("/api/v1/todos?page=3&limit=10")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        for uri in ["/api/v1/todos?page=4&limit=10", "/api/v1/todos?page=42949673&limit=100", "/api/v1/todos?page=4294967296"] {
+            let response = testing::send(&state, testing::get(uri)).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", uri);
+            let body = testing::json(response).await;
+            assert_eq!(body["status"], "error", "{}", uri);
+            assert!(body["error"].is_string(), "{}", uri);
+        }
+    }
+
+    #[sqlx::test]
+    async fn put_rejects_omitted_fields_and_leaves_the_todo_alone(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let id = testing::insert_todo(&db, "Buy milk").await;
+        sqlx::query("UPDATE todos SET completed = true, description = 'Two liters' WHERE id = $1")
+            .bind(id)
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let request = testing::json_request(Method::PUT, &format!("/api/v1/todos/{}", id), &json!({"title": "Buy oat milk"}));
+        let response = testing::send(&state, request).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let message = testing::json(response).await["error"].as_str().unwrap().to_string();
+        for field in ["description", "completed", "due_date"] {
+            assert!(
+                message.contains(&format!("{} is required because PUT replaces the whole todo, use PATCH", field)),
+                "{}",
+                message
+            );
+        }
+        assert!(!message.contains("title is required"), "{}", message);
+        let todo = testing::json(testing::send(&state, testing::get(&format!("/api/v1/todos/{}", id))).await).await;
+        assert_eq!(todo["data"]["title"], "Buy milk");
+        assert_eq!(todo["data"]["completed"], true);
+        assert_eq!(todo["data"]["description"], "Two liters");
+    }
+
+    #[sqlx::test]
+    async fn put_replaces_every_field_and_null_clears(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let id = testing::insert_todo(&db, "Buy milk").await;
+        let uri = format!("/api/v1/todos/{}", id);
+
+        let replacement = json!({
+            "title": "Call the plumber",
+            "description": "About the leak",
+            "completed": true,
+            "due_date": "2030-06-01T10:00:00Z"
+        });
+        let response = testing::send(&state, testing::json_request(Method::PUT, &uri, &replacement)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let todo = &testing::json(response).await["data"];
+        assert_eq!(
+            (&todo["title"], &todo["description"], &todo["completed"], &todo["due_date"]),
+            (&json!("Call the plumber"), &json!("About the leak"), &json!(true), &json!("2030-06-01T10:00:00.000Z"))
+        );
+
+        let cleared = json!({"title": "Call the plumber", "description": null, "completed": false, "due_date": null});
+        let todo = &testing::json(testing::send(&state, testing::json_request(Method::PUT, &uri, &cleared)).await).await["data"];
+        assert_eq!((&todo["description"], &todo["completed"], &todo["due_date"]), (&json!(null), &json!(false), &json!(null)));
+
+        let missing = testing::json_request(Method::PUT, &format!("/api/v1/todos/{}", uuid::Uuid::new_v4()), &cleared);
+        assert_eq!(testing::send(&state, missing).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn every_update_path_bumps_updated_at_and_keeps_created_at(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let upserting = testing::state(db.clone(), testing::config(&[("PUT_CREATES", "true")]));
+        let id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO todos (title, created_at, updated_at) VALUES ('Buy milk', '2020-01-01Z', '2020-01-01Z') RETURNING id",
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        let uri = format!("/api/v1/todos/{}", id);
+        let replacement = json!({"title": "Buy oat milk", "description": null, "completed": false, "due_date": null});
+        let json_patch = r#"[{"op": "replace", "path": "/completed", "value": true}]"#;
+        let requests = [
+            ("PUT", &state, testing::json_request(Method::PUT, &uri, &replacement)),
+            ("PUT upserting", &upserting, testing::json_request(Method::PUT, &uri, &replacement)),
+            ("PATCH", &state, testing::json_request(Method::PATCH, &uri, &json!({"description": "Two liters"}))),
+            (
+                "JSON Patch",
+                &state,
+                testing::request(Method::PATCH, &uri, Some("application/json-patch+json"), json_patch.into()),
+            ),
+        ];
+
+        let timestamps = || {
+            sqlx::query_as::<_, (DateTime<Utc>, DateTime<Utc>)>("SELECT created_at, updated_at FROM todos WHERE id = $1")
+                .bind(id)
+                .fetch_one(&db)
+        };
+        let created_at = Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap();
+        let mut last_updated_at = created_at;
+        for (path, state, request) in requests {
+            let response = testing::send(state, request).await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", path);
+            let todo = testing::json(response).await;
+
+            let (stored_created_at, updated_at) = timestamps().await.unwrap();
+            assert_eq!(stored_created_at, created_at, "{}", path);
+            assert!(updated_at > last_updated_at, "{}: {} is not after {}", path, updated_at, last_updated_at);
+            // La respuesta lleva lo que puso el trigger, no lo que había
+            let stored = testing::json(testing::send(state, testing::get(&uri)).await).await;
+            assert_eq!(todo["data"]["created_at"], "2020-01-01T00:00:00.000Z", "{}", path);
+            assert_eq!(todo["data"]["updated_at"], stored["data"]["updated_at"], "{}", path);
+            last_updated_at = updated_at;
+        }
+
+        // Tampoco un UPDATE escrito a mano puede fijar los timestamps
+        sqlx::query("UPDATE todos SET title = 'By hand', created_at = '2025-01-01Z', updated_at = '2000-01-01Z' WHERE id = $1")
+            .bind(id)
+            .execute(&db)
+            .await
+            .unwrap();
+        let (stored_created_at, updated_at) = timestamps().await.unwrap();
+        assert_eq!(stored_created_at, created_at);
+        assert!(updated_at > last_updated_at);
+    }
+
+    #[sqlx::test]
+    async fn single_creates_point_at_the_new_todo(db: PgPool) {
+        use axum::{body::Body, http::header::LOCATION};
+
+        let state = testing::state(db, testing::config(&[]));
+        let response = testing::send(&state, create(json!({"title": "Buy milk"}))).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[LOCATION].to_str().unwrap().to_string();
+        let todo = testing::json(response).await;
+        assert_eq!(location, format!("/api/v1/todos/{}", todo["data"]["id"].as_str().unwrap()));
+
+        let one_line = testing::request(Method::POST, "/api/v1/todos", Some("text/plain"), Body::from("Buy eggs\n"));
+        let response = testing::send(&state, one_line).await;
+        assert!(response.headers()[LOCATION].to_str().unwrap().starts_with("/api/v1/todos/"));
+        let several = testing::request(Method::POST, "/api/v1/todos", Some("text/plain"), Body::from("Buy eggs\nBuy bread"));
+        let response = testing::send(&state, several).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().get(LOCATION).is_none());
+    }
+
+    #[sqlx::test]
+    async fn due_dates_round_trip_in_the_canonical_form(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        for (due_date, tz, canonical) in [
+            ("2024-05-01T10:00:00Z", None, "2024-05-01T10:00:00.000Z"),
+            ("2024-05-01T12:00:00+02:00", None, "2024-05-01T10:00:00.000Z"),
+            ("2024-05-01T10:00:00.123456-00:00", None, "2024-05-01T10:00:00.123Z"),
+            ("2024-05-01 05:30:00-0430", None, "2024-05-01T10:00:00.000Z"),
+            ("2024-05-01", None, "2024-05-01T00:00:00.000Z"),
+            ("2024-05-01", Some("Europe/Madrid"), "2024-04-30T22:00:00.000Z"),
+            // Ignora tz cuando el instante ya lleva su desplazamiento
+            ("2024-05-01T10:00:00Z", Some("Europe/Madrid"), "2024-05-01T10:00:00.000Z"),
+        ] {
+            let body = json!({"title": "Due", "due_date": due_date, "tz": tz});
+            let response = testing::send(&state, testing::json_request(Method::POST, "/api/v1/todos", &body)).await;
+            assert_eq!(response.status(), StatusCode::CREATED, "{}", due_date);
+            let todo = testing::json(response).await["data"].clone();
+            assert_eq!(todo["due_date"], canonical, "{}", due_date);
+
+            // Lo que sale se puede volver a enviar y no cambia
+            let uri = format!("/api/v1/todos/{}", todo["id"].as_str().unwrap());
+            let replaced = json!({"title": "Due", "description": null, "completed": false, "due_date": todo["due_date"]});
+            let response = testing::send(&state, testing::json_request(Method::PUT, &uri, &replaced)).await;
+            assert_eq!(testing::json(response).await["data"]["due_date"], canonical, "{}", due_date);
+        }
+
+        // created_at y updated_at vienen de Postgres con microsegundos
+        let id = testing::insert_todo(&db, "Stored").await;
+        sqlx::query("UPDATE todos SET due_date = '2030-01-01T00:00:00.987654Z' WHERE id = $1").bind(id).execute(&db).await.unwrap();
+        let todo = testing::json(testing::send(&state, testing::get(&format!("/api/v1/todos/{}", id))).await).await;
+        for field in ["created_at", "updated_at", "due_date"] {
+            let value = todo["data"][field].as_str().unwrap();
+            assert!(value.len() == 24 && value.ends_with('Z') && value.as_bytes()[19] == b'.', "{}: {}", field, value);
+        }
+        assert_eq!(todo["data"]["due_date"], "2030-01-01T00:00:00.987Z");
+    }
+
+    #[sqlx::test]
+    async fn rejects_unparseable_due_dates(db: PgPool) {
+        let state = testing::state(db, testing::config(&[]));
+        for due_date in ["2024-13-01", "2024-05-01T25:00:00Z", "soonish"] {
+            let body = json!({"title": "Due", "due_date": due_date});
+            let response = testing::send(&state, testing::json_request(Method::POST, "/api/v1/todos", &body)).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", due_date);
+        }
+    }
+
+    async fn change_ops(db: &PgPool, id: uuid::Uuid) -> Vec<String> {
+        sqlx::query_scalar("SELECT op FROM todo_changes WHERE todo_id = $1 ORDER BY seq")
+            .bind(id)
+            .fetch_all(db)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn put_creates_a_missing_todo_only_with_put_creates(db: PgPool) {
+        use axum::http::header::LOCATION;
+
+        let body = json!({"title": "Synced", "description": "From the phone", "completed": false, "due_date": null});
+        let id = uuid::Uuid::new_v4();
+        let uri = format!("/api/v1/todos/{}", id);
+
+        // Sin el flag, exactamente el 404 de siempre y nada escrito
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let response = testing::send(&state, testing::json_request(Method::PUT, &uri, &body)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(testing::json(response).await, json!({"status": "error", "data": null, "error": "Resource not found"}));
+        assert_eq!(change_ops(&db, id).await, Vec::<String>::new());
+
+        let state = testing::state(db.clone(), testing::config(&[("PUT_CREATES", "true"), ("BASE_PATH", "/todo")]));
+        let invalid = json!({"title": "", "description": null, "completed": false, "due_date": null});
+        let response = testing::send(&state, testing::json_request(Method::PUT, &format!("/todo{}", uri), &invalid)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(change_ops(&db, id).await, Vec::<String>::new());
+
+        let response = testing::send(&state, testing::json_request(Method::PUT, &format!("/todo{}", uri), &body)).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[LOCATION], format!("/todo{}", uri));
+        let created = testing::json(response).await["data"].clone();
+        assert_eq!((&created["id"], &created["title"]), (&json!(id.to_string()), &json!("Synced")));
+        assert_eq!(change_ops(&db, id).await, ["created"]);
+
+        let replacement = json!({"title": "Synced again", "description": null, "completed": true, "due_date": null});
+        let response = testing::send(&state, testing::json_request(Method::PUT, &format!("/todo{}", uri), &replacement)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(LOCATION).is_none());
+        let updated = testing::json(response).await["data"].clone();
+        assert_eq!((&updated["title"], &updated["description"], &updated["completed"]), (&json!("Synced again"), &json!(null), &json!(true)));
+        assert_eq!(updated["created_at"], created["created_at"]);
+        assert_eq!(change_ops(&db, id).await, ["created", "updated"]);
+
+        // Sin el flag, el PUT a un todo existente sigue actualizándolo
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let response = testing::send(&state, testing::json_request(Method::PUT, &uri, &body)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(change_ops(&db, id).await, ["created", "updated", "updated"]);
+    }
+
+    #[sqlx::test]
+    async fn concurrent_puts_to_the_same_new_id_create_it_once(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[("PUT_CREATES", "true")]));
+        let id = uuid::Uuid::new_v4();
+        let uri = format!("/api/v1/todos/{}", id);
+        let put = |title: &str| {
+            let body = json!({"title": title, "description": null, "completed": false, "due_date": null});
+            testing::send(&state, testing::json_request(Method::PUT, &uri, &body))
+        };
+
+        let (first, second) = tokio::join!(put("First"), put("Second"));
+
+        let mut statuses = [first.status(), second.status()];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::CREATED]);
+        assert_eq!(change_ops(&db, id).await, ["created", "updated"]);
+    }
+}
@@ -0,0 +1,119 @@
+use std::hash::{BuildHasher, RandomState};
+use std::sync::{atomic::Ordering, Arc};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use crate::{handler::check_readiness, model::AppState};
+
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+/// Each wait is stretched by up to this fraction of the interval.
+const MAX_JITTER: f64 = 0.1;
+
+/// Pings `HEARTBEAT_URL` every `HEARTBEAT_INTERVAL_SECS` for a dead man's
+/// switch monitor (healthchecks.io style).
+///
+/// A ready service sends `GET <url>`; otherwise `POST <url>/fail` with the
+/// reason as the body, which such monitors keep in the ping log. Stops once a
+/// shutdown has started so a planned exit is not reported as a failure.
+pub async fn run(state: Arc<AppState>, url: String, interval: Duration) {
+    let client = match reqwest::Client::builder().timeout(PING_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Heartbeat disabled, could not build HTTP client: {}", e);
+            return;
+        }
+    };
+    let url = url.trim_end_matches('/').to_string();
+    info!("💓 Heartbeat enabled, pinging every {:?}", interval);
+
+    loop {
+        tokio::time::sleep(interval + jitter(interval)).await;
+        if state.shutting_down.load(Ordering::Acquire) {
+            info!("Heartbeat stopped");
+            return;
+        }
+
+        let request = match check_readiness(&state).await {
+            Ok(()) => client.get(&url),
+            Err(reason) => client.post(format!("{}/fail", url)).body(reason),
+        };
+        match request.send().await.and_then(|response| response.error_for_status()) {
+            Ok(response) => debug!("Heartbeat sent to {}", response.url()),
+            Err(e) => warn!("Heartbeat ping failed: {}", e),
+        }
+    }
+}
+
+/// A random share of `interval` so a fleet started together does not ping in sync.
+fn jitter(interval: Duration) -> Duration {
+    let random = RandomState::new().hash_one(0u8) as f64 / u64::MAX as f64;
+    interval.mul_f64(random * MAX_JITTER)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{extract::{Request, State}, http::StatusCode, Router};
+    use sqlx::PgPool;
+    use std::sync::{atomic::Ordering, Arc, Mutex};
+    use std::time::Duration;
+    use crate::testing;
+
+    /// Pings received by the monitor, as (method, path, body).
+    type Pings = Arc<Mutex<Vec<(String, String, String)>>>;
+
+    /// A monitor that records every ping and answers the first one with a 500.
+    async fn monitor() -> (String, Pings) {
+        let pings = Pings::default();
+        let app = Router::new().fallback(|State(pings): State<Pings>, request: Request| async move {
+            let (method, path) = (request.method().to_string(), request.uri().path().to_string());
+            let body = axum::body::to_bytes(request.into_body(), usize::MAX).await.unwrap().to_vec();
+            let mut pings = pings.lock().unwrap();
+            pings.push((method, path, String::from_utf8(body).unwrap()));
+            if pings.len() == 1 { StatusCode::INTERNAL_SERVER_ERROR } else { StatusCode::OK }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/ping/", listener.local_addr().unwrap());
+        let app = app.with_state(pings.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, pings)
+    }
+
+    async fn wait_for_pings(pings: &Pings, count: usize) -> Vec<(String, String, String)> {
+        for _ in 0..200 {
+            if pings.lock().unwrap().len() >= count {
+                return pings.lock().unwrap().clone();
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("expected {} pings, got {:?}", count, pings.lock().unwrap());
+    }
+
+    #[sqlx::test]
+    async fn pings_while_ready_and_reports_failures_with_the_reason(db: PgPool) {
+        let (url, pings) = monitor().await;
+        let state = testing::state(db, testing::config(&[]));
+        let heartbeat = tokio::spawn(super::run(state.clone(), url, Duration::from_millis(20)));
+
+        // El 500 del primer ping no detiene el bucle
+        let received = wait_for_pings(&pings, 2).await;
+        for (method, path, body) in &received[..2] {
+            assert_eq!((method.as_str(), path.as_str(), body.as_str()), ("GET", "/ping", ""));
+        }
+
+        state.draining.store(true, Ordering::Release);
+        let count = pings.lock().unwrap().len();
+        let received = wait_for_pings(&pings, count + 2).await;
+        let (method, path, body) = received.last().unwrap();
+        assert_eq!((method.as_str(), path.as_str(), body.as_str()), ("POST", "/ping/fail", "Service is draining"));
+
+        state.shutting_down.store(true, Ordering::Release);
+        tokio::time::timeout(Duration::from_secs(1), heartbeat).await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn jitter_stays_within_a_tenth_of_the_interval() {
+        let interval = Duration::from_secs(60);
+        for _ in 0..100 {
+            assert!(super::jitter(interval) <= interval.mul_f64(super::MAX_JITTER));
+        }
+    }
+}
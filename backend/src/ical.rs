@@ -0,0 +1,509 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// A VTODO component mapped onto the fields a todo understands.
+pub struct IcsTodo {
+    pub uid: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub completed: bool,
+    pub due_date: Option<DateTime<Utc>>,
+    /// The component carried an RRULE, which is not imported.
+    pub recurring: bool,
+}
+
+/// One VTODO found in the calendar, in file order (`index` starts at 1).
+pub struct Component {
+    pub index: usize,
+    pub uid: Option<String>,
+    pub result: Result<IcsTodo, String>,
+}
+
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Extracts every VTODO of an iCalendar (RFC 5545) document.
+///
+/// A document that is not a VCALENDAR is an error; a malformed VTODO only
+/// fails its own [`Component`] so the rest of the file can still be imported.
+pub fn parse_vtodos(input: &str) -> Result<Vec<Component>, String> {
+    let lines = unfold(input);
+    if !lines.first().is_some_and(|line| line.eq_ignore_ascii_case("BEGIN:VCALENDAR")) {
+        return Err("expected BEGIN:VCALENDAR on the first line".to_string());
+    }
+
+    let mut components = Vec::new();
+    let mut current: Option<Vec<Result<Property, String>>> = None;
+    // Profundidad de componentes anidados dentro de un VTODO (p. ej. VALARM)
+    let mut nested = 0usize;
+
+    for (line_number, line) in lines.iter().enumerate() {
+        let upper = line.to_ascii_uppercase();
+        match current.as_mut() {
+            None => {
+                if upper == "BEGIN:VTODO" {
+                    current = Some(Vec::new());
+                }
+            }
+            // Un VTODO sin END no se traga el siguiente ni el final del calendario
+            Some(_) if nested == 0 && (upper == "BEGIN:VTODO" || upper == "END:VCALENDAR") => {
+                let properties = current.take().unwrap_or_default();
+                components.push(unterminated(components.len() + 1, properties));
+                if upper == "BEGIN:VTODO" {
+                    current = Some(Vec::new());
+                }
+            }
+            Some(properties) => {
+                if upper.starts_with("BEGIN:") {
+                    nested += 1;
+                } else if upper.starts_with("END:") && nested > 0 {
+                    nested -= 1;
+                } else if upper == "END:VTODO" {
+                    let properties = current.take().unwrap_or_default();
+                    components.push(build_component(components.len() + 1, properties));
+                } else if nested == 0 {
+                    properties.push(
+                        parse_property(line)
+                            .map_err(|e| format!("line {}: {}", line_number + 1, e)),
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(properties) = current {
+        components.push(unterminated(components.len() + 1, properties));
+    }
+
+    Ok(components)
+}
+
+fn build_component(index: usize, properties: Vec<Result<Property, String>>) -> Component {
+    let uid = find_uid(&properties);
+
+    Component {
+        index,
+        uid: uid.clone(),
+        result: properties
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|properties| to_todo(uid, &properties)),
+    }
+}
+
+/// A VTODO cut short by the next one or by the end of the file.
+fn unterminated(index: usize, properties: Vec<Result<Property, String>>) -> Component {
+    Component {
+        index,
+        uid: find_uid(&properties),
+        result: Err("missing END:VTODO".to_string()),
+    }
+}
+
+fn find_uid(properties: &[Result<Property, String>]) -> Option<String> {
+    properties
+        .iter()
+        .flatten()
+        .find(|property| property.name == "UID")
+        .map(|property| unescape_text(&property.value))
+}
+
+fn to_todo(uid: Option<String>, properties: &[Property]) -> Result<IcsTodo, String> {
+    let find = |name: &str| properties.iter().find(|property| property.name == name);
+
+    let title = find("SUMMARY")
+        .map(|property| unescape_text(&property.value).trim().to_string())
+        .filter(|title| !title.is_empty())
+        .ok_or("SUMMARY is missing or empty")?;
+    if title.chars().count() > 255 {
+        return Err("SUMMARY must be at most 255 characters".to_string());
+    }
+
+    let description = find("DESCRIPTION")
+        .map(|property| unescape_text(&property.value).trim().to_string())
+        .filter(|description| !description.is_empty());
+
+    let completed = find("STATUS").is_some_and(|property| property.value.eq_ignore_ascii_case("COMPLETED"))
+        || find("COMPLETED").is_some();
+
+    let due_date = find("DUE")
+        .map(|property| parse_date_time(property).map_err(|e| format!("DUE: {}", e)))
+        .transpose()?;
+
+    Ok(IcsTodo {
+        uid,
+        title,
+        description,
+        completed,
+        due_date,
+        recurring: find("RRULE").is_some(),
+    })
+}
+
+/// Resolves DATE, UTC, TZID-qualified and floating DATE-TIME values to UTC.
+/// Floating times and plain dates are taken as UTC.
+fn parse_date_time(property: &Property) -> Result<DateTime<Utc>, String> {
+    let value = property.value.trim();
+
+    if property.param("VALUE").is_some_and(|kind| kind.eq_ignore_ascii_case("DATE")) || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map_err(|_| format!("invalid date {:?}", value))?;
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        let local = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map_err(|_| format!("invalid date-time {:?}", value))?;
+        return Ok(local.and_utc());
+    }
+
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .map_err(|_| format!("invalid date-time {:?}", value))?;
+
+    match property.param("TZID") {
+        None => Ok(local.and_utc()),
+        Some(tzid) => {
+            let tz = resolve_tzid(tzid).ok_or_else(|| format!("unknown TZID {:?}", tzid))?;
+            tz.from_local_datetime(&local)
+                .earliest()
+                .map(|date_time| date_time.with_timezone(&Utc))
+                .ok_or_else(|| format!("{} does not exist in {}", value, tzid))
+        }
+    }
+}
+
+/// Looks up an IANA zone, tolerating vendor prefixes such as
+/// `/mozilla.org/20050126_1/Europe/Madrid`.
+fn resolve_tzid(tzid: &str) -> Option<Tz> {
+    std::iter::once(tzid)
+        .chain(tzid.match_indices('/').map(|(position, _)| &tzid[position + 1..]))
+        .find_map(|name| name.parse().ok())
+}
+
+/// Joins folded lines (a line break followed by a space or tab) and drops blanks.
+fn unfold(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in input.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ if raw.trim().is_empty() => {}
+            _ => lines.push(raw.to_string()),
+        }
+    }
+    lines
+}
+
+/// Splits `NAME;PARAM=value;PARAM="quoted":VALUE`.
+fn parse_property(line: &str) -> Result<Property, String> {
+    let mut in_quotes = false;
+    let colon = line
+        .char_indices()
+        .find(|&(_, c)| {
+            if c == '"' {
+                in_quotes = !in_quotes;
+            }
+            c == ':' && !in_quotes
+        })
+        .map(|(position, _)| position)
+        .ok_or_else(|| format!("malformed property {:?}", line))?;
+
+    let head = &line[..colon];
+    let name_end = head.find(';').unwrap_or(head.len());
+    let name = head[..name_end].trim().to_ascii_uppercase();
+    if name.is_empty() {
+        return Err(format!("malformed property {:?}", line));
+    }
+
+    let mut params = Vec::new();
+    for part in split_params(&head[name_end..]) {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| format!("malformed parameter {:?}", part))?;
+        params.push((key.trim().to_string(), value.trim().trim_matches('"').to_string()));
+    }
+
+    Ok(Property {
+        name,
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+/// Splits the `;PARAM=value;...` tail of a property name, honoring quotes.
+fn split_params(tail: &str) -> Vec<&str> {
+    let mut params = Vec::new();
+    let mut in_quotes = false;
+    let mut start = None;
+    for (position, c) in tail.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                if let Some(start) = start {
+                    params.push(&tail[start..position]);
+                }
+                start = Some(position + 1);
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = start {
+        params.push(&tail[start..]);
+    }
+    params.into_iter().filter(|param| !param.is_empty()).collect()
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::{Method, StatusCode}};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::testing;
+
+    /// A calendar holding `lines`, joined with CRLF as RFC 5545 asks.
+    fn calendar(lines: &[&str]) -> String {
+        ["BEGIN:VCALENDAR", "VERSION:2.0"]
+            .iter()
+            .chain(lines)
+            .chain(&["END:VCALENDAR"])
+            .map(|line| format!("{}\r\n", line))
+            .collect()
+    }
+
+    fn todo(lines: &[&str]) -> Result<IcsTodo, String> {
+        let lines = [&["BEGIN:VTODO"][..], lines, &["END:VTODO"]].concat();
+        let mut components = parse_vtodos(&calendar(&lines)).expect("a calendar");
+        assert_eq!(components.len(), 1);
+        components.remove(0).result
+    }
+
+    fn due(property: &str) -> Result<String, String> {
+        todo(&["SUMMARY:Due", property]).map(|todo| todo.due_date.unwrap().to_rfc3339())
+    }
+
+    #[test]
+    fn converts_tzid_values_to_utc() {
+        assert_eq!(due("DUE;TZID=Europe/Madrid:20300601T090000"), Ok("2030-06-01T07:00:00+00:00".to_string()));
+        assert_eq!(due("DUE;TZID=Europe/Madrid:20300115T090000"), Ok("2030-01-15T08:00:00+00:00".to_string()));
+        assert_eq!(due("DUE;TZID=\"America/New_York\":20300601T090000"), Ok("2030-06-01T13:00:00+00:00".to_string()));
+        assert_eq!(due("due;tzid=Asia/Tokyo:20300601T090000"), Ok("2030-06-01T00:00:00+00:00".to_string()));
+        // UTC y hora flotante no dependen de ningún TZID
+        assert_eq!(due("DUE:20300601T090000Z"), Ok("2030-06-01T09:00:00+00:00".to_string()));
+        assert_eq!(due("DUE:20300601T090000"), Ok("2030-06-01T09:00:00+00:00".to_string()));
+        // En el cambio de otoño se toma la primera de las dos horas
+        assert_eq!(due("DUE;TZID=Europe/Madrid:20301027T023000"), Ok("2030-10-27T00:30:00+00:00".to_string()));
+
+        assert_eq!(due("DUE;TZID=Mars/Olympus:20300601T090000"), Err("DUE: unknown TZID \"Mars/Olympus\"".to_string()));
+        assert_eq!(
+            due("DUE;TZID=America/New_York:20300310T023000"),
+            Err("DUE: 20300310T023000 does not exist in America/New_York".to_string())
+        );
+        assert_eq!(due("DUE;TZID=Europe/Madrid:next week"), Err("DUE: invalid date-time \"next week\"".to_string()));
+    }
+
+    #[test]
+    fn resolves_vendor_prefixed_tzids() {
+        assert_eq!(
+            due("DUE;TZID=/mozilla.org/20050126_1/Europe/Madrid:20300601T090000"),
+            Ok("2030-06-01T07:00:00+00:00".to_string())
+        );
+        assert_eq!(
+            due("DUE;TZID=/freeassociation.sourceforge.net/Tzfile/America/Los_Angeles:20300601T090000"),
+            Ok("2030-06-01T16:00:00+00:00".to_string())
+        );
+        assert_eq!(resolve_tzid("/citadel.org/20190914_1/America/Argentina/Buenos_Aires"), Some(chrono_tz::America::Argentina::Buenos_Aires));
+        assert_eq!(resolve_tzid("/example.com/Nowhere/Else"), None);
+    }
+
+    #[test]
+    fn takes_date_only_values_as_midnight_utc() {
+        assert_eq!(due("DUE;VALUE=DATE:20300601"), Ok("2030-06-01T00:00:00+00:00".to_string()));
+        assert_eq!(due("DUE:20300601"), Ok("2030-06-01T00:00:00+00:00".to_string()));
+        // Una fecha no tiene zona: el TZID no la mueve
+        assert_eq!(due("DUE;VALUE=DATE;TZID=Asia/Tokyo:20300601"), Ok("2030-06-01T00:00:00+00:00".to_string()));
+        assert_eq!(due("DUE;VALUE=DATE:20301301"), Err("DUE: invalid date \"20301301\"".to_string()));
+    }
+
+    #[test]
+    fn flags_recurring_todos() {
+        let recurring = todo(&["SUMMARY:Water the plants", "RRULE:FREQ=WEEKLY;BYDAY=MO"]).unwrap();
+        assert!(recurring.recurring);
+        assert_eq!(recurring.title, "Water the plants");
+        assert!(!todo(&["SUMMARY:Once"]).unwrap().recurring);
+    }
+
+    #[test]
+    fn maps_the_todo_fields() {
+        let milk = todo(&[
+            "UID:milk@example.com",
+            "SUMMARY:  Buy milk\\, eggs\\; bread ",
+            "DESCRIPTION:Two liters\\nSemi-skimmed \\\\ fresh",
+            "STATUS:COMPLETED",
+            // Las propiedades de un VALARM no son del VTODO
+            "BEGIN:VALARM",
+            "SUMMARY:Alarm",
+            "DESCRIPTION:Reminder",
+            "END:VALARM",
+        ])
+        .unwrap();
+        assert_eq!(milk.uid.as_deref(), Some("milk@example.com"));
+        assert_eq!(milk.title, "Buy milk, eggs; bread");
+        assert_eq!(milk.description.as_deref(), Some("Two liters\nSemi-skimmed \\ fresh"));
+        assert!(milk.completed);
+        assert!(milk.due_date.is_none());
+
+        assert!(todo(&["SUMMARY:Done", "COMPLETED:20300601T090000Z"]).unwrap().completed);
+        assert!(!todo(&["SUMMARY:Open", "STATUS:NEEDS-ACTION"]).unwrap().completed);
+    }
+
+    #[test]
+    fn reports_malformed_components_one_by_one() {
+        let ics = calendar(&[
+            "BEGIN:VTODO",
+            "UID:first@example.com",
+            "SUMMARY:First",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "UID:no-colon@example.com",
+            "SUMMARY No colon",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "UID:empty@example.com",
+            "SUMMARY:   ",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "UID:bad-param@example.com",
+            "SUMMARY;LANGUAGE:Bad parameter",
+            "END:VTODO",
+            // Sin END: el siguiente BEGIN la cierra
+            "BEGIN:VTODO",
+            "UID:unterminated@example.com",
+            "SUMMARY:Unterminated",
+            "BEGIN:VTODO",
+            "UID:last@example.com",
+            "SUMMARY:Last",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "UID:truncated@example.com",
+            "SUMMARY:Truncated",
+        ]);
+
+        let components = parse_vtodos(&ics).unwrap();
+
+        let outcomes = components
+            .iter()
+            .map(|component| {
+                (
+                    component.index,
+                    component.uid.as_deref().unwrap(),
+                    component.result.as_ref().map(|todo| todo.title.as_str()).map_err(String::as_str),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outcomes,
+            [
+                (1, "first@example.com", Ok("First")),
+                (2, "no-colon@example.com", Err("line 9: malformed property \"SUMMARY No colon\"")),
+                (3, "empty@example.com", Err("SUMMARY is missing or empty")),
+                (4, "bad-param@example.com", Err("line 17: malformed parameter \"LANGUAGE\"")),
+                (5, "unterminated@example.com", Err("missing END:VTODO")),
+                (6, "last@example.com", Ok("Last")),
+                (7, "truncated@example.com", Err("missing END:VTODO")),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_documents_that_are_not_calendars() {
+        assert_eq!(parse_vtodos("").err().unwrap(), "expected BEGIN:VCALENDAR on the first line");
+        assert_eq!(parse_vtodos("BEGIN:VTODO\r\nSUMMARY:x\r\nEND:VTODO\r\n").err().unwrap(), "expected BEGIN:VCALENDAR on the first line");
+        assert!(parse_vtodos(&calendar(&[])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unfolds_folded_lines() {
+        // Plegado con espacio y con tabulador, saltos CRLF y LF y líneas en blanco
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:Renew the\r\n  passport at\n\tthe consulate\r\n\r\nDESCRIPTION;\r\n LANGUAGE=en:Bring two\r\n  photos\r\nDUE;TZID=Europe/\r\n Madrid:20300601T090000\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+
+        let components = parse_vtodos(ics).unwrap();
+
+        let todo = components[0].result.as_ref().unwrap();
+        assert_eq!(todo.title, "Renew the passport atthe consulate");
+        assert_eq!(todo.description.as_deref(), Some("Bring two photos"));
+        assert_eq!(todo.due_date.unwrap().to_rfc3339(), "2030-06-01T07:00:00+00:00");
+        assert_eq!(unfold("A:1\r\n 2\r\n\r\nB:3\n\t\n"), ["A:12", "B:3"]);
+    }
+
+    #[sqlx::test]
+    async fn imports_the_valid_components_and_reports_the_rest(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let ics = calendar(&[
+            "BEGIN:VTODO",
+            "UID:weekly@example.com",
+            "SUMMARY:Water the plants",
+            "RRULE:FREQ=WEEKLY",
+            "DUE;TZID=Europe/Madrid:20300601T090000",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "UID:broken@example.com",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "UID:truncated@example.com",
+            "SUMMARY:Truncated",
+        ]);
+
+        let request = testing::request(Method::POST, "/api/v1/todos/import.ics", Some("text/calendar"), Body::from(ics));
+        let response = testing::send(&state, request).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let report = testing::json(response).await["data"].take();
+        assert_eq!(report["created"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            report["warnings"],
+            json!([{ "index": 1, "uid": "weekly@example.com", "message": "RRULE is not supported, imported as a single todo" }])
+        );
+        assert_eq!(
+            report["errors"],
+            json!([
+                { "index": 2, "uid": "broken@example.com", "message": "SUMMARY is missing or empty" },
+                { "index": 3, "uid": "truncated@example.com", "message": "missing END:VTODO" }
+            ])
+        );
+        let (title, due_date): (String, chrono::DateTime<Utc>) = sqlx::query_as("SELECT title, due_date FROM todos")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!((title.as_str(), due_date.to_rfc3339().as_str()), ("Water the plants", "2030-06-01T07:00:00+00:00"));
+    }
+}
@@ -0,0 +1,416 @@
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+use std::sync::Arc;
+use tracing::info;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+use crate::{
+    model::AppState,
+    response::ApiResponse,
+    error::AppError,
+    config::Config,
+    handler::CreateTodo,
+    service::validate_due_date,
+    ical
+};
+
+#[derive(Serialize, Deserialize, ToSchema, Default)]
+#[schema(example = json!({
+    "created": ["550e8400-e29b-41d4-a716-446655440000"],
+    "skipped": 1,
+    "errors": [{ "index": 3, "uid": "broken@example.com", "message": "SUMMARY is missing or empty" }],
+    "warnings": [{ "index": 2, "uid": "weekly@example.com", "message": "RRULE is not supported, imported as a single todo" }],
+    "lists": [{ "title": "My Tasks", "created": 1, "skipped": 1, "errors": 1 }]
+}))]
+pub struct ImportReport {
+    /// Ids of the todos created by this import
+    pub created: Vec<Uuid>,
+    /// Entries already imported earlier (same UID or task id)
+    pub skipped: usize,
+    /// Entries that could not be imported
+    pub errors: Vec<ImportIssue>,
+    /// Entries imported with caveats
+    pub warnings: Vec<ImportIssue>,
+    /// Outcome per task list, in file order (Google Tasks only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lists: Vec<ImportListSummary>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ImportListSummary {
+    #[schema(example = "My Tasks")]
+    pub title: String,
+    /// Todos created from this list
+    pub created: usize,
+    /// Tasks already imported earlier
+    pub skipped: usize,
+    /// Tasks that could not be imported
+    pub errors: usize,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ImportIssue {
+    /// Position of the entry in the uploaded file (starts from 1)
+    #[schema(example = 3)]
+    pub index: usize,
+    #[schema(example = "broken@example.com")]
+    pub uid: Option<String>,
+    #[schema(example = "SUMMARY is missing or empty")]
+    pub message: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/import.ics",
+    request_body(content = String, description = "iCalendar file with VTODO components", content_type = "text/calendar"),
+    responses(
+        (status = 200, description = "Import finished, per-entry problems are listed in the report", body = ApiResponseImportReport),
+        (status = 400, description = "The file is not an iCalendar document", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn import_ics(
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> Result<impl IntoResponse, AppError> {
+    let components = ical::parse_vtodos(&body)
+        .map_err(|e| AppError::ValidationError(format!("Invalid iCalendar file: {}", e)))?;
+
+    let now = state.clock.now();
+    let mut report = ImportReport::default();
+    let mut tx = state.db.begin().await?;
+
+    for component in components {
+        let issue = |message: String| ImportIssue {
+            index: component.index,
+            uid: component.uid.clone(),
+            message,
+        };
+
+        let todo = match component.result {
+            Ok(todo) => todo,
+            Err(message) => {
+                report.errors.push(issue(message));
+                continue;
+            }
+        };
+        if let Err(message) = validate_entry(&state.config, now, &todo.title, todo.description.as_deref(), todo.due_date) {
+            report.errors.push(issue(message));
+            continue;
+        }
+
+        let created = insert_todo(
+            &mut tx,
+            &todo.title,
+            todo.description.as_deref(),
+            todo.completed,
+            todo.due_date,
+            todo.uid.as_ref().map(|uid| format!("ics:{}", uid)),
+        )
+        .await?;
+
+        match created {
+            Some(id) => {
+                report.created.push(id);
+                if todo.recurring {
+                    report.warnings.push(issue(
+                        "RRULE is not supported, imported as a single todo".to_string(),
+                    ));
+                }
+            }
+            None => report.skipped += 1,
+        }
+    }
+
+    tx.commit().await?;
+
+    info!(
+        "ICS import finished: {} created, {} skipped, {} errors",
+        report.created.len(),
+        report.skipped,
+        report.errors.len()
+    );
+    Ok((StatusCode::OK, Json(ApiResponse::success(report))))
+}
+
+/// Subset of the `Tasks.json` file produced by Google Takeout.
+#[derive(Deserialize)]
+struct GoogleTaskLists {
+    items: Vec<GoogleTaskList>,
+}
+
+#[derive(Deserialize)]
+struct GoogleTaskList {
+    title: String,
+    #[serde(default)]
+    items: Vec<GoogleTask>,
+}
+
+#[derive(Deserialize)]
+struct GoogleTask {
+    id: String,
+    #[serde(default)]
+    title: String,
+    notes: Option<String>,
+    status: Option<String>,
+    #[serde(default, deserialize_with = "crate::timestamp::option::deserialize")]
+    due: Option<DateTime<Utc>>,
+    parent: Option<String>,
+    #[serde(default)]
+    deleted: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/import/google-tasks",
+    request_body(content = String, description = "Tasks.json file from a Google Takeout export", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Import finished, per-task problems are listed in the report", body = ApiResponseImportReport),
+        (status = 400, description = "The file is not a valid Google Tasks export", body = ApiResponseString),
+        (status = 415, description = "Content-Type is not application/json", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn import_google_tasks(
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> Result<impl IntoResponse, AppError> {
+    let export: GoogleTaskLists = serde_json::from_str(&body)
+        .map_err(|e| AppError::ValidationError(format!("Invalid Google Tasks export: {}", e)))?;
+
+    let now = state.clock.now();
+    let mut report = ImportReport::default();
+    let mut tx = state.db.begin().await?;
+
+    let mut index = 0;
+    for list in &export.items {
+        let (created, skipped, errors) = (report.created.len(), report.skipped, report.errors.len());
+        for task in &list.items {
+            // Los borrados también cuentan: index es la posición en el fichero
+            index += 1;
+            if task.deleted {
+                continue;
+            }
+            let issue = |message: String| ImportIssue {
+                index,
+                uid: Some(task.id.clone()),
+                message,
+            };
+
+            let title = task.title.trim();
+            let notes = task.notes.as_deref().map(str::trim).filter(|notes| !notes.is_empty());
+            if let Err(message) = validate_entry(&state.config, now, title, notes, task.due) {
+                report.errors.push(issue(message));
+                continue;
+            }
+
+            let created = insert_todo(
+                &mut tx,
+                title,
+                notes,
+                task.status.as_deref() == Some("completed"),
+                task.due,
+                Some(format!("google-tasks:{}", task.id)),
+            )
+            .await?;
+
+            match created {
+                Some(id) => {
+                    report.created.push(id);
+                    if task.parent.is_some() {
+                        report.warnings.push(issue(format!(
+                            "Subtasks are not supported, imported as a top-level todo (list \"{}\")",
+                            list.title
+                        )));
+                    }
+                }
+                None => report.skipped += 1,
+            }
+        }
+        report.lists.push(ImportListSummary {
+            title: list.title.clone(),
+            created: report.created.len() - created,
+            skipped: report.skipped - skipped,
+            errors: report.errors.len() - errors,
+        });
+    }
+
+    tx.commit().await?;
+
+    info!(
+        "Google Tasks import finished: {} lists, {} created, {} skipped, {} errors",
+        export.items.len(),
+        report.created.len(),
+        report.skipped,
+        report.errors.len()
+    );
+    Ok((StatusCode::OK, Json(ApiResponse::success(report))))
+}
+
+/// Checks an imported entry against the rules for a todo created through the
+/// API, returning the message for the report.
+fn validate_entry(
+    config: &Config,
+    now: DateTime<Utc>,
+    title: &str,
+    description: Option<&str>,
+    due_date: Option<DateTime<Utc>>,
+) -> Result<(), String> {
+    let todo = CreateTodo {
+        title: title.to_string(),
+        description: description.map(str::to_string),
+        completed: None,
+        due_date: None,
+        tz: None,
+    };
+    todo.validate().map_err(|errors| errors.to_string())?;
+    validate_due_date(config, now, due_date).map_err(|errors| errors.to_string())
+}
+
+/// Inserts an imported todo unless one with the same `external_ref` exists,
+/// live or archived, returning the new id.
+async fn insert_todo(
+    tx: &mut Transaction<'_, Postgres>,
+    title: &str,
+    description: Option<&str>,
+    completed: bool,
+    due_date: Option<DateTime<Utc>>,
+    external_ref: Option<String>,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO todos (title, description, completed, due_date, external_ref)
+        SELECT $1, $2, $3, $4, $5
+        -- Reimportar no debe resucitar lo que ya se archivó
+        WHERE NOT EXISTS (SELECT 1 FROM todos_archive WHERE external_ref = $5)
+        ON CONFLICT (external_ref) DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(title)
+    .bind(description)
+    .bind(completed)
+    .bind(due_date)
+    .bind(external_ref)
+    .fetch_optional(&mut **tx)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{Method, StatusCode}};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::testing;
+
+    #[sqlx::test]
+    async fn rejects_ics_descriptions_over_the_api_limit(db: PgPool) {
+        let state = testing::state(db, testing::config(&[]));
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:long@example.com\r\nSUMMARY:Long\r\nDESCRIPTION:{}\r\nEND:VTODO\r\nBEGIN:VTODO\r\nUID:short@example.com\r\nSUMMARY:Short\r\nDESCRIPTION:{}\r\nEND:VTODO\r\nEND:VCALENDAR\r\n",
+            "x".repeat(10001),
+            "x".repeat(10000)
+        );
+
+        let request = testing::request(Method::POST, "/api/v1/todos/import.ics", Some("text/calendar"), Body::from(ics));
+        let response = testing::send(&state, request).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let report = &testing::json(response).await["data"];
+        assert_eq!(report["created"].as_array().unwrap().len(), 1);
+        assert_eq!(report["errors"][0]["uid"], "long@example.com");
+        let message = report["errors"][0]["message"].as_str().unwrap();
+        assert!(message.contains("Description must be at most 10000 characters"), "{}", message);
+    }
+
+    #[sqlx::test]
+    async fn rejects_google_task_notes_over_the_api_limit(db: PgPool) {
+        let state = testing::state(db, testing::config(&[]));
+        let export = json!({
+            "items": [{
+                "title": "Inbox",
+                "items": [
+                    { "id": "long", "title": "Long", "notes": "x".repeat(10001) },
+                    { "id": "title", "title": "t".repeat(256) },
+                    { "id": "ok", "title": "Ok", "notes": "x".repeat(10000) }
+                ]
+            }]
+        });
+
+        let request = testing::json_request(Method::POST, "/api/v1/todos/import/google-tasks", &export);
+        let report = &testing::json(testing::send(&state, request).await).await["data"];
+
+        assert_eq!(report["created"].as_array().unwrap().len(), 1);
+        assert_eq!(report["lists"], json!([{ "title": "Inbox", "created": 1, "skipped": 0, "errors": 2 }]));
+        let messages = report["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| (error["uid"].as_str().unwrap(), error["message"].as_str().unwrap()))
+            .collect::<Vec<_>>();
+        assert!(messages[0].0 == "long" && messages[0].1.contains("Description must be at most 10000 characters"), "{:?}", messages);
+        assert!(messages[1].0 == "title" && messages[1].1.contains("Title must be between 1 and 255 characters"), "{:?}", messages);
+    }
+
+    #[sqlx::test]
+    async fn rejects_malformed_or_truncated_takeout_files_without_importing(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let valid = r#"{"kind":"tasks#taskLists","items":[{"title":"Inbox","items":[{"id":"a","title":"First"},{"id":"b","title":"Second"}]}]}"#;
+        // Cortado a mitad de la segunda tarea: la primera ya se había leído entera
+        let truncated = &valid[..valid.find(r#"{"id":"b""#).unwrap() + 12];
+
+        for body in [truncated, "", "not json", r#"{"items":[{"items":[{"id":"a","title":"No list title"}]}]}"#, r#"{"items":{}}"#] {
+            let request = testing::request(
+                Method::POST,
+                "/api/v1/todos/import/google-tasks",
+                Some("application/json"),
+                Body::from(body.to_string()),
+            );
+            let response = testing::send(&state, request).await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", body);
+            let envelope = testing::json(response).await;
+            assert_eq!((&envelope["status"], &envelope["data"]), (&json!("error"), &json!(null)), "{}", body);
+            let error = envelope["error"].as_str().unwrap();
+            assert!(error.starts_with("Invalid Google Tasks export: "), "{}", error);
+        }
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM todos").fetch_one(&db).await.unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[sqlx::test]
+    async fn skips_entries_already_in_the_archive(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        sqlx::query(
+            "INSERT INTO todos_archive (id, title, completed, external_ref, created_at, updated_at, archived_at)
+             VALUES (gen_random_uuid(), 'Archived', true, $1, NOW(), NOW(), NOW())",
+        )
+        .bind("ics:archived@example.com")
+        .execute(&db)
+        .await
+        .unwrap();
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+            BEGIN:VTODO\r\nUID:archived@example.com\r\nSUMMARY:Archived\r\nEND:VTODO\r\n\
+            BEGIN:VTODO\r\nUID:new@example.com\r\nSUMMARY:New\r\nEND:VTODO\r\n\
+            END:VCALENDAR\r\n";
+
+        for created in [1, 0] {
+            let request = testing::request(Method::POST, "/api/v1/todos/import.ics", Some("text/calendar"), Body::from(ics));
+            let report = &testing::json(testing::send(&state, request).await).await["data"];
+            assert_eq!(report["created"].as_array().unwrap().len(), created);
+            assert_eq!(report["skipped"], 2 - created);
+        }
+        let titles: Vec<String> = sqlx::query_scalar("SELECT title FROM todos").fetch_all(&db).await.unwrap();
+        assert_eq!(titles, ["New"]);
+    }
+}
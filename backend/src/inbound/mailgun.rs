@@ -0,0 +1,244 @@
+//! Mailgun inbound routes, forwarding to `POST /api/v1/inbound/email`.
+
+use axum::{
+    extract::{Request, State},
+    response::Response,
+};
+use chrono::DateTime;
+use std::sync::Arc;
+use crate::{model::AppState, error::AppError, signature};
+use super::{accept, invalid_signature, read_fields, within_window, Delivery, Email};
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/inbound/email",
+    request_body(content = String, description = "Mailgun inbound route payload (multipart/form-data or application/x-www-form-urlencoded) with `timestamp`, `token`, `signature`, `subject`, `body-plain` and `Message-Id` fields", content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "Todo created from the email", body = ApiResponseTodo),
+        (status = 200, description = "Duplicate delivery ignored: the token or the Message-Id was seen before", body = ApiResponseString),
+        (status = 400, description = "Malformed payload", body = ApiResponseString),
+        (status = 401, description = "Signature could not be verified, or its timestamp is more than 5 minutes off", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "inbound"
+)]
+pub async fn inbound_email(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+) -> Result<Response, AppError> {
+    let fields = read_fields(request).await?;
+
+    // Verificar la firma antes de mirar el contenido
+    let (Some(key), Some(timestamp), Some(token), Some(signature)) = (
+        state.config.inbound_email_signing_key.as_deref(),
+        fields.get("timestamp"),
+        fields.get("token"),
+        fields.get("signature"),
+    ) else {
+        return Err(invalid_signature());
+    };
+    // Mailgun firma cada webhook con HMAC-SHA256(clave, timestamp + token)
+    if !signature::verify_hex(key.as_bytes(), &[timestamp.as_bytes(), token.as_bytes()], signature) {
+        return Err(invalid_signature());
+    }
+    let signed_at = timestamp
+        .parse::<i64>()
+        .ok()
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0));
+    let signed_at = within_window(state.clock.now(), signed_at, timestamp)?;
+
+    let email = Email {
+        subject: fields.get("subject").cloned(),
+        body: fields.get("body-plain").cloned(),
+        message_id: fields.get("Message-Id").or_else(|| fields.get("message-id")).cloned(),
+    };
+    accept(&state, Delivery { token: token.clone(), signed_at, email }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{Method, StatusCode}};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use sqlx::PgPool;
+    use crate::{clock::MockClock, signature, testing};
+
+    const KEY: &str = "test-signing-key";
+
+    /// When the tests receive their emails.
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    fn sign(timestamp: &str, token: &str) -> String {
+        hex::encode(signature::sign(KEY.as_bytes(), &[timestamp.as_bytes(), token.as_bytes()]))
+    }
+
+    fn email(timestamp: i64, token: &str, signature: &str, message_id: &str) -> axum::extract::Request {
+        let body = format!(
+            "timestamp={}&token={}&signature={}&subject=Buy+milk&body-plain=Two+liters&Message-Id={}",
+            timestamp, token, signature, message_id
+        );
+        testing::request(
+            Method::POST,
+            "/api/v1/inbound/email",
+            Some("application/x-www-form-urlencoded"),
+            Body::from(body),
+        )
+    }
+
+    fn signed(timestamp: i64, token: &str, message_id: &str) -> axum::extract::Request {
+        email(timestamp, token, &sign(&timestamp.to_string(), token), message_id)
+    }
+
+    fn state(db: PgPool) -> std::sync::Arc<crate::model::AppState> {
+        let config = testing::config(&[("INBOUND_EMAIL_SIGNING_KEY", KEY)]);
+        testing::state_at(db, config, &MockClock::new(now()))
+    }
+
+    async fn todo_count(db: &PgPool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM todos").fetch_one(db).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn creates_a_todo_from_a_signed_email(db: PgPool) {
+        let state = state(db.clone());
+
+        let response = testing::send(&state, signed(now().timestamp(), "token-1", "<1@mail>")).await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = testing::json(response).await;
+        assert_eq!(body["data"]["title"], "Buy milk");
+        assert_eq!(body["data"]["description"], "Two liters");
+        assert_eq!(todo_count(&db).await, 1);
+    }
+
+    #[sqlx::test]
+    async fn rejects_a_bad_signature(db: PgPool) {
+        let state = state(db.clone());
+        let now = now().timestamp();
+
+        // Firmado con otro token, y una firma que ni siquiera es hex
+        let other = email(now, "token-1", &sign(&now.to_string(), "token-2"), "<1@mail>");
+        let garbage = email(now, "token-1", "not-hex", "<1@mail>");
+        for request in [other, garbage] {
+            assert_eq!(testing::send(&state, request).await.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert_eq!(todo_count(&db).await, 0);
+    }
+
+    #[sqlx::test]
+    async fn accepts_timestamps_up_to_five_minutes_off_either_way(db: PgPool) {
+        let state = state(db.clone());
+        let now = now().timestamp();
+
+        for (timestamp, token) in [(now - 301, "old"), (now + 301, "future"), (i64::MIN, "min"), (i64::MAX, "max")] {
+            let response = testing::send(&state, signed(timestamp, token, token)).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "timestamp {}", timestamp);
+        }
+        assert_eq!(todo_count(&db).await, 0);
+
+        for (timestamp, token) in [(now - 300, "oldest"), (now + 300, "newest")] {
+            let response = testing::send(&state, signed(timestamp, token, token)).await;
+            assert_eq!(response.status(), StatusCode::CREATED, "timestamp {}", timestamp);
+        }
+    }
+
+    #[sqlx::test]
+    async fn ignores_a_replayed_token_even_with_another_message_id(db: PgPool) {
+        let state = state(db.clone());
+        let now = now().timestamp();
+
+        let first = testing::send(&state, signed(now, "token-1", "<1@mail>")).await;
+        assert_eq!(first.status(), StatusCode::CREATED);
+
+        let replay = testing::send(&state, signed(now, "token-1", "<2@mail>")).await;
+        // Mailgun reintenta cualquier 4xx salvo el 406: un 409 haría que lo reintentara sin fin
+        assert_eq!(replay.status(), StatusCode::OK);
+        assert_eq!(testing::json(replay).await["data"], "Duplicate delivery ignored");
+        assert_eq!(todo_count(&db).await, 1);
+    }
+
+    #[sqlx::test]
+    async fn ignores_a_duplicate_message_id_with_a_new_token(db: PgPool) {
+        let state = state(db.clone());
+        let now = now().timestamp();
+
+        testing::send(&state, signed(now, "token-1", "<1@mail>")).await;
+        let retry = testing::send(&state, signed(now, "token-2", "<1@mail>")).await;
+
+        assert_eq!(retry.status(), StatusCode::OK);
+        assert_eq!(todo_count(&db).await, 1);
+    }
+
+    #[sqlx::test]
+    async fn purges_tokens_outside_the_window(db: PgPool) {
+        let state = state(db.clone());
+        for (token, age) in [("expired", 301), ("oldest", 300)] {
+            sqlx::query("INSERT INTO inbound_email_tokens (token, signed_at) VALUES ($1, $2)")
+                .bind(token)
+                .bind(now() - Duration::seconds(age))
+                .execute(&db)
+                .await
+                .unwrap();
+        }
+
+        testing::send(&state, signed(now().timestamp(), "token-1", "<1@mail>")).await;
+
+        let tokens: Vec<String> = sqlx::query_scalar("SELECT token FROM inbound_email_tokens ORDER BY signed_at")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(tokens, ["oldest", "token-1"]);
+    }
+
+    #[sqlx::test]
+    async fn reads_multipart_deliveries_skipping_attachments(db: PgPool) {
+        let state = state(db.clone());
+        let timestamp = now().timestamp().to_string();
+        let mut body = String::new();
+        for (name, value) in [
+            ("timestamp", timestamp.as_str()),
+            ("token", "token-1"),
+            ("signature", &sign(&timestamp, "token-1")),
+            ("subject", &"s".repeat(300)),
+            ("body-plain", "  \r\n  "),
+        ] {
+            body.push_str(&format!("--b\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", name, value));
+        }
+        body.push_str("--b\r\nContent-Disposition: form-data; name=\"attachment-1\"; filename=\"photo.jpg\"\r\n");
+        body.push_str("Content-Type: image/jpeg\r\n\r\n\u{ff}\u{d8}binary\r\n--b--\r\n");
+
+        let request = testing::request(Method::POST, "/api/v1/inbound/email", Some("multipart/form-data; boundary=b"), Body::from(body));
+        let response = testing::send(&state, request).await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let todo = &testing::json(response).await["data"];
+        assert_eq!(todo["title"], "s".repeat(255));
+        assert_eq!(todo["description"], serde_json::Value::Null);
+    }
+
+    #[sqlx::test]
+    async fn titles_emails_without_a_subject(db: PgPool) {
+        let state = state(db.clone());
+        let timestamp = now().timestamp();
+        let body = format!("timestamp={}&token=token-1&signature={}&subject=+&body-plain=Call+mom", timestamp, sign(&timestamp.to_string(), "token-1"));
+        let request = testing::request(Method::POST, "/api/v1/inbound/email", Some("application/x-www-form-urlencoded"), Body::from(body));
+
+        let response = testing::send(&state, request).await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let todo = &testing::json(response).await["data"];
+        assert_eq!((&todo["title"], &todo["description"]), (&serde_json::json!("(no subject)"), &serde_json::json!("Call mom")));
+    }
+
+    #[sqlx::test]
+    async fn rejects_every_delivery_without_a_signing_key(db: PgPool) {
+        let state = testing::state_at(db.clone(), testing::config(&[]), &MockClock::new(now()));
+
+        let response = testing::send(&state, signed(now().timestamp(), "token-1", "<1@mail>")).await;
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(testing::json(response).await["error"], "Invalid signature");
+        assert_eq!(todo_count(&db).await, 0);
+    }
+}
@@ -0,0 +1,276 @@
+//! Inbound email webhooks. Each provider has its own endpoint, which checks
+//! the provider's signature and turns its payload into an [`Email`]; from
+//! there every delivery is handled alike: a signature already seen or a
+//! Message-ID already imported is answered with 200 and ignored, anything
+//! else becomes a todo.
+
+pub mod mailgun;
+pub mod sendgrid;
+pub mod ses;
+
+use axum::{
+    extract::{FromRequest, Multipart, Request, Form},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use mail_parser::MessageParser;
+use std::collections::HashMap;
+use tracing::{info, warn};
+use validator::Validate;
+use crate::{
+    api::CreateTodo,
+    model::AppState,
+    repository::TodoFields,
+    response::ApiResponse,
+    error::AppError,
+};
+
+const MAX_TITLE_CHARS: usize = 255;
+const MAX_DESCRIPTION_CHARS: usize = 10000;
+/// How far the signed timestamp may be from the server clock, either way.
+/// Older deliveries are refused as possible replays of a captured request.
+const MAX_TIMESTAMP_SKEW_SECS: i64 = 5 * 60;
+
+/// The parts of an email a todo is made of.
+#[derive(Default)]
+struct Email {
+    subject: Option<String>,
+    body: Option<String>,
+    message_id: Option<String>,
+}
+
+/// A webhook whose signature checked out.
+struct Delivery {
+    /// Unique per signed request, e.g. Mailgun's token; prefixed by the
+    /// provider unless it is Mailgun, whose tokens were stored bare first
+    token: String,
+    signed_at: DateTime<Utc>,
+    email: Email,
+}
+
+fn invalid_signature() -> AppError {
+    warn!("Rejected inbound email with unverifiable signature");
+    AppError::Unauthorized("Invalid signature".to_string())
+}
+
+/// `signed_at` if it is within [`MAX_TIMESTAMP_SKEW_SECS`] of `now`; `raw`
+/// is the timestamp as received, for the log.
+fn within_window(now: DateTime<Utc>, signed_at: Option<DateTime<Utc>>, raw: &str) -> Result<DateTime<Utc>, AppError> {
+    match signed_at {
+        Some(signed_at) if (now - signed_at).abs() <= Duration::seconds(MAX_TIMESTAMP_SKEW_SECS) => Ok(signed_at),
+        _ => {
+            warn!("Rejected inbound email signed at {}, outside the accepted window", raw);
+            Err(AppError::Unauthorized("Signature timestamp is too old or in the future".to_string()))
+        }
+    }
+}
+
+fn duplicate() -> Response {
+    (StatusCode::OK, Json(ApiResponse::<String>::success("Duplicate delivery ignored".to_string()))).into_response()
+}
+
+/// Creates the todo for `delivery` unless its token or its Message-ID was
+/// seen before.
+async fn accept(state: &AppState, delivery: Delivery) -> Result<Response, AppError> {
+    let Delivery { token, signed_at, email } = delivery;
+    let now = state.clock.now();
+    let title = match email.subject.as_deref().map(str::trim) {
+        Some(subject) if !subject.is_empty() => truncate(subject, MAX_TITLE_CHARS),
+        _ => "(no subject)".to_string(),
+    };
+    let description = email
+        .body
+        .as_deref()
+        .map(str::trim)
+        .filter(|body| !body.is_empty())
+        .map(|body| truncate(body, MAX_DESCRIPTION_CHARS));
+    let message_id = email.message_id.as_deref().and_then(normalize_message_id);
+
+    let todo = CreateTodo { title, description, completed: None, due_date: None, tz: None };
+    todo.validate()?;
+
+    let mut tx = state.db.begin().await?;
+
+    // El token y el Message-ID se guardan en una transacción que sigue abierta
+    // mientras se crea el todo: una entrega repetida espera a que termine y se
+    // ignora, y si la creación falla no queda nada y el reintento del
+    // proveedor no se toma por una repetición
+    sqlx::query("DELETE FROM inbound_email_tokens WHERE signed_at < $1")
+        .bind(now - Duration::seconds(MAX_TIMESTAMP_SKEW_SECS))
+        .execute(&mut *tx)
+        .await?;
+    let fresh = sqlx::query(
+        r#"
+        INSERT INTO inbound_email_tokens (token, signed_at)
+        VALUES ($1, $2)
+        ON CONFLICT (token) DO NOTHING
+        "#
+    )
+    .bind(&token)
+    .bind(signed_at)
+    .execute(&mut *tx)
+    .await?;
+    if fresh.rows_affected() == 0 {
+        // 200 y no 409: los proveedores reintentan los 4xx, y un token ya visto
+        // es una entrega que ya se procesó
+        info!("Ignoring inbound email with an already used token");
+        return Ok(duplicate());
+    }
+
+    if let Some(message_id) = &message_id {
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO inbound_emails (message_id)
+            VALUES ($1)
+            ON CONFLICT (message_id) DO NOTHING
+            "#
+        )
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            tx.commit().await?;
+            info!("Ignoring duplicate inbound email with Message-Id: {}", message_id);
+            return Ok(duplicate());
+        }
+    }
+
+    let fields = TodoFields { title: todo.title, description: todo.description, completed: false, due_date: None };
+    let todo = state.todos.create(now, fields).await?;
+
+    if let Some(message_id) = &message_id {
+        sqlx::query(
+            r#"
+            UPDATE inbound_emails
+            SET todo_id = $1
+            WHERE message_id = $2
+            "#
+        )
+        .bind(todo.id)
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    info!("Todo created from inbound email with id: {}", todo.id);
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(todo))).into_response())
+}
+
+/// Collects the text fields of a multipart or urlencoded body. File parts
+/// (`attachment-N`) are skipped without being buffered.
+async fn read_fields(request: Request) -> Result<HashMap<String, String>, AppError> {
+    let is_multipart = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+
+    if !is_multipart {
+        let Form(fields) = Form::<HashMap<String, String>>::from_request(request, &())
+            .await
+            .map_err(|e| AppError::ValidationError(format!("Invalid form body: {}", e.body_text())))?;
+        return Ok(fields);
+    }
+
+    let mut multipart = Multipart::from_request(request, &())
+        .await
+        .map_err(|e| AppError::ValidationError(format!("Invalid multipart body: {}", e.body_text())))?;
+
+    let mut fields = HashMap::new();
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::ValidationError(format!("Invalid multipart body: {}", e.body_text())))?
+    {
+        // Los adjuntos se ignoran por ahora
+        if field.file_name().is_some() {
+            continue;
+        }
+        let Some(name) = field.name().map(str::to_string) else {
+            continue;
+        };
+        let value = field
+            .text()
+            .await
+            .map_err(|e| AppError::ValidationError(format!("Invalid multipart field {}: {}", name, e.body_text())))?;
+        fields.insert(name, value);
+    }
+
+    Ok(fields)
+}
+
+/// The subject, plain-text body (converted from HTML if that is all there
+/// is) and Message-ID of a raw RFC 5322 message. A header block on its own
+/// parses too, with no body.
+fn parse_mime(raw: &[u8]) -> Email {
+    let Some(message) = MessageParser::default().parse(raw) else {
+        return Email::default();
+    };
+    Email {
+        subject: message.subject().map(str::to_string),
+        body: message.body_text(0).map(|body| body.into_owned()),
+        message_id: message.message_id().map(str::to_string),
+    }
+}
+
+/// `<id>` whether or not the provider gave the angle brackets, so the same
+/// email is recognised whichever provider delivers it.
+fn normalize_message_id(id: &str) -> Option<String> {
+    let id = id.trim();
+    let id = id.strip_prefix('<').and_then(|id| id.strip_suffix('>')).unwrap_or(id).trim();
+    (!id.is_empty()).then(|| format!("<{}>", id))
+}
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_message_ids() {
+        assert_eq!(normalize_message_id("<1@mail>").as_deref(), Some("<1@mail>"));
+        assert_eq!(normalize_message_id(" 1@mail ").as_deref(), Some("<1@mail>"));
+        assert_eq!(normalize_message_id("< 1@mail >").as_deref(), Some("<1@mail>"));
+        assert_eq!(normalize_message_id("<>"), None);
+        assert_eq!(normalize_message_id("  "), None);
+    }
+
+    #[test]
+    fn parses_raw_messages() {
+        let raw = "From: Ana <ana@example.com>\r\n\
+                   Subject: =?UTF-8?Q?Comprar_caf=C3=A9?=\r\n\
+                   Message-ID: <abc@example.com>\r\n\
+                   Content-Type: multipart/alternative; boundary=\"b\"\r\n\
+                   \r\n\
+                   --b\r\n\
+                   Content-Type: text/plain; charset=utf-8\r\n\
+                   \r\n\
+                   Two bags\r\n\
+                   --b\r\n\
+                   Content-Type: text/html; charset=utf-8\r\n\
+                   \r\n\
+                   <p>Two <b>bags</b></p>\r\n\
+                   --b--\r\n";
+
+        let email = parse_mime(raw.as_bytes());
+
+        assert_eq!(email.subject.as_deref(), Some("Comprar café"));
+        assert_eq!(email.body.as_deref().map(str::trim), Some("Two bags"));
+        assert_eq!(email.message_id.as_deref(), Some("abc@example.com"));
+
+        let html_only = parse_mime(b"Subject: Hi\r\nContent-Type: text/html\r\n\r\n<p>Call <i>mom</i></p>\r\n");
+        assert_eq!(html_only.body.as_deref().map(str::trim), Some("Call mom"));
+
+        let headers = parse_mime(b"Subject: Only headers\r\nMessage-Id: <h@example.com>\r\n");
+        assert_eq!((headers.subject.as_deref(), headers.message_id.as_deref()), (Some("Only headers"), Some("h@example.com")));
+        assert!(parse_mime(b"").subject.is_none());
+    }
+}
@@ -0,0 +1,270 @@
+//! SendGrid Inbound Parse, posting to `POST /api/v1/inbound/email/sendgrid`
+//! with signature verification enabled: each request is signed with ECDSA
+//! P-256 over the timestamp followed by the raw body, and the public key is
+//! the one SendGrid shows for the parse settings.
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRequest, Request, State},
+    http::{header::CONTENT_TYPE, HeaderMap},
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::DateTime;
+use p256::{
+    ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    pkcs8::DecodePublicKey,
+};
+use std::sync::Arc;
+use crate::{model::AppState, error::AppError};
+use super::{accept, invalid_signature, parse_mime, read_fields, within_window, Delivery, Email};
+
+const SIGNATURE_HEADER: &str = "x-twilio-email-event-webhook-signature";
+const TIMESTAMP_HEADER: &str = "x-twilio-email-event-webhook-timestamp";
+
+/// Decodes the base64 DER public key SendGrid gives for verification.
+pub fn public_key(key: &str) -> Result<VerifyingKey, String> {
+    let der = STANDARD
+        .decode(key.split_whitespace().collect::<String>())
+        .map_err(|_| "INBOUND_EMAIL_SENDGRID_PUBLIC_KEY must be the base64 public key from SendGrid")?;
+    VerifyingKey::from_public_key_der(&der)
+        .map_err(|_| "INBOUND_EMAIL_SENDGRID_PUBLIC_KEY is not an ECDSA P-256 public key".to_string())
+}
+
+/// Whether `signature` (base64 DER) signs `timestamp` followed by `payload`.
+fn verify(key: &VerifyingKey, timestamp: &str, payload: &[u8], signature: &str) -> bool {
+    let Some(signature) = STANDARD.decode(signature.trim()).ok().and_then(|der| Signature::from_der(&der).ok()) else {
+        return false;
+    };
+    key.verify(&[timestamp.as_bytes(), payload].concat(), &signature).is_ok()
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/inbound/email/sendgrid",
+    request_body(content = String, description = "SendGrid Inbound Parse payload (multipart/form-data) with `subject`, `text` and `headers` fields, or `email` when raw mode is on. Signed through the `X-Twilio-Email-Event-Webhook-Signature` and `X-Twilio-Email-Event-Webhook-Timestamp` headers", content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "Todo created from the email", body = ApiResponseTodo),
+        (status = 200, description = "Duplicate delivery ignored: the signature or the Message-ID was seen before", body = ApiResponseString),
+        (status = 400, description = "Malformed payload", body = ApiResponseString),
+        (status = 401, description = "Signature could not be verified, or its timestamp is more than 5 minutes off", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "inbound"
+)]
+pub async fn inbound_email_sendgrid(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+) -> Result<Response, AppError> {
+    let headers = request.headers().clone();
+    // La firma cubre el cuerpo tal cual llegó, así que se lee entero antes de separar los campos
+    let payload = Bytes::from_request(request, &())
+        .await
+        .map_err(|e| AppError::ValidationError(format!("Invalid body: {}", e.body_text())))?;
+
+    let (Some(key), Some(timestamp), Some(signature)) = (
+        state.config.inbound_email_sendgrid_public_key.as_deref(),
+        header(&headers, TIMESTAMP_HEADER),
+        header(&headers, SIGNATURE_HEADER),
+    ) else {
+        return Err(invalid_signature());
+    };
+    let key = public_key(key).map_err(AppError::InternalError)?;
+    if !verify(&key, &timestamp, &payload, &signature) {
+        return Err(invalid_signature());
+    }
+    let signed_at = timestamp
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0));
+    let signed_at = within_window(state.clock.now(), signed_at, &timestamp)?;
+
+    let mut request = Request::new(Body::from(payload));
+    if let Some(content_type) = headers.get(CONTENT_TYPE) {
+        request.headers_mut().insert(CONTENT_TYPE, content_type.clone());
+    }
+    let fields = read_fields(request).await?;
+
+    let email = match fields.get("email") {
+        // Con "POST the raw, full MIME message" llega el mensaje sin separar
+        Some(raw) => parse_mime(raw.as_bytes()),
+        None => Email {
+            subject: fields.get("subject").cloned(),
+            body: fields.get("text").cloned(),
+            message_id: fields.get("headers").and_then(|headers| parse_mime(headers.as_bytes()).message_id),
+        },
+    };
+    let token = format!("sendgrid:{}", signature.trim());
+    accept(&state, Delivery { token, signed_at, email }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{Method, StatusCode};
+    use chrono::{DateTime, TimeZone, Utc};
+    use p256::{ecdsa::SigningKey, pkcs8::EncodePublicKey};
+    use sqlx::PgPool;
+    use crate::{clock::MockClock, testing};
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_slice(&[7; 32]).unwrap()
+    }
+
+    fn public_key_base64() -> String {
+        STANDARD.encode(signing_key().verifying_key().to_public_key_der().unwrap().as_bytes())
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    fn state(db: PgPool) -> Arc<AppState> {
+        let config = testing::config(&[("INBOUND_EMAIL_SENDGRID_PUBLIC_KEY", &public_key_base64())]);
+        testing::state_at(db, config, &MockClock::new(now()))
+    }
+
+    fn multipart(fields: &[(&str, &str)]) -> String {
+        let mut body = String::new();
+        for (name, value) in fields {
+            body.push_str(&format!("--xYzZY\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", name, value));
+        }
+        body.push_str("--xYzZY\r\nContent-Disposition: form-data; name=\"attachment1\"; filename=\"a.pdf\"\r\n\r\n%PDF\r\n--xYzZY--\r\n");
+        body
+    }
+
+    fn sign(timestamp: &str, body: &str) -> String {
+        let signature: Signature = p256::ecdsa::signature::Signer::sign(&signing_key(), [timestamp.as_bytes(), body.as_bytes()].concat().as_slice());
+        STANDARD.encode(signature.to_der().as_bytes())
+    }
+
+    fn delivery(timestamp: &str, signature: &str, body: String) -> Request {
+        let mut request = testing::request(
+            Method::POST,
+            "/api/v1/inbound/email/sendgrid",
+            Some("multipart/form-data; boundary=xYzZY"),
+            Body::from(body),
+        );
+        request.headers_mut().insert(TIMESTAMP_HEADER, timestamp.parse().unwrap());
+        request.headers_mut().insert(SIGNATURE_HEADER, signature.parse().unwrap());
+        request
+    }
+
+    fn signed(timestamp: i64, body: String) -> Request {
+        let timestamp = timestamp.to_string();
+        let signature = sign(&timestamp, &body);
+        delivery(&timestamp, &signature, body)
+    }
+
+    fn parsed(subject: &str, message_id: &str) -> String {
+        multipart(&[
+            ("headers", &format!("From: Ana <ana@example.com>\nMessage-ID: {}\nSubject: {}\n", message_id, subject)),
+            ("subject", subject),
+            ("text", "Two liters\n"),
+            ("to", "todo@example.com"),
+        ])
+    }
+
+    async fn todo_count(db: &PgPool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM todos").fetch_one(db).await.unwrap()
+    }
+
+    #[test]
+    fn accepts_the_public_key_as_sendgrid_shows_it() {
+        let key = public_key_base64();
+        assert!(public_key(&key).is_ok());
+        // Partida en líneas, como puede copiarse desde la consola
+        let wrapped = format!("{}\n{}", &key[..40], &key[40..]);
+        assert_eq!(public_key(&wrapped).ok(), Some(*signing_key().verifying_key()));
+        assert!(public_key("not base64!").is_err());
+        assert!(public_key(&STANDARD.encode(b"not a key")).is_err());
+    }
+
+    #[test]
+    fn verifies_the_timestamp_and_the_exact_body() {
+        let key = *signing_key().verifying_key();
+        let signature = sign("1893499200", "body");
+        assert!(verify(&key, "1893499200", b"body", &signature));
+        assert!(!verify(&key, "1893499201", b"body", &signature));
+        assert!(!verify(&key, "1893499200", b"body ", &signature));
+        assert!(!verify(&key, "1893499200", b"body", "not base64"));
+        assert!(!verify(&key, "1893499200", b"body", &STANDARD.encode(b"not der")));
+        let other = SigningKey::from_slice(&[8; 32]).unwrap();
+        assert!(!verify(other.verifying_key(), "1893499200", b"body", &signature));
+    }
+
+    #[sqlx::test]
+    async fn creates_a_todo_from_a_parsed_email(db: PgPool) {
+        let state = state(db.clone());
+
+        let response = testing::send(&state, signed(now().timestamp(), parsed("Buy milk", "<1@mail>"))).await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let todo = &testing::json(response).await["data"];
+        assert_eq!((&todo["title"], &todo["description"]), (&serde_json::json!("Buy milk"), &serde_json::json!("Two liters")));
+        let message_id: String = sqlx::query_scalar("SELECT message_id FROM inbound_emails").fetch_one(&db).await.unwrap();
+        assert_eq!(message_id, "<1@mail>");
+    }
+
+    #[sqlx::test]
+    async fn creates_a_todo_from_a_raw_email(db: PgPool) {
+        let state = state(db.clone());
+        let raw = "From: ana@example.com\r\nSubject: Call the plumber\r\nMessage-ID: <raw@mail>\r\n\r\nBefore Friday\r\n";
+
+        let response = testing::send(&state, signed(now().timestamp(), multipart(&[("email", raw)]))).await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let todo = &testing::json(response).await["data"];
+        assert_eq!((&todo["title"], &todo["description"]), (&serde_json::json!("Call the plumber"), &serde_json::json!("Before Friday")));
+    }
+
+    #[sqlx::test]
+    async fn rejects_unsigned_tampered_and_stale_deliveries(db: PgPool) {
+        let state = state(db.clone());
+        let timestamp = now().timestamp().to_string();
+        let body = parsed("Buy milk", "<1@mail>");
+        let signature = sign(&timestamp, &body);
+
+        let unsigned = testing::request(
+            Method::POST,
+            "/api/v1/inbound/email/sendgrid",
+            Some("multipart/form-data; boundary=xYzZY"),
+            Body::from(body.clone()),
+        );
+        let tampered = delivery(&timestamp, &signature, body.replace("Buy milk", "Buy beer"));
+        let retimed = delivery(&(now().timestamp() + 1).to_string(), &signature, body.clone());
+        let stale = signed(now().timestamp() - 301, body.clone());
+        for request in [unsigned, tampered, retimed, stale] {
+            assert_eq!(testing::send(&state, request).await.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        // Sin clave configurada no se acepta nada
+        let unconfigured = testing::state_at(db.clone(), testing::config(&[]), &MockClock::new(now()));
+        let response = testing::send(&unconfigured, signed(now().timestamp(), body)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(todo_count(&db).await, 0);
+    }
+
+    #[sqlx::test]
+    async fn ignores_replays_and_duplicate_message_ids(db: PgPool) {
+        let state = state(db.clone());
+        let body = parsed("Buy milk", "<1@mail>");
+        let timestamp = now().timestamp().to_string();
+        let signature = sign(&timestamp, &body);
+
+        let first = testing::send(&state, delivery(&timestamp, &signature, body.clone())).await;
+        assert_eq!(first.status(), StatusCode::CREATED);
+        let replay = testing::send(&state, delivery(&timestamp, &signature, body)).await;
+        assert_eq!(replay.status(), StatusCode::OK);
+        // Otra entrega firmada del mismo email, con el Message-ID sin corchetes
+        let retry = testing::send(&state, signed(now().timestamp() + 5, parsed("Buy milk", "1@mail"))).await;
+        assert_eq!(retry.status(), StatusCode::OK);
+        assert_eq!(testing::json(retry).await["data"], "Duplicate delivery ignored");
+        assert_eq!(todo_count(&db).await, 1);
+    }
+}
@@ -0,0 +1,92 @@
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+use utoipa::ToSchema;
+use crate::{
+    model::AppState,
+    response::ApiResponse,
+    features::RequestFeatures
+};
+
+/// Crate version from Cargo.toml.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Value vergen emits when it cannot determine something (e.g. building
+/// outside a git checkout).
+const VERGEN_UNKNOWN: &str = "VERGEN_IDEMPOTENT_OUTPUT";
+
+/// Commit the binary was built from, if it was built from a git checkout.
+pub fn git_commit() -> Option<&'static str> {
+    known(env!("VERGEN_GIT_SHA"))
+}
+
+/// Version of the compiler the binary was built with.
+pub fn rustc_version() -> Option<&'static str> {
+    known(env!("VERGEN_RUSTC_SEMVER"))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BuildInfo {
+    #[schema(example = "0.1.0")]
+    pub version: String,
+    #[schema(example = "9baa19d0c4f1e2a3b5d6e7f8091a2b3c4d5e6f70")]
+    pub git_commit: Option<String>,
+    /// Whether the working tree had uncommitted changes at build time
+    #[schema(example = false)]
+    pub git_dirty: Option<bool>,
+    #[schema(example = "2025-07-06T10:15:00.000000000Z")]
+    pub build_timestamp: Option<String>,
+    #[schema(example = "1.87.0")]
+    pub rustc_version: Option<String>,
+    /// Cargo features the binary was compiled with
+    #[schema(example = json!([]))]
+    pub features: Vec<String>,
+    /// Feature flags in effect for this request (`FEATURES` plus signed overrides)
+    #[schema(example = json!(["sync"]))]
+    pub flags: Vec<String>,
+    /// Deployment environment name (`APP_ENV`)
+    #[schema(example = "production")]
+    pub environment: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/info",
+    responses(
+        (status = 200, description = "Version and build information", body = ApiResponseBuildInfo),
+        (status = 401, description = "Feature overrides with a missing or wrong signature", body = ApiResponseString)
+    ),
+    tag = "health"
+)]
+pub async fn build_info(
+    State(state): State<Arc<AppState>>,
+    features: RequestFeatures,
+) -> impl IntoResponse {
+    info!("Build info requested");
+
+    let info = BuildInfo {
+        version: VERSION.to_string(),
+        git_commit: git_commit().map(str::to_string),
+        git_dirty: known(env!("VERGEN_GIT_DIRTY")).and_then(|dirty| dirty.parse().ok()),
+        build_timestamp: known(env!("VERGEN_BUILD_TIMESTAMP")).map(str::to_string),
+        rustc_version: rustc_version().map(str::to_string),
+        features: env!("VERGEN_CARGO_FEATURES")
+            .split(',')
+            .filter(|feature| !feature.is_empty() && *feature != VERGEN_UNKNOWN)
+            .map(str::to_string)
+            .collect(),
+        flags: features.enabled().map(str::to_string).collect(),
+        environment: state.config.app_env.clone(),
+    };
+
+    (StatusCode::OK, Json(ApiResponse::success(info)))
+}
+
+fn known(value: &'static str) -> Option<&'static str> {
+    Some(value).filter(|value| !value.is_empty() && *value != VERGEN_UNKNOWN)
+}
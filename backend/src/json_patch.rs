@@ -0,0 +1,366 @@
+//! RFC 6902 JSON Patch on a todo, for `PATCH /api/v1/todos/:id` with
+//! `Content-Type: application/json-patch+json`.
+//!
+//! The operations apply to a document holding the mutable fields of the todo
+//! (`/title`, `/description`, `/completed` and `/due_date`); the patched
+//! document is then validated like a PUT body. The handler runs all of it
+//! through [`crate::service::TodoService::update_with`], so a `test` that
+//! passed still holds when the result is written.
+
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use crate::{casing, error::AppError, model::Todo, timestamp};
+
+pub const MEDIA_TYPE: &str = "application/json-patch+json";
+
+const MUTABLE_FIELDS: [&str; 4] = ["title", "description", "completed", "due_date"];
+/// Removing one of these sets it to `null`; the other mutable fields are
+/// required.
+const NULLABLE_FIELDS: [&str; 2] = ["description", "due_date"];
+const READ_ONLY_FIELDS: [&str; 3] = ["id", "created_at", "updated_at"];
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Operation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Test { path: String, value: Value },
+    Move {},
+    Copy {},
+}
+
+/// The mutable fields of `todo`, as the operations see them.
+pub fn document(todo: &Todo) -> Map<String, Value> {
+    let document = json!({
+        "title": todo.title,
+        "description": todo.description,
+        "completed": todo.completed,
+        "due_date": todo.due_date.as_ref().map(timestamp::format),
+    });
+    match document {
+        Value::Object(document) => document,
+        _ => Map::new(),
+    }
+}
+
+/// Applies `operations` in order. Malformed operations and paths outside the
+/// mutable fields are a 422, a failed `test` a 409; both name the zero-based
+/// index of the operation.
+pub fn apply(operations: &[Value], document: &mut Map<String, Value>) -> Result<(), AppError> {
+    for (index, operation) in operations.iter().enumerate() {
+        let operation = serde_json::from_value::<Operation>(operation.clone())
+            .map_err(|e| unprocessable(index, e.to_string()))?;
+        match operation {
+            Operation::Add { path, value } | Operation::Replace { path, value } => {
+                let field = field(index, &path)?;
+                document.insert(field.to_string(), value);
+            }
+            Operation::Remove { path } => {
+                let field = field(index, &path)?;
+                if !NULLABLE_FIELDS.contains(&field) {
+                    return Err(unprocessable(
+                        index,
+                        format!("{} is required and cannot be removed, replace it instead", path),
+                    ));
+                }
+                document.insert(field.to_string(), Value::Null);
+            }
+            Operation::Test { path, value } => {
+                let field = field(index, &path)?;
+                let current = document.get(field).unwrap_or(&Value::Null);
+                if !same(field, current, &value) {
+                    return Err(AppError::Conflict(format!(
+                        "Operation {}: test failed, {} is {}",
+                        index, path, current
+                    )));
+                }
+            }
+            Operation::Move {} | Operation::Copy {} => {
+                return Err(unprocessable(
+                    index,
+                    "only add, remove, replace and test are supported".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The mutable field a JSON Pointer names, in snake_case whatever
+/// `JSON_CASE` the client uses.
+fn field(index: usize, path: &str) -> Result<&'static str, AppError> {
+    let Some(name) = path.strip_prefix('/') else {
+        return Err(unprocessable(
+            index,
+            format!("path {:?} must point to a field of the todo, such as /title", path),
+        ));
+    };
+    let name = casing::camel_to_snake(name);
+    if READ_ONLY_FIELDS.contains(&name.as_str()) {
+        return Err(unprocessable(index, format!("{} is read-only", path)));
+    }
+    MUTABLE_FIELDS
+        .iter()
+        .find(|field| **field == name)
+        .copied()
+        .ok_or_else(|| {
+            unprocessable(
+                index,
+                format!("{} is not a mutable field of the todo, expected one of /{}", path, MUTABLE_FIELDS.join(", /")),
+            )
+        })
+}
+
+/// JSON equality, except that due dates compare as instants so a `test` need
+/// not repeat the exact format the server returned.
+fn same(field: &str, current: &Value, expected: &Value) -> bool {
+    match (current, expected) {
+        (Value::String(current), Value::String(expected)) if field == "due_date" => {
+            match (timestamp::parse(current), timestamp::parse(expected)) {
+                (Some(current), Some(expected)) => current == expected,
+                _ => false,
+            }
+        }
+        _ => current == expected,
+    }
+}
+
+fn unprocessable(index: usize, message: String) -> AppError {
+    AppError::UnprocessableEntity(format!("Operation {}: {}", index, message))
+}
+
+/// Adds the `application/json-patch+json` alternative to the request body of
+/// `PATCH /api/v1/todos/{id}`, which `#[utoipa::path]` can only give one schema.
+pub fn document_json_patch(openapi: &mut utoipa::openapi::OpenApi) {
+    use utoipa::openapi::{path::PathItemType, ArrayBuilder, Content, ObjectBuilder, SchemaType};
+
+    let request_body = openapi
+        .paths
+        .paths
+        .get_mut("/api/v1/todos/{id}")
+        .and_then(|item| item.operations.get_mut(&PathItemType::Patch))
+        .and_then(|operation| operation.request_body.as_mut());
+    if let Some(request_body) = request_body {
+        let operation = ObjectBuilder::new()
+            .property(
+                "op",
+                ObjectBuilder::new()
+                    .schema_type(SchemaType::String)
+                    .enum_values(Some(["add", "remove", "replace", "test"])),
+            )
+            .required("op")
+            .property(
+                "path",
+                ObjectBuilder::new()
+                    .schema_type(SchemaType::String)
+                    .description(Some(format!("One of /{}", MUTABLE_FIELDS.join(", /")))),
+            )
+            .required("path")
+            .property(
+                "value",
+                ObjectBuilder::new().description(Some("Required by add, replace and test")),
+            );
+        let schema = ArrayBuilder::new()
+            .items(operation)
+            .description(Some(
+                "RFC 6902 operations, applied in order and all or none; a failed test is a 409",
+            ))
+            .example(Some(json!([
+                { "op": "test", "path": "/completed", "value": false },
+                { "op": "replace", "path": "/completed", "value": true },
+                { "op": "remove", "path": "/due_date" }
+            ])));
+        request_body.content.insert(MEDIA_TYPE.to_string(), Content::new(schema));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::{Method, StatusCode}};
+    use serde_json::{json, Map, Value};
+    use sqlx::PgPool;
+    use crate::{error::AppError, testing};
+    use super::{apply, MEDIA_TYPE};
+
+    fn document() -> Map<String, Value> {
+        match json!({"title": "Buy milk", "description": null, "completed": false, "due_date": "2030-01-01T10:00:00.000Z"}) {
+            Value::Object(document) => document,
+            _ => unreachable!(),
+        }
+    }
+
+    fn patched(operations: Value) -> Result<Value, AppError> {
+        let mut document = document();
+        apply(operations.as_array().unwrap(), &mut document).map(|()| Value::Object(document))
+    }
+
+    fn unprocessable(operations: Value) -> String {
+        match patched(operations) {
+            Err(AppError::UnprocessableEntity(message)) => message,
+            other => panic!("expected a 422, got {:?}", other.map(|_| ()).map_err(|e| format!("{:?}", e))),
+        }
+    }
+
+    #[test]
+    fn applies_add_replace_remove_and_test_in_order() {
+        let result = patched(json!([
+            {"op": "test", "path": "/completed", "value": false},
+            {"op": "replace", "path": "/completed", "value": true},
+            {"op": "test", "path": "/completed", "value": true},
+            {"op": "add", "path": "/description", "value": "Two liters"},
+            {"op": "remove", "path": "/due_date"},
+            {"op": "replace", "path": "/title", "value": "Buy oat milk"}
+        ]))
+        .unwrap();
+        assert_eq!(result, json!({"title": "Buy oat milk", "description": "Two liters", "completed": true, "due_date": null}));
+        assert_eq!(patched(json!([])).unwrap(), Value::Object(document()));
+        // Los nombres en camelCase llegan al mismo campo
+        assert_eq!(patched(json!([{"op": "remove", "path": "/dueDate"}])).unwrap()["due_date"], Value::Null);
+    }
+
+    #[test]
+    fn compares_due_dates_as_instants() {
+        for same in ["2030-01-01T10:00:00Z", "2030-01-01T12:00:00+02:00", "2030-01-01T10:00:00.000000Z"] {
+            assert!(patched(json!([{"op": "test", "path": "/due_date", "value": same}])).is_ok(), "{}", same);
+        }
+        for different in [json!("2030-01-01T10:00:01Z"), json!("tomorrow"), json!(null)] {
+            assert!(patched(json!([{"op": "test", "path": "/due_date", "value": different}])).is_err(), "{}", different);
+        }
+    }
+
+    #[test]
+    fn a_failed_test_is_a_conflict_naming_the_operation() {
+        let result = patched(json!([
+            {"op": "replace", "path": "/title", "value": "Buy oat milk"},
+            {"op": "test", "path": "/title", "value": "Buy milk"}
+        ]));
+        match result {
+            Err(AppError::Conflict(message)) => assert_eq!(message, "Operation 1: test failed, /title is \"Buy oat milk\""),
+            other => panic!("expected a 409, got {:?}", other.map(|_| ()).map_err(|e| format!("{:?}", e))),
+        }
+    }
+
+    #[test]
+    fn rejects_read_only_unknown_and_malformed_operations_by_index() {
+        let replace = json!({"op": "replace", "path": "/title", "value": "Ok"});
+        for (operation, message) in [
+            (json!({"op": "replace", "path": "/id", "value": "x"}), "Operation 1: /id is read-only"),
+            (json!({"op": "remove", "path": "/created_at"}), "Operation 1: /created_at is read-only"),
+            (json!({"op": "test", "path": "/updatedAt", "value": "x"}), "Operation 1: /updatedAt is read-only"),
+            (
+                json!({"op": "add", "path": "/tags", "value": []}),
+                "Operation 1: /tags is not a mutable field of the todo, expected one of /title, /description, /completed, /due_date",
+            ),
+            (
+                json!({"op": "replace", "path": "title", "value": "x"}),
+                "Operation 1: path \"title\" must point to a field of the todo, such as /title",
+            ),
+            (
+                json!({"op": "remove", "path": "/title"}),
+                "Operation 1: /title is required and cannot be removed, replace it instead",
+            ),
+            (
+                json!({"op": "move", "from": "/title", "path": "/description"}),
+                "Operation 1: only add, remove, replace and test are supported",
+            ),
+            (
+                json!({"op": "copy", "from": "/title", "path": "/description"}),
+                "Operation 1: only add, remove, replace and test are supported",
+            ),
+        ] {
+            assert_eq!(unprocessable(json!([replace, operation])), message);
+        }
+
+        for operation in [json!({"op": "replace", "path": "/title"}), json!({"op": "rename", "path": "/title"}), json!({"path": "/title"}), json!("replace")] {
+            let message = unprocessable(json!([replace, operation]));
+            assert!(message.starts_with("Operation 1: "), "{}", message);
+        }
+    }
+
+    fn json_patch(uri: &str, operations: Value) -> axum::extract::Request {
+        testing::request(Method::PATCH, uri, Some(MEDIA_TYPE), Body::from(operations.to_string()))
+    }
+
+    async fn title(db: &PgPool, id: uuid::Uuid) -> (String, bool) {
+        sqlx::query_as("SELECT title, completed FROM todos WHERE id = $1").bind(id).fetch_one(db).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn patches_are_validated_and_applied_all_or_nothing(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let id = testing::insert_todo(&db, "Buy milk").await;
+        let uri = format!("/api/v1/todos/{}", id);
+
+        for (operations, status, message) in [
+            (
+                json!([{"op": "replace", "path": "/completed", "value": true}, {"op": "replace", "path": "/id", "value": "x"}]),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Operation 1: /id is read-only",
+            ),
+            (
+                json!([{"op": "replace", "path": "/completed", "value": true}, {"op": "test", "path": "/title", "value": "Buy eggs"}]),
+                StatusCode::CONFLICT,
+                "Operation 1: test failed, /title is \"Buy milk\"",
+            ),
+            (
+                json!([{"op": "replace", "path": "/completed", "value": true}, {"op": "replace", "path": "/title", "value": ""}]),
+                StatusCode::BAD_REQUEST,
+                "Title must be between 1 and 255 characters",
+            ),
+            (
+                json!([{"op": "replace", "path": "/completed", "value": "yes"}]),
+                StatusCode::BAD_REQUEST,
+                "Validation failed",
+            ),
+        ] {
+            let response = testing::send(&state, json_patch(&uri, operations.clone())).await;
+            assert_eq!(response.status(), status, "{}", operations);
+            let body = testing::json(response).await;
+            assert!(body["error"].as_str().unwrap().contains(message), "{}: {}", operations, body);
+            assert_eq!(title(&db, id).await, ("Buy milk".to_string(), false), "{}", operations);
+        }
+
+        let operations = json!([
+            {"op": "test", "path": "/completed", "value": false},
+            {"op": "replace", "path": "/completed", "value": true},
+            {"op": "add", "path": "/dueDate", "value": "2030-06-01T10:00:00+02:00"}
+        ]);
+        let response = testing::send(&state, json_patch(&uri, operations)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let todo = &testing::json(response).await["data"];
+        assert_eq!((&todo["completed"], &todo["due_date"]), (&json!(true), &json!("2030-06-01T08:00:00.000Z")));
+
+        // El PATCH de tipo merge sigue funcionando con application/json
+        let merge = testing::json_request(Method::PATCH, &uri, &json!({"title": "Buy oat milk"}));
+        let response = testing::send(&state, merge).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(title(&db, id).await, ("Buy oat milk".to_string(), true));
+
+        let missing = format!("/api/v1/todos/{}", uuid::Uuid::new_v4());
+        let response = testing::send(&state, json_patch(&missing, json!([{"op": "remove", "path": "/due_date"}]))).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn a_test_operation_is_a_compare_and_swap(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let id = testing::insert_todo(&db, "Buy milk").await;
+        let uri = format!("/api/v1/todos/{}", id);
+        let claim = |title: &str| {
+            json_patch(&uri, json!([
+                {"op": "test", "path": "/completed", "value": false},
+                {"op": "replace", "path": "/completed", "value": true},
+                {"op": "replace", "path": "/title", "value": title}
+            ]))
+        };
+
+        // Solo uno de los dos ve completed = false cuando escribe
+        let (first, second) = tokio::join!(testing::send(&state, claim("First")), testing::send(&state, claim("Second")));
+        let mut statuses = [first.status(), second.status()];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+        let winner = if first.status() == StatusCode::OK { "First" } else { "Second" };
+        assert_eq!(title(&db, id).await, (winner.to_string(), true));
+    }
+}
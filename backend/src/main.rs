@@ -1,13 +1,20 @@
-use axum::Router;
-use std::sync::Arc;
+use axum::{extract::{DefaultBodyLimit, Request}, middleware, response::Response, Router, ServiceExt};
+use tower::{Layer, Service};
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 use model::AppState;
 use routes::app_routes;
-use sqlx::PgPool;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::time::Duration;
 use dotenvy::dotenv;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tower_http::{cors::CorsLayer, decompression::RequestDecompressionLayer, trace::TraceLayer};
 use config::Config;
+use features::FeatureSource;
+use startup::StartupError;
+use std::process::ExitCode;
+use tracing::Instrument;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 mod routes;
 mod handler;
@@ -15,30 +22,117 @@ mod response;
 mod model;
 mod config;
 mod error;
+mod markdown;
+mod inbound;
+mod export;
+mod import;
+mod ical;
+mod natural_date;
+mod suggest;
+mod startup;
+mod info;
+mod admin;
+mod telemetry;
+mod heartbeat;
+mod features;
+mod clock;
+mod repository;
+mod service;
+mod archive;
+mod filter;
+mod encoding;
+mod casing;
+mod migrate;
+mod changes;
+mod backup;
+mod grouped;
+mod client_ip;
+mod access_log;
+mod timestamp;
+mod retry;
+mod metrics;
+mod json_patch;
+mod api;
+mod signature;
+#[cfg(test)]
+mod testing;
+
+/// Mailgun accepts messages up to 25 MB, attachments included.
+const INBOUND_EMAIL_BODY_LIMIT: usize = 32 * 1024 * 1024;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         handler::create_todo,
+        handler::quick_add,
         handler::get_todos,
+        export::export_todos_xlsx,
+        import::import_ics,
+        import::import_google_tasks,
+        archive::list_archived,
+        archive::restore_archived,
+        changes::get_changes,
+        grouped::get_grouped,
+        suggest::suggest,
         handler::get_todo,
+        handler::get_todo_description_html,
         handler::update_todo,
+        handler::patch_todo,
         handler::delete_todo,
-        handler::health_check
+        handler::health_check,
+        handler::readiness_check,
+        info::build_info,
+        metrics::metrics,
+        inbound::mailgun::inbound_email,
+        inbound::sendgrid::inbound_email_sendgrid,
+        inbound::ses::inbound_email_ses,
+        admin::shutdown,
+        admin::undrain,
+        archive::run_archival
     ),
     components(
         schemas(
             model::Todo,
             handler::CreateTodo,
+            handler::ReplaceTodo,
+            handler::PatchTodo,
             handler::PaginationQuery,
+            handler::FilterQuery,
+            handler::QuickAddQuery,
             response::ApiResponseTodo,
             response::ApiResponseVecTodo,
-            response::ApiResponseString
+            response::ApiResponseString,
+            import::ImportReport,
+            import::ImportIssue,
+            import::ImportListSummary,
+            response::ApiResponseImportReport,
+            suggest::SuggestQuery,
+            suggest::Suggestion,
+            response::ApiResponseVecSuggestion,
+            info::BuildInfo,
+            response::ApiResponseBuildInfo,
+            admin::ShutdownQuery,
+            archive::ArchivedTodo,
+            archive::ArchiveSearch,
+            archive::ArchiveRunQuery,
+            archive::ArchiveRun,
+            response::ApiResponseVecArchivedTodo,
+            response::ApiResponseArchiveRun,
+            changes::ChangesQuery,
+            changes::TodoChange,
+            changes::Changes,
+            response::ApiResponseChanges,
+            grouped::GroupBy,
+            grouped::GroupedQuery,
+            grouped::TodoGroup,
+            response::ApiResponseVecTodoGroup
         )
     ),
     tags(
         (name = "todos", description = "Todo management API"),
-        (name = "health", description = "Health check endpoints")
+        (name = "health", description = "Health check endpoints"),
+        (name = "inbound", description = "Inbound webhooks from email providers"),
+        (name = "admin", description = "Operational endpoints, protected by X-Admin-Secret")
     ),
     info(
         title = "Todo API",
@@ -53,56 +147,264 @@ mod error;
 struct ApiDoc;
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
+    // Cargar variables de entorno
+    dotenv().ok();
+
+    let command = match backup::Command::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(usage) => {
+            eprintln!("{}", usage);
+            // EX_USAGE
+            return ExitCode::from(64);
+        }
+    };
+
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            let e = StartupError::Config(e);
+            e.report();
+            return e.exit_code();
+        }
+    };
+
+    timestamp::set_precision(config.timestamp_precision);
+
+    // Los subcomandos de backup no arrancan el servidor ni el logging
+    if !matches!(command, backup::Command::Serve) {
+        for warning in &config.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+    let result = match command {
+        backup::Command::Serve => None,
+        backup::Command::Backup { out } => Some(backup::backup(&config, &out).await),
+        backup::Command::Restore { file, wipe } => Some(backup::restore(&config, &file, wipe).await),
+    };
+    if let Some(result) = result {
+        return match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    // Sentry solo se inicializa (y sus capas solo se instalan) si hay DSN
+    let sentry = telemetry::init_sentry(&config);
+
     // Inicializar logging
-    tracing_subscriber::fmt()
-        .with_env_filter("backend=debug,tower_http=debug")
+    tracing_subscriber::registry()
+        .with(EnvFilter::new("backend=debug,tower_http=debug"))
+        .with(tracing_subscriber::fmt::layer())
+        .with(sentry.as_ref().map(|_| sentry_tracing::layer()))
         .init();
 
-    // Cargar variables de entorno
-    dotenv().ok();
+    // Span raíz: todos los logs llevan la versión y el commit desplegados
+    let root_span = tracing::info_span!(
+        "todo_api",
+        version = info::VERSION,
+        commit = info::git_commit().unwrap_or("unknown")
+    );
+
+    let result = run(config, sentry.is_some()).instrument(root_span).await;
 
-    let config = Config::from_env()
-        .expect("Failed to load configuration from environment variables");
+    // Enviar los eventos pendientes antes de salir
+    if let Some(sentry) = sentry {
+        sentry.flush(Some(Duration::from_secs(2)));
+    }
 
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            e.report();
+            e.exit_code()
+        }
+    }
+}
+
+async fn run(config: Config, sentry_enabled: bool) -> Result<(), StartupError> {
     tracing::info!("Starting Todo API server...");
-    tracing::debug!("Database URL: {}", config.database_url);
+    for warning in &config.warnings {
+        tracing::warn!("{}", warning);
+    }
+    if !config.features.is_empty() {
+        tracing::info!("🚩 Feature flags enabled: {}", config.features.join(", "));
+    }
+    tracing::debug!("Database URL: {}", config::redact_password(&config.database_url));
 
-    // Crear pool de conexiones
-    let pool = PgPool::connect(&config.database_url)
-        .await
-        .expect("Failed to connect to PostgreSQL");
+    let state = if config.db_connect_lazy {
+        // Modo lazy: no se conecta ni migra hasta que la base de datos responda
+        tracing::info!("💤 Lazy database connection enabled, migrations deferred");
+        let pool = PgPoolOptions::new().connect_lazy(&config.database_url)?;
+        let state = Arc::new(AppState::new(pool, config.clone(), false));
+        tokio::spawn(migrate::run_deferred(state.clone()));
+        state
+    } else {
+        // Crear pool de conexiones
+        let pool = PgPool::connect(&config.database_url).await?;
 
-    // Ejecutar migraciones
-    tracing::info!("🔄 Running database migrations...");
-    sqlx::migrate!("./migrations")
-        .run(&pool)
+        // Ejecutar migraciones
+        tracing::info!("🔄 Running database migrations...");
+        let lock_timeout = Duration::from_secs(config.migration_lock_timeout_secs);
+        let migrated = migrate::run(&pool, lock_timeout).await?;
+        tracing::info!("🗄️ Database connected successfully");
+
+        let state = Arc::new(AppState::new(pool, config.clone(), migrated));
+        if !migrated {
+            // Sin el lock no se sale: el servicio arranca sin estar listo y reintenta
+            tracing::warn!("Starting without migrations, readiness will fail until they are applied");
+            tokio::spawn(migrate::run_deferred(state.clone()));
+        }
+        state
+    };
+
+    let access_log = access_log::init(&config).map_err(StartupError::AccessLog)?;
+    if let (Some(_), Some(path)) = (&access_log, &config.access_log_path) {
+        tracing::info!("📝 Access log enabled at {} ({:?} rotation)", path, config.access_log_rotation);
+    }
+    let app = app(state.clone(), sentry_enabled, access_log.as_ref().map(|(log, _)| log.clone()));
+    let base_path = config.base_path.as_str();
+
+    let bind_address = format!("{}:{}", config.server_host, config.server_port);
+    
+    let listener = tokio::net::TcpListener::bind(&bind_address)
         .await
-        .expect("Failed to run migrations");
-    tracing::info!("✅ Migrations completed successfully");
+        .map_err(|source| StartupError::Bind {
+            address: bind_address.clone(),
+            source,
+        })?;
+
+    tracing::info!("🚀 Server starting on http://{}", bind_address);
+    tracing::info!("📚 Swagger UI available at http://{}{}/swagger-ui", bind_address, base_path);
+    tracing::info!("🏥 Health check available at http://{}{}/api/v1/health", bind_address, base_path);
 
-    let state = Arc::new(AppState {
-        db: pool,
+    let heartbeat = config.heartbeat_url.clone().map(|url| {
+        let interval = Duration::from_secs(config.heartbeat_interval_secs);
+        tokio::spawn(heartbeat::run(state.clone(), url, interval))
     });
 
+    let archival = config
+        .archive_after_days
+        .map(|days| tokio::spawn(archive::run_job(state.clone(), days)));
+
+    let listener_task = tokio::spawn(changes::run_listener(state.clone()));
+
+    let served = axum::serve(listener, ServiceExt::<axum::extract::Request>::into_make_service_with_connect_info::<SocketAddr>(app))
+        .with_graceful_shutdown(admin::shutdown_signal(state))
+        .await;
+    for task in [heartbeat, archival, Some(listener_task)].into_iter().flatten() {
+        task.abort();
+    }
+    served.map_err(StartupError::Server)
+}
+
+/// The OpenAPI document, adjusted to the configuration and flags the router
+/// is built with.
+fn openapi(config: &Config, features: &dyn FeatureSource) -> utoipa::openapi::OpenApi {
+    let mut openapi = ApiDoc::openapi();
+    if !features.is_enabled(features::QUICK_ADD) {
+        openapi.paths.paths.remove("/api/v1/quick-add");
+    }
+    handler::document_pagination(&mut openapi, config);
+    handler::document_plain_create(&mut openapi);
+    json_patch::document_json_patch(&mut openapi);
+    if config.json_case == casing::JsonCase::Camel {
+        casing::document(&mut openapi);
+    }
+
+    if !config.base_path.is_empty() {
+        openapi.servers = Some(vec![utoipa::openapi::Server::new(&config.base_path)]);
+    }
+    openapi
+}
+
+/// The whole service: routes, Swagger UI and every middleware, as served by
+/// [`run`]. Requests must carry `ConnectInfo<SocketAddr>` for the client IP
+/// to be resolved.
+fn app(
+    state: Arc<AppState>,
+    sentry_enabled: bool,
+    access_log: Option<Arc<access_log::AccessLog>>,
+) -> impl Service<Request, Response = Response, Error = Infallible, Future: Send> + Clone + Send + 'static {
+    let config = &state.config;
     let app = Router::new()
         .nest("/api/v1/todos", app_routes())
         .route("/api/v1/health", axum::routing::get(handler::health_check))
+        .route("/api/v1/health/ready", axum::routing::get(handler::readiness_check))
+        .route("/api/v1/info", axum::routing::get(info::build_info))
+        .route("/api/v1/metrics", axum::routing::get(metrics::metrics))
+        .route("/api/v1/suggest", axum::routing::get(suggest::suggest))
+        .route(
+            "/api/v1/inbound/email",
+            axum::routing::post(inbound::mailgun::inbound_email)
+                .layer(DefaultBodyLimit::max(INBOUND_EMAIL_BODY_LIMIT)),
+        )
+        .route(
+            "/api/v1/inbound/email/sendgrid",
+            axum::routing::post(inbound::sendgrid::inbound_email_sendgrid)
+                .layer(DefaultBodyLimit::max(INBOUND_EMAIL_BODY_LIMIT)),
+        )
+        .route(
+            "/api/v1/inbound/email/ses",
+            axum::routing::post(inbound::ses::inbound_email_ses)
+                .layer(DefaultBodyLimit::max(INBOUND_EMAIL_BODY_LIMIT)),
+        )
+        .route("/api/v1/admin/shutdown", axum::routing::post(admin::shutdown))
+        .route("/api/v1/admin/undrain", axum::routing::post(admin::undrain))
+        .route("/api/v1/admin/archive", axum::routing::post(archive::run_archival));
+    // Experimentales: sin su flag al arrancar no se montan y dan 404
+    let app = if state.features.is_enabled(features::QUICK_ADD) {
+        app.route(
+            "/api/v1/quick-add",
+            axum::routing::post(handler::quick_add)
+                .layer(middleware::from_fn_with_state(encoding::PLAIN_TEXT, encoding::require_content_type)),
+        )
+    } else {
+        app
+    };
+    let app = if sentry_enabled { telemetry::sentry_layers(app) } else { app };
+    let app = match config.json_case {
+        casing::JsonCase::Camel => app.layer(middleware::from_fn(casing::camel_case_json)),
+        casing::JsonCase::Snake => app,
+    };
+    let app = app
+        .layer(RequestDecompressionLayer::new())
+        .layer(middleware::from_fn(encoding::reject_unsupported_encoding))
+        .layer(middleware::from_fn_with_state(state.clone(), admin::reject_while_draining))
+        .with_state(state.clone());
+
+    // Las capas de arriba ven la ruta sin el prefijo; Swagger UI se monta fuera
+    // porque su redirección a /swagger-ui/ es absoluta
+    let base_path = config.base_path.as_str();
+    let app = if base_path.is_empty() { app } else { Router::new().nest(base_path, app) };
+    let app = app
         .merge(
-            SwaggerUi::new("/swagger-ui")
-                .url("/api-docs/openapi.json", ApiDoc::openapi())
+            SwaggerUi::new(format!("{}/swagger-ui", base_path))
+                .url(format!("{}/api-docs/openapi.json", base_path), openapi(config, state.features.as_ref()))
         )
         .layer(CorsLayer::permissive())
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
-
-    let bind_address = format!("{}:{}", config.server_host, config.server_port);
-    
-    tracing::info!("🚀 Server starting on http://{}", bind_address);
-    tracing::info!("📚 Swagger UI available at http://{}/swagger-ui", bind_address);
-    tracing::info!("🗄️ Database connected successfully");
-    tracing::info!("🏥 Health check available at http://{}/api/v1/health", bind_address);
-
-    let listener = tokio::net::TcpListener::bind(&bind_address).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
-}
\ No newline at end of file
+        .layer(TraceLayer::new_for_http().make_span_with(|request: &Request| {
+            let client = request.extensions().get::<client_ip::ClientIp>().map(|client| client.0);
+            let request_id = request.extensions().get::<telemetry::RequestId>().map(|id| id.0.as_str());
+            tracing::debug_span!(
+                "request",
+                method = %request.method(),
+                uri = %request.uri(),
+                version = ?request.version(),
+                client_ip = client.map(tracing::field::display),
+                request_id,
+            )
+        }));
+    let app = match access_log {
+        Some(log) => app.layer(middleware::from_fn_with_state(log, access_log::log_request)),
+        None => app,
+    };
+    // Fuera de todo lo demás para que la traza, el access log y los handlers vean la misma IP y el mismo id
+    let app = app
+        .layer(middleware::from_fn(telemetry::assign_request_id))
+        .layer(middleware::from_fn_with_state(state.clone(), client_ip::resolve_client_ip));
+    middleware::from_fn_with_state(state, routes::trim_trailing_slash).layer(app)
+}
@@ -0,0 +1,105 @@
+use ammonia::Builder;
+use pulldown_cmark::{html, Options, Parser};
+
+/// Markdown extensions enabled when rendering descriptions: GFM tables and
+/// task-list checkboxes. Everything else is plain CommonMark.
+const MARKDOWN_OPTIONS: Options = Options::ENABLE_TABLES.union(Options::ENABLE_TASKLISTS);
+
+/// Renders a Markdown description to HTML that is safe to embed as-is.
+///
+/// The raw output of pulldown-cmark is passed through ammonia, so raw HTML in
+/// the source (script, style, iframe, event handlers...) and links with
+/// dangerous schemes such as `javascript:` are stripped. The only addition to
+/// ammonia's default allow-list is the disabled `<input type="checkbox">`
+/// emitted for task-list items.
+pub fn render_html(markdown: &str) -> String {
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, MARKDOWN_OPTIONS));
+
+    sanitizer().clean(&unsafe_html).to_string()
+}
+
+fn sanitizer() -> Builder<'static> {
+    let mut builder = Builder::default();
+    builder
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            ("input", "type") if value != "checkbox" => None,
+            _ => Some(value.into()),
+        });
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_html;
+
+    #[test]
+    fn strips_script_tags_and_their_content() {
+        let html = render_html("before\n\n<script>alert('xss')</script>\n\nafter");
+
+        assert!(!html.contains("<script"), "{}", html);
+        assert!(!html.contains("alert"), "{}", html);
+        assert!(html.contains("<p>before</p>") && html.contains("<p>after</p>"), "{}", html);
+    }
+
+    #[test]
+    fn drops_javascript_links_but_keeps_their_text() {
+        for source in [
+            "[click](javascript:alert(1))",
+            "[click](JavaScript:alert(1))",
+            "<a href=\"javascript:alert(1)\">click</a>",
+        ] {
+            let html = render_html(source);
+            assert!(!html.to_lowercase().contains("javascript"), "{} -> {}", source, html);
+            assert!(html.contains("click"), "{} -> {}", source, html);
+        }
+    }
+
+    #[test]
+    fn keeps_safe_links() {
+        let html = render_html("[docs](https://example.com/docs)");
+
+        assert!(html.contains("href=\"https://example.com/docs\""), "{}", html);
+    }
+
+    #[test]
+    fn strips_raw_html_elements() {
+        let html = render_html(
+            "<iframe src=\"https://evil.example\"></iframe>\n\n<style>body { display: none }</style>\n\n<form action=\"/steal\"><button>go</button></form>",
+        );
+
+        for tag in ["<iframe", "<style", "display: none", "<form", "<button"] {
+            assert!(!html.contains(tag), "{} in {}", tag, html);
+        }
+    }
+
+    #[test]
+    fn strips_event_handlers() {
+        let html = render_html(
+            "<img src=\"x.png\" onerror=\"alert(1)\">\n\n<p onclick=\"alert(2)\" onmouseover=\"alert(3)\">text</p>",
+        );
+
+        assert!(!html.contains("onerror") && !html.contains("onclick") && !html.contains("onmouseover"), "{}", html);
+        assert!(!html.contains("alert"), "{}", html);
+        assert!(html.contains("text"), "{}", html);
+    }
+
+    #[test]
+    fn renders_tables_and_task_lists() {
+        let html = render_html("| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n- [ ] todo");
+
+        assert!(html.contains("<table>") && html.contains("<td>1</td>"), "{}", html);
+        assert!(html.contains("<input disabled=\"\" type=\"checkbox\" checked=\"\">"), "{}", html);
+        assert!(html.contains("<input disabled=\"\" type=\"checkbox\">"), "{}", html);
+    }
+
+    #[test]
+    fn only_allows_checkbox_inputs() {
+        let html = render_html("<input type=\"text\" value=\"secret\"> <input type=\"password\">");
+
+        assert!(!html.contains("type=\"text\"") && !html.contains("type=\"password\""), "{}", html);
+        assert!(!html.contains("value="), "{}", html);
+    }
+}
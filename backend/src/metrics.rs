@@ -0,0 +1,109 @@
+//! Process-wide counters, served in the Prometheus text format at
+//! `GET /api/v1/metrics`, along with the build the process runs.
+
+use axum::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+};
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex};
+use crate::info;
+
+// Por operación; los nombres son literales del código, así que el mapa no crece sin límite
+static DATABASE_RETRIES: Mutex<BTreeMap<&'static str, u64>> = Mutex::new(BTreeMap::new());
+
+/// Counts a retry of the database operation `operation`.
+pub fn record_database_retry(operation: &'static str) {
+    let mut retries = DATABASE_RETRIES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    *retries.entry(operation).or_default() += 1;
+}
+
+/// Retries of `operation` since the process started.
+#[cfg(test)]
+pub fn database_retries(operation: &str) -> u64 {
+    let retries = DATABASE_RETRIES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    retries.get(operation).copied().unwrap_or_default()
+}
+
+fn render() -> String {
+    let retries = DATABASE_RETRIES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut body = String::from(
+        "# HELP ha_todo_build_info Build of the running binary, in its labels; the value is always 1.\n\
+         # TYPE ha_todo_build_info gauge\n",
+    );
+    // Los mismos valores que /api/v1/info
+    let _ = writeln!(
+        body,
+        "ha_todo_build_info{{version=\"{}\",git_commit=\"{}\",rustc_version=\"{}\"}} 1",
+        info::VERSION,
+        info::git_commit().unwrap_or("unknown"),
+        info::rustc_version().unwrap_or("unknown")
+    );
+    body.push_str(
+        "# HELP ha_todo_database_retries_total Transient database errors retried, by operation.\n\
+         # TYPE ha_todo_database_retries_total counter\n",
+    );
+    for (operation, count) in retries.iter() {
+        let _ = writeln!(body, "ha_todo_database_retries_total{{operation=\"{}\"}} {}", operation, count);
+    }
+    body
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/metrics",
+    responses(
+        (status = 200, description = "Build info and counters in the Prometheus text exposition format", body = String, content_type = "text/plain")
+    ),
+    tag = "health"
+)]
+pub async fn metrics() -> impl IntoResponse {
+    (StatusCode::OK, [(CONTENT_TYPE, "text/plain; version=0.0.4")], render())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header::CONTENT_TYPE, StatusCode};
+    use crate::testing;
+
+    #[tokio::test]
+    async fn serves_the_retry_counters_per_operation() {
+        super::record_database_retry("metrics test");
+        super::record_database_retry("metrics test");
+        assert_eq!(super::database_retries("metrics test"), 2);
+        assert_eq!(super::database_retries("never retried"), 0);
+
+        let state = testing::state(
+            sqlx::PgPool::connect_lazy("postgres://test@localhost/test").unwrap(),
+            testing::config(&[]),
+        );
+        let response = testing::send(&state, testing::get("/api/v1/metrics")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/plain; version=0.0.4");
+        let body = String::from_utf8(testing::body(response).await).unwrap();
+        assert!(body.contains("\n# HELP ha_todo_database_retries_total "), "{}", body);
+        assert!(body.contains("\nha_todo_database_retries_total{operation=\"metrics test\"} 2\n"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn serves_the_build_of_the_info_endpoint() {
+        let state = testing::state(
+            sqlx::PgPool::connect_lazy("postgres://test@localhost/test").unwrap(),
+            testing::config(&[]),
+        );
+        let info = testing::json(testing::send(&state, testing::get("/api/v1/info")).await).await;
+        let response = testing::send(&state, testing::get("/api/v1/metrics")).await;
+        let body = String::from_utf8(testing::body(response).await).unwrap();
+
+        let label = |name: &str| info["data"][name].as_str().unwrap_or("unknown").to_string();
+        let line = format!(
+            "ha_todo_build_info{{version=\"{}\",git_commit=\"{}\",rustc_version=\"{}\"}} 1\n",
+            label("version"),
+            label("git_commit"),
+            label("rustc_version")
+        );
+        assert!(body.starts_with("# HELP ha_todo_build_info "), "{}", body);
+        assert!(body.contains("\n# TYPE ha_todo_build_info gauge\n"), "{}", body);
+        assert!(body.contains(&format!("\n{}", line)), "{}", body);
+        assert!(line.contains(&format!("version=\"{}\"", env!("CARGO_PKG_VERSION"))), "{}", line);
+    }
+}
@@ -0,0 +1,203 @@
+//! Database migrations, applied by one replica at a time.
+//!
+//! Every instance takes a session-level advisory lock before migrating. The
+//! first one to get it applies the pending migrations; the others wait, then
+//! find nothing pending and only check that the database is up to date. An
+//! instance that cannot get the lock within `MIGRATION_LOCK_TIMEOUT_SECS`
+//! keeps running but stays unready and retries in the background.
+
+use sqlx::{
+    migrate::{Migrate, MigrateError, Migrator},
+    Connection, PgConnection, PgPool,
+};
+use std::{
+    collections::HashSet,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+use crate::model::AppState;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Advisory lock key shared by every instance of this service.
+const LOCK_KEY: i64 = i64::from_be_bytes(*b"ha-todo\0");
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Applies the pending migrations under the advisory lock. Returns `false`
+/// when another instance held the lock for longer than `lock_timeout`.
+///
+/// The lock is taken on a connection of its own rather than one from `pool`,
+/// and that connection is closed afterwards: the lock ends with the session,
+/// so it cannot stay behind in the pool if unlocking fails or this future is
+/// dropped half-way.
+pub async fn run(pool: &PgPool, lock_timeout: Duration) -> Result<bool, MigrateError> {
+    let mut conn = PgConnection::connect_with(&pool.connect_options()).await?;
+
+    if !acquire_lock(&mut conn, lock_timeout).await? {
+        warn!("⏳ Another instance held the migration lock for more than {:?}", lock_timeout);
+        return Ok(false);
+    }
+    let result = migrate_locked(&mut conn).await;
+    // Cerrar la sesión libera el lock, también si la migración falló; si el
+    // cierre ordenado falla, el socket se descarta igualmente
+    if let Err(e) = conn.close().await {
+        warn!("Could not close the migration connection cleanly: {}", e);
+    }
+
+    result?;
+    Ok(true)
+}
+
+/// Retries [`run`] with backoff until it succeeds, then marks the service as
+/// ready. Used when the database is not reachable at startup (lazy mode) or
+/// the migration lock could not be acquired in time.
+pub async fn run_deferred(state: Arc<AppState>) {
+    let lock_timeout = Duration::from_secs(state.config.migration_lock_timeout_secs);
+    let mut delay = Duration::from_secs(1);
+    loop {
+        match run(&state.db, lock_timeout).await {
+            Ok(true) => {
+                state.migrations_done.store(true, Ordering::Release);
+                info!("✅ Deferred migrations completed successfully");
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => warn!("Database not ready, retrying migrations in {:?}: {}", delay, e),
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(Duration::from_secs(30));
+    }
+}
+
+async fn acquire_lock(conn: &mut PgConnection, timeout: Duration) -> Result<bool, sqlx::Error> {
+    let deadline = Instant::now() + timeout;
+    let mut waiting = false;
+    loop {
+        let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(LOCK_KEY)
+            .fetch_one(&mut *conn)
+            .await?;
+        if locked {
+            return Ok(true);
+        }
+        if Instant::now() >= deadline {
+            return Ok(false);
+        }
+        if !waiting {
+            info!("⏳ Another instance is applying migrations, waiting for it");
+            waiting = true;
+        }
+        tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+    }
+}
+
+async fn migrate_locked(conn: &mut PgConnection) -> Result<(), MigrateError> {
+    conn.ensure_migrations_table().await?;
+    let applied = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect::<HashSet<_>>();
+    let pending = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration() && !applied.contains(&migration.version))
+        .count();
+
+    // run_direct (lo que usa run() por dentro) también valida checksums y
+    // migraciones aplicadas a medias; run() no compila con una conexión
+    // prestada dentro de un future Send
+    MIGRATOR.run_direct(conn).await?;
+    if pending == 0 {
+        info!("✅ Database schema is up to date, no migrations to apply");
+    } else {
+        info!("✅ Applied {} pending migrations", pending);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Connection, PgConnection, PgPool};
+    use std::time::Duration;
+    use super::{LOCK_KEY, MIGRATOR};
+
+    async fn lock_is_free(pool: &PgPool) -> bool {
+        let mut conn = PgConnection::connect_with(&pool.connect_options()).await.unwrap();
+        sqlx::query_scalar("SELECT pg_try_advisory_lock($1)").bind(LOCK_KEY).fetch_one(&mut conn).await.unwrap()
+    }
+
+    async fn applied(pool: &PgPool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations WHERE success").fetch_one(pool).await.unwrap()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn concurrent_migrators_both_succeed_and_apply_everything_once(pool: PgPool) {
+        let (first, second) = tokio::join!(
+            super::run(&pool, Duration::from_secs(30)),
+            super::run(&pool, Duration::from_secs(30)),
+        );
+
+        assert!(first.unwrap() && second.unwrap());
+        assert_eq!(applied(&pool).await, MIGRATOR.iter().count() as i64);
+        assert!(lock_is_free(&pool).await);
+        // Y una tercera instancia no encuentra nada pendiente
+        assert!(super::run(&pool, Duration::ZERO).await.unwrap());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn gives_up_while_another_session_holds_the_lock(pool: PgPool) {
+        let mut holder = PgConnection::connect_with(&pool.connect_options()).await.unwrap();
+        sqlx::query("SELECT pg_advisory_lock($1)").bind(LOCK_KEY).execute(&mut holder).await.unwrap();
+
+        assert!(!super::run(&pool, Duration::from_millis(600)).await.unwrap());
+        let table: Option<String> = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations')::text").fetch_one(&pool).await.unwrap();
+        assert_eq!(table, None);
+
+        holder.close().await.unwrap();
+        assert!(super::run(&pool, Duration::from_secs(5)).await.unwrap());
+        assert!(lock_is_free(&pool).await);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn releases_the_lock_when_dropped_while_holding_it(pool: PgPool) {
+        use sqlx::migrate::Migrate;
+
+        // La migración toma el lock y se queda esperando por la tabla
+        let mut holder = PgConnection::connect_with(&pool.connect_options()).await.unwrap();
+        holder.ensure_migrations_table().await.unwrap();
+        let mut tx = holder.begin().await.unwrap();
+        sqlx::query("LOCK TABLE _sqlx_migrations IN ACCESS EXCLUSIVE MODE").execute(&mut *tx).await.unwrap();
+
+        let migration = tokio::spawn({
+            let pool = pool.clone();
+            async move { super::run(&pool, Duration::from_secs(30)).await }
+        });
+        let held = "SELECT EXISTS (
+            SELECT 1 FROM pg_locks
+            WHERE locktype = 'advisory' AND granted
+              AND database = (SELECT oid FROM pg_database WHERE datname = current_database())
+        )";
+        let mut waited = 0;
+        while !sqlx::query_scalar::<_, bool>(held).fetch_one(&pool).await.unwrap() {
+            assert!(waited < 100, "the migration never took the lock");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            waited += 1;
+        }
+        migration.abort();
+        assert!(migration.await.unwrap_err().is_cancelled());
+        tx.rollback().await.unwrap();
+
+        // La sesión abandonada termina en cuanto deja de esperar
+        let mut free = false;
+        for _ in 0..100 {
+            free = lock_is_free(&pool).await;
+            if free {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(free);
+    }
+}
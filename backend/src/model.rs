@@ -1,30 +1,48 @@
-use serde::{Serialize, Deserialize};
-use uuid::Uuid;
-use utoipa::ToSchema;
-use sqlx::{PgPool, FromRow};
-use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use crate::config::Config;
+use crate::features::{FeatureSource, StaticFeatures};
+use crate::clock::{Clock, SystemClock};
+use crate::repository::PgTodoRepository;
+use crate::service::TodoService;
+use crate::changes::ChangeFeed;
+use std::sync::atomic::AtomicBool;
+use tokio::sync::Notify;
 
 pub struct AppState {
     pub db: PgPool,
+    pub config: Config,
+    /// Set once the migrations have been applied; until then the service is not ready.
+    pub migrations_done: AtomicBool,
+    /// Set by the admin shutdown endpoint; readiness fails and new requests get 503.
+    pub draining: AtomicBool,
+    /// Set once a shutdown has started or been scheduled; undrain is refused
+    /// and background tasks stop.
+    pub shutting_down: AtomicBool,
+    /// Notified when the admin endpoint asks the server to exit.
+    pub shutdown: Notify,
+    pub features: Box<dyn FeatureSource>,
+    pub clock: Box<dyn Clock>,
+    pub todos: TodoService<PgTodoRepository>,
+    pub changes: ChangeFeed,
 }
 
-#[derive(Serialize, Deserialize, Clone, ToSchema, FromRow)]
-#[schema(example = json!({
-    "id": "550e8400-e29b-41d4-a716-446655440000",
-    "title": "Buy groceries",
-    "completed": false,
-    "created_at": "2023-01-01T00:00:00Z",
-    "updated_at": "2023-01-01T00:00:00Z"
-}))]
-pub struct Todo {
-    #[schema(example = "550e8400-e29b-41d4-a716-446655440000")]
-    pub id: Uuid,
-    #[schema(example = "Buy groceries")]
-    pub title: String,
-    #[schema(example = false)]
-    pub completed: bool,
-    #[schema(example = "2023-01-01T00:00:00Z")]
-    pub created_at: DateTime<Utc>,
-    #[schema(example = "2023-01-01T00:00:00Z")]
-    pub updated_at: DateTime<Utc>,
-}
\ No newline at end of file
+impl AppState {
+    /// State for a service on `db`, with the flags from the configuration and
+    /// the system clock; `migrations_done` tells whether it starts ready.
+    pub fn new(db: PgPool, config: Config, migrations_done: bool) -> Self {
+        AppState {
+            db: db.clone(),
+            migrations_done: AtomicBool::new(migrations_done),
+            draining: AtomicBool::new(false),
+            shutting_down: AtomicBool::new(false),
+            shutdown: Notify::new(),
+            features: Box::new(StaticFeatures::new(&config.features)),
+            clock: Box::new(SystemClock),
+            todos: TodoService::new(PgTodoRepository::new(db), config.clone()),
+            changes: ChangeFeed::new(config.changes_max_waiters),
+            config,
+        }
+    }
+}
+
+pub use crate::api::Todo;
@@ -0,0 +1,321 @@
+//! Parser for human-friendly due dates such as "tomorrow", "next friday",
+//! "in 3 days" or "may 15 5pm".
+//!
+//! Phrases are resolved in the caller's timezone:
+//! - a date without a time means midnight of that day;
+//! - a bare weekday ("friday", "this friday") is the next such day after today,
+//!   while "next friday" is the Friday of the following Monday-based week;
+//! - a month and day without a year is its next occurrence (today included);
+//! - a time without a date is its next occurrence (today or tomorrow);
+//! - "in N days/weeks/months" keeps the current wall-clock time, so it stays
+//!   stable across DST changes, whereas minutes and hours are exact durations.
+//!
+//! Local times repeated by a DST change resolve to the earlier instant; local
+//! times skipped by one are rejected.
+
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+
+/// Resolves `input` relative to `now` in `tz`, returning the concrete instant.
+pub fn parse(input: &str, now: DateTime<Utc>, tz: Tz) -> Result<DateTime<Utc>, String> {
+    let tokens = tokenize(input);
+    let tokens = tokens.iter().map(String::as_str).collect::<Vec<_>>();
+    let tokens = match tokens.as_slice() {
+        ["on" | "due" | "at", rest @ ..] => rest,
+        all => all,
+    };
+    let local_now = now.with_timezone(&tz).naive_local();
+
+    match tokens {
+        [] => Err("empty phrase".to_string()),
+        ["now"] => Ok(now),
+        ["in", rest @ ..] => parse_relative(rest, now, local_now, tz),
+        _ => {
+            let (date, rest) = match parse_date(tokens, local_now.date()) {
+                Some((date, rest)) => (Some(date), rest),
+                None => (None, tokens),
+            };
+            let time = match rest {
+                [] => None,
+                [time] | ["at", time] => {
+                    let what = if date.is_some() { "time" } else { "phrase" };
+                    Some(parse_time(time).ok_or_else(|| format!("unrecognized {} {:?}", what, time))?)
+                }
+                _ => return Err(format!("unrecognized words {:?}", rest.join(" "))),
+            };
+
+            let local = match (date, time) {
+                (Some(date), time) => date.and_time(time.unwrap_or(NaiveTime::MIN)),
+                (None, Some(time)) => {
+                    let today = local_now.date().and_time(time);
+                    if today > local_now { today } else { today + Duration::days(1) }
+                }
+                (None, None) => return Err("no date or time found".to_string()),
+            };
+            to_utc(local, tz)
+        }
+    }
+}
+
+/// Lowercases, drops commas and joins "5 pm" into "5pm".
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    for word in input.to_lowercase().replace(',', " ").split_whitespace() {
+        match tokens.last_mut() {
+            Some(last) if matches!(word, "am" | "pm") && last.chars().all(|c| c.is_ascii_digit() || c == ':') => {
+                last.push_str(word);
+            }
+            _ => tokens.push(word.to_string()),
+        }
+    }
+    tokens
+}
+
+fn parse_relative(
+    tokens: &[&str],
+    now: DateTime<Utc>,
+    local_now: NaiveDateTime,
+    tz: Tz,
+) -> Result<DateTime<Utc>, String> {
+    let [amount, unit] = tokens else {
+        return Err("expected \"in <number> <unit>\"".to_string());
+    };
+    let amount: u32 = match *amount {
+        "a" | "an" => 1,
+        number => number.parse().map_err(|_| format!("invalid amount {:?}", number))?,
+    };
+
+    let out_of_range = || "amount is out of range".to_string();
+    match unit.trim_end_matches('s') {
+        "minute" | "min" => now.checked_add_signed(Duration::minutes(amount.into())).ok_or_else(out_of_range),
+        "hour" | "hr" => now.checked_add_signed(Duration::hours(amount.into())).ok_or_else(out_of_range),
+        "day" => to_utc(local_now.checked_add_signed(Duration::days(amount.into())).ok_or_else(out_of_range)?, tz),
+        "week" => to_utc(local_now.checked_add_signed(Duration::weeks(amount.into())).ok_or_else(out_of_range)?, tz),
+        "month" => to_utc(local_now.checked_add_months(Months::new(amount)).ok_or_else(out_of_range)?, tz),
+        _ => Err(format!("unknown unit {:?}", unit)),
+    }
+}
+
+/// Matches a date at the start of `tokens`, returning it with the remaining tokens.
+fn parse_date<'a, 'b>(tokens: &'a [&'b str], today: NaiveDate) -> Option<(NaiveDate, &'a [&'b str])> {
+    match tokens {
+        ["today" | "tonight", rest @ ..] => Some((today, rest)),
+        ["tomorrow", rest @ ..] => Some((today.succ_opt()?, rest)),
+        ["next", "week", rest @ ..] => Some((start_of_next_week(today), rest)),
+        ["next", day, rest @ ..] => {
+            let weekday = parse_weekday(day)?;
+            let date = start_of_next_week(today) + Duration::days(weekday.num_days_from_monday().into());
+            Some((date, rest))
+        }
+        ["this", day, rest @ ..] => Some((next_weekday(today, parse_weekday(day)?), rest)),
+        [first, second, rest @ ..] if parse_month(first).is_some() || parse_month(second).is_some() => {
+            let (month, day) = match (parse_month(first), parse_month(second)) {
+                (Some(month), _) => (month, parse_day(second)?),
+                (None, Some(month)) => (month, parse_day(first)?),
+                (None, None) => return None,
+            };
+            match rest {
+                [year, rest @ ..] if year.len() == 4 && year.parse::<i32>().is_ok() => {
+                    Some((NaiveDate::from_ymd_opt(year.parse().ok()?, month, day)?, rest))
+                }
+                _ => {
+                    let this_year = NaiveDate::from_ymd_opt(today.year(), month, day);
+                    let date = match this_year {
+                        Some(date) if date >= today => date,
+                        _ => NaiveDate::from_ymd_opt(today.year() + 1, month, day)?,
+                    };
+                    Some((date, rest))
+                }
+            }
+        }
+        [day, rest @ ..] => match parse_weekday(day) {
+            Some(weekday) => Some((next_weekday(today, weekday), rest)),
+            None => Some((NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()?, rest)),
+        },
+        [] => None,
+    }
+}
+
+fn start_of_next_week(today: NaiveDate) -> NaiveDate {
+    today + Duration::days(7 - i64::from(today.weekday().num_days_from_monday()))
+}
+
+/// The next `weekday` strictly after `today`.
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    today + Duration::days(if ahead == 0 { 7 } else { ahead.into() })
+}
+
+fn parse_weekday(token: &str) -> Option<Weekday> {
+    match token {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thur" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_month(token: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "january", "february", "march", "april", "may", "june",
+        "july", "august", "september", "october", "november", "december",
+    ];
+    MONTHS
+        .iter()
+        .position(|month| token == *month || (token.len() >= 3 && month.starts_with(token)))
+        .map(|index| index as u32 + 1)
+}
+
+/// Day of month, with an optional ordinal suffix ("15", "15th", "1st").
+fn parse_day(token: &str) -> Option<u32> {
+    let digits = token.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let suffix = &token[digits.len()..];
+    if !matches!(suffix, "" | "st" | "nd" | "rd" | "th") {
+        return None;
+    }
+    digits.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+/// "noon", "midnight", "5pm", "5:30am" or 24-hour "17:00".
+fn parse_time(token: &str) -> Option<NaiveTime> {
+    match token {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return Some(NaiveTime::MIN),
+        _ => {}
+    }
+
+    let (clock, meridiem) = match (token.strip_suffix("am"), token.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, Some(false)),
+        (_, Some(clock)) => (clock, Some(true)),
+        _ => (token, None),
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        Some(_) => return None,
+        None if meridiem.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+
+    let hour = match meridiem {
+        None => hour,
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(false) => hour % 12,
+        Some(true) => hour % 12 + 12,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn to_utc(local: NaiveDateTime, tz: Tz) -> Result<DateTime<Utc>, String> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|date_time| date_time.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} does not exist in {} (skipped by a DST change)", local, tz))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use chrono_tz::{Europe::Madrid, Tz};
+
+    fn at(instant: &str) -> DateTime<Utc> {
+        instant.parse().unwrap()
+    }
+
+    fn parse(input: &str, now: &str, tz: Tz) -> Result<String, String> {
+        super::parse(input, at(now), tz).map(|instant| instant.to_rfc3339())
+    }
+
+    // En 2025 Madrid pasa de CET (+01) a CEST (+02) el 30 de marzo a las 02:00
+    // y vuelve el 26 de octubre a las 03:00
+    const BEFORE_SPRING: &str = "2025-03-29T12:00:00Z";
+
+    #[test]
+    fn dates_without_a_time_are_local_midnight() {
+        assert_eq!(parse("tomorrow", BEFORE_SPRING, Madrid).unwrap(), "2025-03-29T23:00:00+00:00");
+        assert_eq!(parse("2025-03-31", BEFORE_SPRING, Madrid).unwrap(), "2025-03-30T22:00:00+00:00");
+        assert_eq!(parse("today", BEFORE_SPRING, Tz::UTC).unwrap(), "2025-03-29T00:00:00+00:00");
+    }
+
+    #[test]
+    fn days_keep_the_wall_clock_time_across_dst() {
+        // 13:00 CET un día más tarde son las 13:00 CEST: solo 23 horas
+        assert_eq!(parse("in 1 day", BEFORE_SPRING, Madrid).unwrap(), "2025-03-30T11:00:00+00:00");
+        assert_eq!(parse("in 1 week", "2025-10-20T10:00:00Z", Madrid).unwrap(), "2025-10-27T11:00:00+00:00");
+        assert_eq!(parse("in 1 month", "2025-01-31T10:00:00Z", Madrid).unwrap(), "2025-02-28T10:00:00+00:00");
+    }
+
+    #[test]
+    fn hours_and_minutes_are_exact_durations_across_dst() {
+        assert_eq!(parse("in 24 hours", BEFORE_SPRING, Madrid).unwrap(), "2025-03-30T12:00:00+00:00");
+        assert_eq!(parse("in 2 hours", "2025-03-30T00:30:00Z", Madrid).unwrap(), "2025-03-30T02:30:00+00:00");
+        assert_eq!(parse("in 90 minutes", "2025-10-26T00:30:00Z", Madrid).unwrap(), "2025-10-26T02:00:00+00:00");
+    }
+
+    #[test]
+    fn times_skipped_by_dst_are_rejected() {
+        // Las 02:30 del 30 de marzo no existen en Madrid
+        let error = parse("2:30am", "2025-03-29T23:30:00Z", Madrid).unwrap_err();
+        assert!(error.contains("skipped by a DST change"), "{}", error);
+        assert_eq!(parse("3:30am", "2025-03-29T23:30:00Z", Madrid).unwrap(), "2025-03-30T01:30:00+00:00");
+    }
+
+    #[test]
+    fn times_repeated_by_dst_resolve_to_the_earlier_instant() {
+        // Las 02:30 del 26 de octubre ocurren dos veces; la primera es en CEST
+        assert_eq!(parse("2:30am", "2025-10-25T22:30:00Z", Madrid).unwrap(), "2025-10-26T00:30:00+00:00");
+    }
+
+    #[test]
+    fn times_without_a_date_are_their_next_occurrence() {
+        // 13:00 en Madrid: las 17:00 son hoy, las 9:00 ya han pasado
+        assert_eq!(parse("5pm", BEFORE_SPRING, Madrid).unwrap(), "2025-03-29T16:00:00+00:00");
+        assert_eq!(parse("at 9:00", BEFORE_SPRING, Madrid).unwrap(), "2025-03-30T07:00:00+00:00");
+        assert_eq!(parse("noon", BEFORE_SPRING, Madrid).unwrap(), "2025-03-30T10:00:00+00:00");
+    }
+
+    #[test]
+    fn weekdays() {
+        // 2025-03-26 es miércoles
+        let wednesday = "2025-03-26T10:00:00Z";
+        assert_eq!(parse("friday", wednesday, Tz::UTC).unwrap(), "2025-03-28T00:00:00+00:00");
+        assert_eq!(parse("this friday", wednesday, Tz::UTC).unwrap(), "2025-03-28T00:00:00+00:00");
+        assert_eq!(parse("next friday", wednesday, Tz::UTC).unwrap(), "2025-04-04T00:00:00+00:00");
+        assert_eq!(parse("wednesday", wednesday, Tz::UTC).unwrap(), "2025-04-02T00:00:00+00:00");
+        assert_eq!(parse("next week", wednesday, Tz::UTC).unwrap(), "2025-03-31T00:00:00+00:00");
+    }
+
+    #[test]
+    fn month_and_day() {
+        let now = "2025-06-10T10:00:00Z";
+        assert_eq!(parse("may 15 5pm", now, Madrid).unwrap(), "2026-05-15T15:00:00+00:00");
+        assert_eq!(parse("15th june", now, Tz::UTC).unwrap(), "2025-06-15T00:00:00+00:00");
+        assert_eq!(parse("june 10", now, Tz::UTC).unwrap(), "2025-06-10T00:00:00+00:00");
+        assert_eq!(parse("feb 29 2028", now, Tz::UTC).unwrap(), "2028-02-29T00:00:00+00:00");
+        assert!(parse("feb 30 2028", now, Tz::UTC).is_err());
+    }
+
+    #[test]
+    fn huge_amounts_are_out_of_range_instead_of_panicking() {
+        for unit in ["minutes", "hours", "days", "weeks", "months"] {
+            let input = format!("in {} {}", u32::MAX, unit);
+            match parse(&input, BEFORE_SPRING, Madrid) {
+                Ok(_) => assert_eq!(unit, "minutes", "{} should be out of range", input),
+                Err(error) => assert_eq!(error, "amount is out of range"),
+            }
+        }
+        // Cerca del final del calendario hasta una hora es demasiado
+        let error = parse("in 1 hour", "+262142-12-31T23:30:00Z", Tz::UTC).unwrap_err();
+        assert_eq!(error, "amount is out of range");
+    }
+
+    #[test]
+    fn rejects_unknown_phrases() {
+        for input in ["", "someday", "in 3 fortnights", "in many days", "tomorrow at teatime", "25:00"] {
+            assert!(parse(input, BEFORE_SPRING, Madrid).is_err(), "{:?}", input);
+        }
+    }
+}
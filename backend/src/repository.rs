@@ -0,0 +1,356 @@
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Row};
+use std::future::Future;
+use uuid::Uuid;
+use crate::{model::Todo, filter::{self, Expr}, retry};
+
+/// The writable fields of a todo, already validated.
+pub struct TodoFields {
+    pub title: String,
+    pub description: Option<String>,
+    pub completed: bool,
+    pub due_date: Option<DateTime<Utc>>,
+}
+
+/// Storage for todos. Only persistence lives here; the rules about what may
+/// be stored belong to [`crate::service::TodoService`]. Reads and updates
+/// retry transient errors (see [`crate::retry`]); inserts, upserts, deletes
+/// and conditional updates do not.
+pub trait TodoRepository: Send + Sync {
+    /// Newest first, optionally restricted by a `filter` expression.
+    fn list(&self, filter: Option<&Expr>, limit: i64, offset: i64) -> impl Future<Output = Result<Vec<Todo>, sqlx::Error>> + Send;
+    fn get(&self, id: Uuid) -> impl Future<Output = Result<Option<Todo>, sqlx::Error>> + Send;
+    fn insert(&self, fields: &TodoFields) -> impl Future<Output = Result<Todo, sqlx::Error>> + Send;
+    /// Inserts all of `fields` or none of them, in order.
+    fn insert_many(&self, fields: &[TodoFields]) -> impl Future<Output = Result<Vec<Todo>, sqlx::Error>> + Send;
+    /// Returns `None` when no todo has this id.
+    fn update(&self, id: Uuid, fields: &TodoFields) -> impl Future<Output = Result<Option<Todo>, sqlx::Error>> + Send;
+    /// Like [`TodoRepository::update`], but only while the todo still has the
+    /// `updated_at` it was read with; `None` also when it has changed since.
+    fn update_if_unchanged(
+        &self,
+        id: Uuid,
+        updated_at: DateTime<Utc>,
+        fields: &TodoFields,
+    ) -> impl Future<Output = Result<Option<Todo>, sqlx::Error>> + Send;
+    /// Updates the todo with this id, or inserts it with this id when there
+    /// is none, in one statement; the flag tells whether it was inserted.
+    fn upsert(&self, id: Uuid, fields: &TodoFields) -> impl Future<Output = Result<(Todo, bool), sqlx::Error>> + Send;
+    /// Returns whether a todo was deleted.
+    fn delete(&self, id: Uuid) -> impl Future<Output = Result<bool, sqlx::Error>> + Send;
+}
+
+const INSERT_TODO: &str = r#"
+    INSERT INTO todos (title, description, completed, due_date)
+    VALUES ($1, $2, $3, $4)
+    RETURNING id, title, description, completed, due_date, created_at, updated_at
+"#;
+
+pub struct PgTodoRepository {
+    db: PgPool,
+}
+
+impl PgTodoRepository {
+    pub fn new(db: PgPool) -> Self {
+        PgTodoRepository { db }
+    }
+}
+
+impl TodoRepository for PgTodoRepository {
+    async fn list(&self, filter: Option<&Expr>, limit: i64, offset: i64) -> Result<Vec<Todo>, sqlx::Error> {
+        retry::idempotent("list todos", || async {
+            let mut query = QueryBuilder::<Postgres>::new(
+                "SELECT id, title, description, completed, due_date, created_at, updated_at FROM todos",
+            );
+            if let Some(filter) = filter {
+                query.push(" WHERE ");
+                filter::push_sql(filter, &mut query);
+            }
+            query
+                .push(" ORDER BY created_at DESC LIMIT ")
+                .push_bind(limit)
+                .push(" OFFSET ")
+                .push_bind(offset);
+
+            query.build_query_as::<Todo>().fetch_all(&self.db).await
+        })
+        .await
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<Todo>, sqlx::Error> {
+        retry::idempotent("get todo", || {
+            sqlx::query_as::<_, Todo>(
+                r#"
+                SELECT id, title, description, completed, due_date, created_at, updated_at
+                FROM todos
+                WHERE id = $1
+                "#
+            )
+            .bind(id)
+            .fetch_optional(&self.db)
+        })
+        .await
+    }
+
+    async fn insert(&self, fields: &TodoFields) -> Result<Todo, sqlx::Error> {
+        sqlx::query_as::<_, Todo>(INSERT_TODO)
+            .bind(&fields.title)
+            .bind(&fields.description)
+            .bind(fields.completed)
+            .bind(fields.due_date)
+            .fetch_one(&self.db)
+            .await
+    }
+
+    async fn insert_many(&self, fields: &[TodoFields]) -> Result<Vec<Todo>, sqlx::Error> {
+        let mut tx = self.db.begin().await?;
+        let mut todos = Vec::with_capacity(fields.len());
+        for fields in fields {
+            let todo = sqlx::query_as::<_, Todo>(INSERT_TODO)
+                .bind(&fields.title)
+                .bind(&fields.description)
+                .bind(fields.completed)
+                .bind(fields.due_date)
+                .fetch_one(&mut *tx)
+                .await?;
+            todos.push(todo);
+        }
+        tx.commit().await?;
+        Ok(todos)
+    }
+
+    async fn update(&self, id: Uuid, fields: &TodoFields) -> Result<Option<Todo>, sqlx::Error> {
+        // Sin reintento: si el primer intento llegó a confirmarse, el segundo
+        // pisaría lo escrito entretanto y movería updated_at otra vez
+        sqlx::query_as::<_, Todo>(
+            r#"
+            UPDATE todos 
+            SET title = $1, description = $2, completed = $3, due_date = $4
+            WHERE id = $5
+            RETURNING id, title, description, completed, due_date, created_at, updated_at
+            "#
+        )
+        .bind(&fields.title)
+        .bind(&fields.description)
+        .bind(fields.completed)
+        .bind(fields.due_date)
+        .bind(id)
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    async fn update_if_unchanged(
+        &self,
+        id: Uuid,
+        updated_at: DateTime<Utc>,
+        fields: &TodoFields,
+    ) -> Result<Option<Todo>, sqlx::Error> {
+        // Sin reintento: si el primer intento llegó a confirmarse, el segundo
+        // vería otro updated_at e informaría de un conflicto que no hubo
+        sqlx::query_as::<_, Todo>(
+            r#"
+            UPDATE todos
+            SET title = $1, description = $2, completed = $3, due_date = $4
+            WHERE id = $5 AND updated_at = $6
+            RETURNING id, title, description, completed, due_date, created_at, updated_at
+            "#
+        )
+        .bind(&fields.title)
+        .bind(&fields.description)
+        .bind(fields.completed)
+        .bind(fields.due_date)
+        .bind(id)
+        .bind(updated_at)
+        .fetch_optional(&self.db)
+        .await
+    }
+
+    async fn upsert(&self, id: Uuid, fields: &TodoFields) -> Result<(Todo, bool), sqlx::Error> {
+        // ON CONFLICT hace que dos PUT concurrentes al mismo id nuevo terminen en
+        // una inserción y una actualización; xmax = 0 solo en la fila insertada
+        let row = sqlx::query(
+            r#"
+            INSERT INTO todos (id, title, description, completed, due_date)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (id) DO UPDATE
+            SET title = EXCLUDED.title, description = EXCLUDED.description,
+                completed = EXCLUDED.completed, due_date = EXCLUDED.due_date
+            RETURNING id, title, description, completed, due_date, created_at, updated_at,
+                (xmax = 0) AS inserted
+            "#
+        )
+        .bind(id)
+        .bind(&fields.title)
+        .bind(&fields.description)
+        .bind(fields.completed)
+        .bind(fields.due_date)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok((Todo::from_row(&row)?, row.try_get("inserted")?))
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM todos 
+            WHERE id = $1
+            "#
+        )
+        .bind(id)
+        .execute(&self.db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Todos kept in memory, for testing [`crate::service::TodoService`] without
+/// Postgres. Clones share the same todos. Filters are not evaluated.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct InMemoryTodoRepository(std::sync::Arc<std::sync::Mutex<InMemoryTodos>>);
+
+#[cfg(test)]
+#[derive(Default)]
+struct InMemoryTodos {
+    todos: Vec<Todo>,
+    /// Bumped by 1 ms on every write, so each one gets a distinct `updated_at`.
+    writes: i64,
+    /// How many of the next conditional updates find the todo already
+    /// rewritten by someone else.
+    interleaved_writes: usize,
+}
+
+#[cfg(test)]
+impl InMemoryTodos {
+    fn tick(&mut self) -> DateTime<Utc> {
+        self.writes += 1;
+        DateTime::UNIX_EPOCH + chrono::Duration::milliseconds(self.writes)
+    }
+
+    fn insert(&mut self, id: Uuid, fields: &TodoFields) -> Todo {
+        let now = self.tick();
+        let todo = Todo {
+            id,
+            title: fields.title.clone(),
+            description: fields.description.clone(),
+            completed: fields.completed,
+            due_date: fields.due_date,
+            created_at: now,
+            updated_at: now,
+        };
+        self.todos.push(todo.clone());
+        todo
+    }
+
+    fn update(&mut self, id: Uuid, fields: &TodoFields) -> Option<Todo> {
+        let now = self.tick();
+        let todo = self.todos.iter_mut().find(|todo| todo.id == id)?;
+        todo.title = fields.title.clone();
+        todo.description = fields.description.clone();
+        todo.completed = fields.completed;
+        todo.due_date = fields.due_date;
+        todo.updated_at = now;
+        Some(todo.clone())
+    }
+}
+
+#[cfg(test)]
+impl InMemoryTodoRepository {
+    /// Makes the next `count` conditional updates lose the race against a
+    /// concurrent write.
+    pub fn interleave_writes(&self, count: usize) {
+        self.0.lock().unwrap().interleaved_writes = count;
+    }
+
+    pub fn todos(&self) -> Vec<Todo> {
+        self.0.lock().unwrap().todos.clone()
+    }
+}
+
+#[cfg(test)]
+impl TodoRepository for InMemoryTodoRepository {
+    async fn list(&self, filter: Option<&Expr>, limit: i64, offset: i64) -> Result<Vec<Todo>, sqlx::Error> {
+        assert!(filter.is_none(), "the in-memory repository does not evaluate filters");
+        let todos = self.0.lock().unwrap().todos.clone();
+        Ok(todos.into_iter().rev().skip(offset as usize).take(limit as usize).collect())
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<Todo>, sqlx::Error> {
+        Ok(self.0.lock().unwrap().todos.iter().find(|todo| todo.id == id).cloned())
+    }
+
+    async fn insert(&self, fields: &TodoFields) -> Result<Todo, sqlx::Error> {
+        Ok(self.0.lock().unwrap().insert(Uuid::new_v4(), fields))
+    }
+
+    async fn insert_many(&self, fields: &[TodoFields]) -> Result<Vec<Todo>, sqlx::Error> {
+        let mut todos = self.0.lock().unwrap();
+        Ok(fields.iter().map(|fields| todos.insert(Uuid::new_v4(), fields)).collect())
+    }
+
+    async fn update(&self, id: Uuid, fields: &TodoFields) -> Result<Option<Todo>, sqlx::Error> {
+        Ok(self.0.lock().unwrap().update(id, fields))
+    }
+
+    async fn update_if_unchanged(
+        &self,
+        id: Uuid,
+        updated_at: DateTime<Utc>,
+        fields: &TodoFields,
+    ) -> Result<Option<Todo>, sqlx::Error> {
+        let mut todos = self.0.lock().unwrap();
+        if todos.interleaved_writes > 0 {
+            todos.interleaved_writes -= 1;
+            let now = todos.tick();
+            if let Some(todo) = todos.todos.iter_mut().find(|todo| todo.id == id) {
+                todo.updated_at = now;
+            }
+        }
+        match todos.todos.iter().find(|todo| todo.id == id) {
+            Some(todo) if todo.updated_at == updated_at => Ok(todos.update(id, fields)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn upsert(&self, id: Uuid, fields: &TodoFields) -> Result<(Todo, bool), sqlx::Error> {
+        let mut todos = self.0.lock().unwrap();
+        match todos.update(id, fields) {
+            Some(todo) => Ok((todo, false)),
+            None => Ok((todos.insert(id, fields), true)),
+        }
+    }
+
+    async fn delete(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let mut todos = self.0.lock().unwrap();
+        let before = todos.todos.len();
+        todos.todos.retain(|todo| todo.id != id);
+        Ok(todos.todos.len() < before)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics;
+    use sqlx::postgres::PgPoolOptions;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn updates_are_not_retried() {
+        // Nada escucha en el puerto 1: cada intento falla con un error transitorio
+        let db = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(200))
+            .connect_lazy("postgres://test@127.0.0.1:1/test")
+            .unwrap();
+        let fields = TodoFields { title: "Buy milk".to_string(), description: None, completed: false, due_date: None };
+
+        let Err(error) = PgTodoRepository::new(db).update(Uuid::new_v4(), &fields).await else {
+            panic!("the update reached a database");
+        };
+
+        assert!(retry::is_transient(&error), "{:?}", error);
+        assert_eq!(metrics::database_retries("update todo"), 0);
+    }
+}
@@ -1,17 +1,25 @@
-use serde::{Serialize, Deserialize};
 use utoipa::ToSchema;
 use crate::model::Todo;
+use crate::import::ImportReport;
+use crate::suggest::Suggestion;
+use crate::info::BuildInfo;
+use crate::archive::{ArchivedTodo, ArchiveRun};
+use crate::changes::Changes;
+use crate::grouped::TodoGroup;
+
+pub use crate::api::ApiResponse;
 
-#[derive(Serialize, Deserialize)]
-pub struct ApiResponse<T> {
-    pub status: String,
-    pub data: Option<T>,
-    pub error: Option<String>,
-}
 
 pub type ApiResponseTodo = ApiResponse<Todo>;
 pub type ApiResponseVecTodo = ApiResponse<Vec<Todo>>;
 pub type ApiResponseString = ApiResponse<String>;
+pub type ApiResponseImportReport = ApiResponse<ImportReport>;
+pub type ApiResponseVecSuggestion = ApiResponse<Vec<Suggestion>>;
+pub type ApiResponseBuildInfo = ApiResponse<BuildInfo>;
+pub type ApiResponseVecArchivedTodo = ApiResponse<Vec<ArchivedTodo>>;
+pub type ApiResponseArchiveRun = ApiResponse<ArchiveRun>;
+pub type ApiResponseChanges = ApiResponse<Changes>;
+pub type ApiResponseVecTodoGroup = ApiResponse<Vec<TodoGroup>>;
 
 impl ToSchema<'_> for ApiResponseTodo {
     fn schema() -> (&'static str, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>) {
@@ -100,20 +108,202 @@ impl ToSchema<'_> for ApiResponseString {
     }
 }
 
-impl<T: Serialize> ApiResponse<T> {
-    pub fn success(data: T) -> Self {
-        Self {
-            status: "success".to_string(),
-            data: Some(data),
-            error: None,
-        }
+impl ToSchema<'_> for ApiResponseImportReport {
+    fn schema() -> (&'static str, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>) {
+        use utoipa::openapi::*;
+        (
+            "ApiResponseImportReport",
+            ObjectBuilder::new()
+                .property(
+                    "status",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .example(Some(serde_json::json!("success")))
+                )
+                .property(
+                    "data",
+                    RefOr::Ref(Ref::from_schema_name("ImportReport"))
+                )
+                .property(
+                    "error",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .nullable(true)
+                )
+                .required("status")
+                .into(),
+        )
     }
+}
 
-    pub fn error(message: &str) -> Self {
-        Self {
-            status: "error".to_string(),
-            data: None,
-            error: Some(message.to_string()),
-        }
+impl ToSchema<'_> for ApiResponseVecSuggestion {
+    fn schema() -> (&'static str, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>) {
+        use utoipa::openapi::*;
+        (
+            "ApiResponseVecSuggestion",
+            ObjectBuilder::new()
+                .property(
+                    "status",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .example(Some(serde_json::json!("success")))
+                )
+                .property(
+                    "data",
+                    ArrayBuilder::new()
+                        .items(RefOr::Ref(Ref::from_schema_name("Suggestion")))
+                )
+                .property(
+                    "error",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .nullable(true)
+                )
+                .required("status")
+                .into(),
+        )
+    }
+}
+
+
+impl ToSchema<'_> for ApiResponseBuildInfo {
+    fn schema() -> (&'static str, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>) {
+        use utoipa::openapi::*;
+        (
+            "ApiResponseBuildInfo",
+            ObjectBuilder::new()
+                .property(
+                    "status",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .example(Some(serde_json::json!("success")))
+                )
+                .property(
+                    "data",
+                    RefOr::Ref(Ref::from_schema_name("BuildInfo"))
+                )
+                .property(
+                    "error",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .nullable(true)
+                )
+                .required("status")
+                .into(),
+        )
     }
-}
\ No newline at end of file
+}
+
+impl ToSchema<'_> for ApiResponseVecArchivedTodo {
+    fn schema() -> (&'static str, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>) {
+        use utoipa::openapi::*;
+        (
+            "ApiResponseVecArchivedTodo",
+            ObjectBuilder::new()
+                .property(
+                    "status",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .example(Some(serde_json::json!("success")))
+                )
+                .property(
+                    "data",
+                    ArrayBuilder::new()
+                        .items(RefOr::Ref(Ref::from_schema_name("ArchivedTodo")))
+                )
+                .property(
+                    "error",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .nullable(true)
+                )
+                .required("status")
+                .into(),
+        )
+    }
+}
+
+impl ToSchema<'_> for ApiResponseArchiveRun {
+    fn schema() -> (&'static str, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>) {
+        use utoipa::openapi::*;
+        (
+            "ApiResponseArchiveRun",
+            ObjectBuilder::new()
+                .property(
+                    "status",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .example(Some(serde_json::json!("success")))
+                )
+                .property(
+                    "data",
+                    RefOr::Ref(Ref::from_schema_name("ArchiveRun"))
+                )
+                .property(
+                    "error",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .nullable(true)
+                )
+                .required("status")
+                .into(),
+        )
+    }
+}
+
+impl ToSchema<'_> for ApiResponseChanges {
+    fn schema() -> (&'static str, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>) {
+        use utoipa::openapi::*;
+        (
+            "ApiResponseChanges",
+            ObjectBuilder::new()
+                .property(
+                    "status",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .example(Some(serde_json::json!("success")))
+                )
+                .property(
+                    "data",
+                    RefOr::Ref(Ref::from_schema_name("Changes"))
+                )
+                .property(
+                    "error",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .nullable(true)
+                )
+                .required("status")
+                .into(),
+        )
+    }
+}
+
+impl ToSchema<'_> for ApiResponseVecTodoGroup {
+    fn schema() -> (&'static str, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>) {
+        use utoipa::openapi::*;
+        (
+            "ApiResponseVecTodoGroup",
+            ObjectBuilder::new()
+                .property(
+                    "status",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .example(Some(serde_json::json!("success")))
+                )
+                .property(
+                    "data",
+                    ArrayBuilder::new()
+                        .items(RefOr::Ref(Ref::from_schema_name("TodoGroup")))
+                )
+                .property(
+                    "error",
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::String)
+                        .nullable(true)
+                )
+                .required("status")
+                .into(),
+        )
+    }
+}
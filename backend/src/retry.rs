@@ -0,0 +1,243 @@
+//! Retries of idempotent database operations on the errors a brief Postgres
+//! failover or restart produces, so they surface as a slower response rather
+//! than a 500.
+//!
+//! Only reads, and writes an idempotency key or a unique constraint keeps
+//! from applying twice, may go through [`idempotent`]: a write that failed
+//! with a connection error may still have been committed, and running it
+//! again could overwrite a later write, record a second change and move
+//! `updated_at`. Plain inserts, updates and deletes are never retried.
+
+use std::{future::Future, io::ErrorKind, time::Duration};
+use tokio::time::Instant;
+use tracing::warn;
+use crate::metrics;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(50);
+const MAX_BACKOFF: Duration = Duration::from_millis(800);
+/// Total time spent on one operation, retries included, beyond which the
+/// last error is returned.
+const BUDGET: Duration = Duration::from_secs(3);
+
+/// Errors worth trying again: lost connections, an exhausted pool, and the
+/// SQLSTATEs Postgres uses for serialization failures, deadlocks and
+/// shutdowns.
+pub fn is_transient(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Io(error) => matches!(
+            error.kind(),
+            ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::ConnectionRefused
+                | ErrorKind::BrokenPipe
+                | ErrorKind::UnexpectedEof
+                | ErrorKind::TimedOut
+        ),
+        sqlx::Error::PoolTimedOut => true,
+        sqlx::Error::Database(error) => error.code().is_some_and(|code| {
+            // 08xxx: excepciones de conexión
+            code.starts_with("08")
+                || matches!(
+                    code.as_ref(),
+                    // serialization_failure, deadlock_detected
+                    "40001" | "40P01"
+                    // admin_shutdown, crash_shutdown, cannot_connect_now
+                    | "57P01" | "57P02" | "57P03"
+                )
+        }),
+        _ => false,
+    }
+}
+
+/// Runs `operation`, retrying transient errors with capped exponential
+/// backoff while the time budget allows. Each retry is logged and counted
+/// under `name` in the metrics.
+pub async fn idempotent<T, F, Fut>(name: &'static str, mut operation: F) -> Result<T, sqlx::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let started = Instant::now();
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(error) if is_transient(&error) && started.elapsed() + backoff < BUDGET => {
+                warn!(
+                    "🔁 Transient database error in {} (attempt {}), retrying in {:?}: {}",
+                    name, attempt, backoff, error
+                );
+                metrics::record_database_retry(name);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{error::{DatabaseError, ErrorKind as DatabaseErrorKind}, PgPool};
+    use std::{
+        borrow::Cow,
+        io::{self, ErrorKind},
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+    use tokio::time::Instant;
+    use super::{idempotent, is_transient, BUDGET};
+    use crate::metrics;
+
+    /// An error from the server with only a SQLSTATE.
+    #[derive(Debug)]
+    struct Sqlstate(&'static str);
+
+    impl std::fmt::Display for Sqlstate {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "error with SQLSTATE {}", self.0)
+        }
+    }
+
+    impl std::error::Error for Sqlstate {}
+
+    impl DatabaseError for Sqlstate {
+        fn message(&self) -> &str {
+            "simulated"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.0))
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> DatabaseErrorKind {
+            DatabaseErrorKind::Other
+        }
+    }
+
+    fn sqlstate(code: &'static str) -> sqlx::Error {
+        sqlx::Error::Database(Box::new(Sqlstate(code)))
+    }
+
+    fn io(kind: ErrorKind) -> sqlx::Error {
+        sqlx::Error::Io(io::Error::from(kind))
+    }
+
+    #[test]
+    fn classifies_sqlstates() {
+        for code in ["08000", "08003", "08006", "08001", "08004", "08P01", "40001", "40P01", "57P01", "57P02", "57P03"] {
+            assert!(is_transient(&sqlstate(code)), "{}", code);
+        }
+        // Violaciones de restricciones, sintaxis, cancelaciones y el resto de la clase 40 y 57
+        for code in ["23505", "23503", "42601", "42P01", "22001", "57014", "40002", "40003", "57000", "53300", "XX000"] {
+            assert!(!is_transient(&sqlstate(code)), "{}", code);
+        }
+    }
+
+    #[test]
+    fn classifies_io_and_driver_errors() {
+        for kind in [
+            ErrorKind::ConnectionReset,
+            ErrorKind::ConnectionAborted,
+            ErrorKind::ConnectionRefused,
+            ErrorKind::BrokenPipe,
+            ErrorKind::UnexpectedEof,
+            ErrorKind::TimedOut,
+        ] {
+            assert!(is_transient(&io(kind)), "{:?}", kind);
+        }
+        for kind in [ErrorKind::PermissionDenied, ErrorKind::InvalidData, ErrorKind::NotFound, ErrorKind::Other] {
+            assert!(!is_transient(&io(kind)), "{:?}", kind);
+        }
+
+        assert!(is_transient(&sqlx::Error::PoolTimedOut));
+        for error in [
+            sqlx::Error::RowNotFound,
+            sqlx::Error::PoolClosed,
+            sqlx::Error::ColumnNotFound("id".to_string()),
+            sqlx::Error::Protocol("unexpected message".to_string()),
+            sqlx::Error::Configuration("bad url".into()),
+        ] {
+            assert!(!is_transient(&error), "{}", error);
+        }
+    }
+
+    /// An operation that fails with `error` on its first `failures` attempts.
+    fn flaky(failures: u32, error: fn() -> sqlx::Error, attempts: &AtomicU32) -> impl FnMut() -> std::future::Ready<Result<u32, sqlx::Error>> + '_ {
+        move || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            std::future::ready(if attempt <= failures { Err(error()) } else { Ok(attempt) })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_transient_errors_with_a_capped_exponential_backoff() {
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+
+        let result = idempotent("flaky connection", flaky(5, || io(ErrorKind::ConnectionReset), &attempts)).await;
+
+        assert_eq!(result.unwrap(), 6);
+        // 50 + 100 + 200 + 400 + 800 ms
+        assert_eq!(started.elapsed(), Duration::from_millis(1550));
+        assert_eq!(metrics::database_retries("flaky connection"), 5);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_with_the_last_error_when_the_budget_runs_out() {
+        let attempts = AtomicU32::new(0);
+        let started = Instant::now();
+
+        let result = idempotent("failover", flaky(u32::MAX, || sqlstate("57P01"), &attempts)).await;
+
+        assert_eq!(result.unwrap_err().as_database_error().unwrap().code().unwrap(), "57P01");
+        assert!(started.elapsed() < BUDGET, "{:?}", started.elapsed());
+        // 50 + 100 + 200 + 400 + 800 + 800 ms; otros 800 pasarían del presupuesto
+        assert_eq!(started.elapsed(), Duration::from_millis(2350));
+        assert_eq!(attempts.load(Ordering::SeqCst), 7);
+        assert_eq!(metrics::database_retries("failover"), 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn returns_other_errors_and_successes_at_once() {
+        let attempts = AtomicU32::new(0);
+        let result = idempotent("unique violation", flaky(1, || sqlstate("23505"), &attempts)).await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        let attempts = AtomicU32::new(0);
+        assert_eq!(idempotent("healthy", flaky(0, || sqlx::Error::PoolTimedOut, &attempts)).await.unwrap(), 1);
+        assert_eq!(metrics::database_retries("unique violation") + metrics::database_retries("healthy"), 0);
+    }
+
+    #[sqlx::test]
+    async fn retries_serialization_failures_raised_by_postgres(db: PgPool) {
+        let attempts = AtomicU32::new(0);
+
+        let result = idempotent("serialization failure", || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                sqlx::query("DO $$ BEGIN RAISE EXCEPTION 'simulated' USING ERRCODE = 'serialization_failure'; END $$")
+                    .execute(&db)
+                    .await?;
+            }
+            sqlx::query_scalar::<_, i32>("SELECT 1").fetch_one(&db).await
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,13 +1,160 @@
-use axum::{routing::{get, post, put, delete}, Router};
-use crate::handler::{create_todo, get_todos, get_todo, update_todo, delete_todo};
+use axum::{
+    extract::{Request, State},
+    http::{Method, Uri},
+    middleware::{from_fn_with_state, Next},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post, put, patch, delete},
+    Router,
+};
+use crate::handler::{create_todo, get_todos, get_todo, get_todo_description_html, update_todo, patch_todo, delete_todo};
+use crate::export::export_todos_xlsx;
+use crate::import::{import_ics, import_google_tasks};
+use crate::archive::{list_archived, restore_archived};
+use crate::changes::get_changes;
+use crate::grouped::get_grouped;
+use crate::encoding::{require_content_type, JSON, JSON_OR_JSON_PATCH, JSON_OR_PLAIN_TEXT};
 use crate::model::AppState;
 use std::sync::Arc;
 
 pub fn app_routes() -> Router<Arc<AppState>> {
     Router::new()
-        .route("/", post(create_todo))
+        .route("/", post(create_todo).layer(from_fn_with_state(JSON_OR_PLAIN_TEXT, require_content_type)))
         .route("/", get(get_todos))
+        .route("/export.xlsx", get(export_todos_xlsx))
+        .route("/import.ics", post(import_ics))
+        .route("/import/google-tasks", post(import_google_tasks).layer(from_fn_with_state(JSON, require_content_type)))
+        .route("/archive", get(list_archived))
+        .route("/archive/:id/restore", post(restore_archived))
+        .route("/changes", get(get_changes))
+        .route("/grouped", get(get_grouped))
         .route("/:id", get(get_todo))
-        .route("/:id", put(update_todo))
+        .route("/:id", put(update_todo).layer(from_fn_with_state(JSON, require_content_type)))
+        .route("/:id", patch(patch_todo).layer(from_fn_with_state(JSON_OR_JSON_PATCH, require_content_type)))
         .route("/:id", delete(delete_todo))
-}
\ No newline at end of file
+        .route("/:id/description.html", get(get_todo_description_html))
+}
+
+/// Serves `/api/v1/todos/` like `/api/v1/todos`: GET and HEAD get a 308 to
+/// the path without trailing slashes, other methods are routed as if it were
+/// absent, since a redirect would make some clients resend them without the
+/// body. Swagger UI keeps its own `/swagger-ui/`.
+///
+/// Wraps the whole router, because middleware added with `Router::layer`
+/// only runs once the route has been chosen.
+pub async fn trim_trailing_slash(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    let swagger = format!("{}/swagger-ui/", state.config.base_path);
+    if path.len() <= 1 || !path.ends_with('/') || path.starts_with(&swagger) {
+        return next.run(request).await;
+    }
+
+    let trimmed = match path.trim_end_matches('/') {
+        "" => "/",
+        trimmed => trimmed,
+    };
+    let target = match request.uri().query() {
+        Some(query) => format!("{}?{}", trimmed, query),
+        None => trimmed.to_string(),
+    };
+    if matches!(*request.method(), Method::GET | Method::HEAD) {
+        return Redirect::permanent(&target).into_response();
+    }
+    if let Ok(uri) = target.parse::<Uri>() {
+        *request.uri_mut() = uri;
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{header::LOCATION, Method, StatusCode},
+    };
+    use serde_json::json;
+    use sqlx::PgPool;
+    use crate::testing;
+
+    fn send_as(method: Method, uri: &str) -> axum::extract::Request {
+        let body = match method {
+            Method::POST => json!({"title": "Buy milk"}),
+            Method::PUT => json!({"title": "Buy milk", "description": null, "completed": false, "due_date": null}),
+            Method::PATCH => json!({"completed": true}),
+            _ => return testing::request(method, uri, None, Body::empty()),
+        };
+        testing::json_request(method, uri, &body)
+    }
+
+    #[sqlx::test]
+    async fn both_slash_variants_reach_the_same_handler(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+
+        for (method, expected) in [
+            (Method::GET, StatusCode::OK),
+            (Method::HEAD, StatusCode::OK),
+            (Method::POST, StatusCode::CREATED),
+            (Method::PUT, StatusCode::METHOD_NOT_ALLOWED),
+            (Method::PATCH, StatusCode::METHOD_NOT_ALLOWED),
+            (Method::DELETE, StatusCode::METHOD_NOT_ALLOWED),
+        ] {
+            let response = testing::send(&state, send_as(method.clone(), "/api/v1/todos?limit=5")).await;
+            assert_eq!(response.status(), expected, "{} /api/v1/todos", method);
+
+            for slashed in ["/api/v1/todos/?limit=5", "/api/v1/todos//?limit=5"] {
+                let response = testing::send(&state, send_as(method.clone(), slashed)).await;
+                if matches!(method, Method::GET | Method::HEAD) {
+                    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT, "{} {}", method, slashed);
+                    assert_eq!(response.headers()[LOCATION], "/api/v1/todos?limit=5", "{} {}", method, slashed);
+                } else {
+                    assert_eq!(response.status(), expected, "{} {}", method, slashed);
+                }
+            }
+        }
+
+        let id = testing::insert_todo(&db, "Existing").await;
+        for (method, expected) in [
+            (Method::GET, StatusCode::OK),
+            (Method::HEAD, StatusCode::OK),
+            (Method::PUT, StatusCode::OK),
+            (Method::PATCH, StatusCode::OK),
+            (Method::POST, StatusCode::METHOD_NOT_ALLOWED),
+            (Method::DELETE, StatusCode::OK),
+        ] {
+            for uri in [format!("/api/v1/todos/{}", id), format!("/api/v1/todos/{}/", id)] {
+                let response = testing::send(&state, send_as(method.clone(), &uri)).await;
+                if uri.ends_with('/') && matches!(method, Method::GET | Method::HEAD) {
+                    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT, "{} {}", method, uri);
+                    assert_eq!(response.headers()[LOCATION], uri.trim_end_matches('/'), "{} {}", method, uri);
+                    continue;
+                }
+                let expected = match (&method, uri.ends_with('/')) {
+                    // El primer DELETE ya lo borró
+                    (&Method::DELETE, true) => StatusCode::NOT_FOUND,
+                    _ => expected,
+                };
+                assert_eq!(response.status(), expected, "{} {}", method, uri);
+            }
+        }
+
+        // Un todo por cada POST a la colección; el existente se borró
+        let created: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM todos").fetch_one(&db).await.unwrap();
+        assert_eq!(created, 3);
+    }
+
+    #[sqlx::test]
+    async fn swagger_ui_and_the_base_path_keep_working(db: PgPool) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        assert_eq!(testing::send(&state, testing::get("/swagger-ui/")).await.status(), StatusCode::OK);
+        let response = testing::send(&state, testing::get("/api-docs/openapi.json/")).await;
+        assert_eq!(response.headers()[LOCATION], "/api-docs/openapi.json");
+        assert_eq!(testing::send(&state, testing::get("/")).await.status(), StatusCode::NOT_FOUND);
+
+        let state = testing::state(db, testing::config(&[("BASE_PATH", "/todo")]));
+        assert_eq!(testing::send(&state, testing::get("/todo/swagger-ui/")).await.status(), StatusCode::OK);
+        let response = testing::send(&state, testing::get("/todo/api/v1/todos/")).await;
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[LOCATION], "/todo/api/v1/todos");
+        let response = testing::send(&state, send_as(Method::POST, "/todo/api/v1/todos/")).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+}
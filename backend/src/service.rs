@@ -0,0 +1,317 @@
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use uuid::Uuid;
+use validator::ValidationErrors;
+use crate::{
+    model::Todo,
+    config::Config,
+    error::AppError,
+    handler::field_error,
+    filter::Expr,
+    repository::{TodoFields, TodoRepository}
+};
+
+/// Reads of a todo [`TodoService::update_with`] makes before giving up on a
+/// todo that keeps being modified by other requests.
+const CONDITIONAL_UPDATE_ATTEMPTS: usize = 3;
+
+/// Business rules for todos, independent of HTTP and of the storage behind
+/// `R`. Handlers parse and validate the request shape, then call in here.
+pub struct TodoService<R> {
+    repo: R,
+    config: Config,
+}
+
+impl<R: TodoRepository> TodoService<R> {
+    pub fn new(repo: R, config: Config) -> Self {
+        TodoService { repo, config }
+    }
+
+    pub async fn list(&self, filter: Option<&Expr>, limit: i64, offset: i64) -> Result<Vec<Todo>, AppError> {
+        Ok(self.repo.list(filter, limit, offset).await?)
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<Todo>, AppError> {
+        Ok(self.repo.get(id).await?)
+    }
+
+    pub async fn create(&self, now: DateTime<Utc>, fields: TodoFields) -> Result<Todo, AppError> {
+        validate_due_date(&self.config, now, fields.due_date)?;
+        Ok(self.repo.insert(&fields).await?)
+    }
+
+    /// Creates every todo or, if one is rejected, none of them.
+    pub async fn create_many(&self, now: DateTime<Utc>, fields: Vec<TodoFields>) -> Result<Vec<Todo>, AppError> {
+        for fields in &fields {
+            validate_due_date(&self.config, now, fields.due_date)?;
+        }
+        Ok(self.repo.insert_many(&fields).await?)
+    }
+
+    /// Returns `None` when no todo has this id.
+    pub async fn update(&self, id: Uuid, now: DateTime<Utc>, fields: TodoFields) -> Result<Option<Todo>, AppError> {
+        if let Err(errors) = validate_due_date(&self.config, now, fields.due_date) {
+            // Un due_date ya vencido que no cambia no debe bloquear la actualización
+            let current = self.repo.get(id).await?.ok_or(AppError::NotFound)?;
+            if current.due_date != fields.due_date {
+                return Err(errors.into());
+            }
+        }
+        Ok(self.repo.update(id, &fields).await?)
+    }
+
+    /// Replaces the todo with this id, creating it when there is none; the
+    /// flag tells whether it was created. As in [`TodoService::update`], an
+    /// existing past due date that does not change is accepted.
+    pub async fn upsert(&self, id: Uuid, now: DateTime<Utc>, fields: TodoFields) -> Result<(Todo, bool), AppError> {
+        if let Err(errors) = validate_due_date(&self.config, now, fields.due_date) {
+            let current = self.repo.get(id).await?;
+            if current.is_none_or(|current| current.due_date != fields.due_date) {
+                return Err(errors.into());
+            }
+        }
+        Ok(self.repo.upsert(id, &fields).await?)
+    }
+
+    /// Builds the new fields from the current todo with `change` and stores
+    /// them only if the todo was not modified in between; on a concurrent
+    /// write the todo is read again and `change` re-run, so whatever `change`
+    /// checked against the todo still holds for what gets written. Returns
+    /// `None` when no todo has this id.
+    pub async fn update_with<F>(&self, id: Uuid, now: DateTime<Utc>, change: F) -> Result<Option<Todo>, AppError>
+    where
+        F: Fn(Todo) -> Result<TodoFields, AppError>,
+    {
+        for _ in 0..CONDITIONAL_UPDATE_ATTEMPTS {
+            let Some(current) = self.repo.get(id).await? else {
+                return Ok(None);
+            };
+            let (updated_at, current_due_date) = (current.updated_at, current.due_date);
+            let fields = change(current)?;
+            // Como en update, un due_date ya vencido que no cambia no se rechaza
+            if current_due_date != fields.due_date {
+                validate_due_date(&self.config, now, fields.due_date)?;
+            }
+            if let Some(todo) = self.repo.update_if_unchanged(id, updated_at, &fields).await? {
+                return Ok(Some(todo));
+            }
+        }
+        Err(AppError::Conflict(
+            "The todo kept changing while it was being updated, try again".to_string(),
+        ))
+    }
+
+    /// Returns whether a todo was deleted.
+    pub async fn delete(&self, id: Uuid) -> Result<bool, AppError> {
+        Ok(self.repo.delete(id).await?)
+    }
+}
+
+/// Rejects due dates earlier than `now` (minus the configured clock-skew
+/// tolerance) when `DUE_DATE_MUST_BE_FUTURE` is enabled.
+pub(crate) fn validate_due_date(
+    config: &Config,
+    now: DateTime<Utc>,
+    due_date: Option<DateTime<Utc>>,
+) -> Result<(), ValidationErrors> {
+    let Some(due_date) = due_date else {
+        return Ok(());
+    };
+    if !config.due_date_must_be_future {
+        return Ok(());
+    }
+
+    let min = now - Duration::seconds(config.due_date_skew_tolerance_secs);
+    if due_date >= min {
+        return Ok(());
+    }
+
+    Err(field_error(
+        "due_date",
+        "due_date_in_past",
+        format!(
+            "Due date {} is in the past, it must be at or after {}",
+            due_date.to_rfc3339_opts(SecondsFormat::Secs, true),
+            min.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use uuid::Uuid;
+    use crate::{
+        error::AppError,
+        repository::{InMemoryTodoRepository, TodoFields},
+        testing,
+    };
+    use super::TodoService;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2030, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    fn fields(title: &str, due_date: Option<DateTime<Utc>>) -> TodoFields {
+        TodoFields { title: title.to_string(), description: None, completed: false, due_date }
+    }
+
+    /// A service that rejects past due dates, with a minute of tolerance.
+    fn service() -> (TodoService<InMemoryTodoRepository>, InMemoryTodoRepository) {
+        let repo = InMemoryTodoRepository::default();
+        let config = testing::config(&[("DUE_DATE_MUST_BE_FUTURE", "true"), ("DUE_DATE_SKEW_TOLERANCE_SECS", "60")]);
+        (TodoService::new(repo.clone(), config), repo)
+    }
+
+    fn is_validation_error(result: Result<impl Sized, AppError>) -> bool {
+        matches!(result, Err(AppError::ValidationError(_)))
+    }
+
+    #[test]
+    fn the_skew_tolerance_is_inclusive_to_the_millisecond() {
+        let config = testing::config(&[("DUE_DATE_MUST_BE_FUTURE", "true"), ("DUE_DATE_SKEW_TOLERANCE_SECS", "60")]);
+        let min = now() - Duration::seconds(60);
+
+        assert!(super::validate_due_date(&config, now(), Some(min)).is_ok());
+        assert!(super::validate_due_date(&config, now(), None).is_ok());
+        let errors = super::validate_due_date(&config, now(), Some(min - Duration::milliseconds(1))).unwrap_err();
+        let error = &errors.field_errors()["due_date"][0];
+        assert_eq!(error.code, "due_date_in_past");
+        assert_eq!(
+            error.message.as_deref(),
+            Some("Due date 2030-01-01T11:58:59Z is in the past, it must be at or after 2030-01-01T11:59:00Z")
+        );
+
+        let strict = testing::config(&[("DUE_DATE_MUST_BE_FUTURE", "true"), ("DUE_DATE_SKEW_TOLERANCE_SECS", "0")]);
+        assert!(super::validate_due_date(&strict, now(), Some(now())).is_ok());
+        assert!(super::validate_due_date(&strict, now(), Some(now() - Duration::milliseconds(1))).is_err());
+        let lenient = testing::config(&[]);
+        assert!(super::validate_due_date(&lenient, now(), Some(DateTime::UNIX_EPOCH)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn updates_keep_a_due_date_that_fell_behind_the_tolerance() {
+        let (service, _) = service();
+        let min = now() - Duration::seconds(60);
+        let todo = service.create(now(), fields("Call", Some(min))).await.unwrap();
+        let past = Some(min - Duration::milliseconds(1));
+        assert!(is_validation_error(service.create(now(), fields("Call", past)).await));
+
+        // Un milisegundo después min ya quedó atrás, pero no cambia
+        let later = now() + Duration::milliseconds(1);
+        let updated = service.update(todo.id, later, fields("Call back", Some(min))).await.unwrap().unwrap();
+        assert_eq!(updated.due_date, Some(min));
+        let (_, created) = service.upsert(todo.id, later, fields("Call again", Some(min))).await.unwrap();
+        assert!(!created);
+        assert!(service.update_with(todo.id, later, |current| Ok(fields("Done", current.due_date))).await.is_ok());
+
+        assert!(is_validation_error(service.update(todo.id, later, fields("Call", past)).await));
+        assert!(is_validation_error(service.upsert(todo.id, later, fields("Call", past)).await));
+    }
+
+    #[tokio::test]
+    async fn create_rejects_past_due_dates_only_when_configured() {
+        let (service, repo) = service();
+        let todo = service.create(now(), fields("Future", Some(now() + Duration::days(1)))).await.unwrap();
+        assert_eq!(todo.title, "Future");
+        assert!(is_validation_error(service.create(now(), fields("Past", Some(now() - Duration::days(1)))).await));
+        assert_eq!(repo.todos().len(), 1);
+
+        let lenient = TodoService::new(InMemoryTodoRepository::default(), testing::config(&[]));
+        assert!(lenient.create(now(), fields("Past", Some(now() - Duration::days(1)))).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn create_many_stores_nothing_when_one_due_date_is_rejected() {
+        let (service, repo) = service();
+        let batch = vec![fields("Fine", None), fields("Past", Some(now() - Duration::days(1)))];
+        assert!(is_validation_error(service.create_many(now(), batch).await));
+        assert!(repo.todos().is_empty());
+
+        let todos = service.create_many(now(), vec![fields("One", None), fields("Two", None)]).await.unwrap();
+        assert_eq!(todos.iter().map(|todo| todo.title.as_str()).collect::<Vec<_>>(), ["One", "Two"]);
+    }
+
+    #[tokio::test]
+    async fn update_accepts_an_unchanged_past_due_date_but_not_a_new_one() {
+        let (service, _) = service();
+        let due_date = Some(now() + Duration::hours(1));
+        let todo = service.create(now(), fields("Call", due_date)).await.unwrap();
+
+        // Un día después el due_date ya venció, pero sigue siendo el mismo
+        let later = now() + Duration::days(1);
+        let updated = service.update(todo.id, later, fields("Call back", due_date)).await.unwrap().unwrap();
+        assert_eq!(updated.title, "Call back");
+
+        let moved = Some(now() + Duration::hours(2));
+        assert!(is_validation_error(service.update(todo.id, later, fields("Call", moved)).await));
+        assert!(matches!(service.update(Uuid::new_v4(), later, fields("Call", moved)).await, Err(AppError::NotFound)));
+        assert!(service.update(Uuid::new_v4(), later, fields("Call", None)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn upsert_creates_then_replaces() {
+        let (service, repo) = service();
+        let id = Uuid::new_v4();
+        let (todo, created) = service.upsert(id, now(), fields("New", None)).await.unwrap();
+        assert!(created);
+        assert_eq!(todo.id, id);
+
+        let (todo, created) = service.upsert(id, now(), fields("Replaced", None)).await.unwrap();
+        assert!(!created);
+        assert_eq!(todo.title, "Replaced");
+        assert_eq!(repo.todos().len(), 1);
+        assert!(is_validation_error(service.upsert(Uuid::new_v4(), now(), fields("Past", Some(now() - Duration::days(1)))).await));
+    }
+
+    #[tokio::test]
+    async fn update_with_re_reads_and_re_runs_the_change_after_a_concurrent_write() {
+        let (service, repo) = service();
+        let todo = service.create(now(), fields("Buy milk", None)).await.unwrap();
+        let runs = AtomicUsize::new(0);
+        let change = |current: crate::model::Todo| {
+            runs.fetch_add(1, Ordering::Relaxed);
+            Ok(TodoFields { completed: true, ..fields(&current.title, current.due_date) })
+        };
+
+        repo.interleave_writes(super::CONDITIONAL_UPDATE_ATTEMPTS - 1);
+        let updated = service.update_with(todo.id, now(), change).await.unwrap().unwrap();
+        assert!(updated.completed);
+        assert_eq!(runs.load(Ordering::Relaxed), super::CONDITIONAL_UPDATE_ATTEMPTS);
+
+        repo.interleave_writes(super::CONDITIONAL_UPDATE_ATTEMPTS);
+        assert!(matches!(service.update_with(todo.id, now(), change).await, Err(AppError::Conflict(_))));
+        assert!(service.update_with(Uuid::new_v4(), now(), change).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn update_with_validates_only_a_changed_due_date() {
+        let (service, _) = service();
+        let due_date = Some(now() + Duration::hours(1));
+        let todo = service.create(now(), fields("Call", due_date)).await.unwrap();
+        let later = now() + Duration::days(1);
+
+        let renamed = service.update_with(todo.id, later, |current| Ok(fields("Renamed", current.due_date))).await;
+        assert_eq!(renamed.unwrap().unwrap().title, "Renamed");
+        let moved = service.update_with(todo.id, later, |_| Ok(fields("Call", Some(later - Duration::hours(1))))).await;
+        assert!(is_validation_error(moved));
+        let rejected = service.update_with(todo.id, later, |_| Err::<TodoFields, _>(AppError::Conflict("no".to_string()))).await;
+        assert!(matches!(rejected, Err(AppError::Conflict(_))));
+    }
+
+    #[tokio::test]
+    async fn get_list_and_delete_go_through_the_repository() {
+        let (service, _) = service();
+        let first = service.create(now(), fields("First", None)).await.unwrap();
+        service.create(now(), fields("Second", None)).await.unwrap();
+
+        let titles = |todos: Vec<crate::model::Todo>| todos.into_iter().map(|todo| todo.title).collect::<Vec<_>>();
+        assert_eq!(titles(service.list(None, 10, 0).await.unwrap()), ["Second", "First"]);
+        assert_eq!(titles(service.list(None, 1, 1).await.unwrap()), ["First"]);
+        assert_eq!(service.get(first.id).await.unwrap().unwrap().title, "First");
+
+        assert!(service.delete(first.id).await.unwrap());
+        assert!(!service.delete(first.id).await.unwrap());
+        assert!(service.get(first.id).await.unwrap().is_none());
+    }
+}
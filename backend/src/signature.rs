@@ -0,0 +1,72 @@
+//! HMAC-SHA256 signing, shared by the inbound email webhook, the feature
+//! override header, the admin secret check and Sentry client ids.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// The HMAC-SHA256 under `key` of `parts`, one after the other.
+pub fn sign(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    mac(key, parts).finalize().into_bytes().into()
+}
+
+/// Whether `signature` is the hex HMAC-SHA256 under `key` of `parts`, one
+/// after the other. The comparison takes constant time.
+pub fn verify_hex(key: &[u8], parts: &[&[u8]], signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    mac(key, parts).verify_slice(&signature).is_ok()
+}
+
+/// Whether `provided` is `secret`, comparing their MACs under `secret` so
+/// that neither the contents nor the length leak through timing.
+pub fn secret_matches(secret: &str, provided: &str) -> bool {
+    let expected = mac(secret.as_bytes(), &[secret.as_bytes()]);
+    let provided = mac(secret.as_bytes(), &[provided.as_bytes()]);
+    expected.verify(&provided.finalize().into_bytes()).is_ok()
+}
+
+fn mac(key: &[u8], parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_like_rfc_4231() {
+        // Caso de prueba 2 del RFC 4231
+        let signature = sign(b"Jefe", &[b"what do ya want ", b"for nothing?"]);
+        assert_eq!(
+            hex::encode(signature),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn verifies_hex_signatures_of_the_concatenated_parts() {
+        let signature = hex::encode(sign(b"key", &[b"1700000000", b"token"]));
+        assert!(verify_hex(b"key", &[b"1700000000token"], &signature));
+        assert!(verify_hex(b"key", &[b"1700000000", b"token"], &signature.to_uppercase()));
+
+        assert!(!verify_hex(b"other", &[b"1700000000", b"token"], &signature));
+        assert!(!verify_hex(b"key", &[b"1700000001", b"token"], &signature));
+        assert!(!verify_hex(b"key", &[b"1700000000", b"token"], &signature[..62]));
+        assert!(!verify_hex(b"key", &[b"1700000000", b"token"], "not hex"));
+        assert!(!verify_hex(b"key", &[b"1700000000", b"token"], ""));
+    }
+
+    #[test]
+    fn matches_only_the_exact_secret() {
+        assert!(secret_matches("s3cret", "s3cret"));
+        assert!(!secret_matches("s3cret", "s3cre"));
+        assert!(!secret_matches("s3cret", "s3cret "));
+        assert!(!secret_matches("s3cret", ""));
+        assert!(secret_matches("", ""));
+    }
+}
@@ -0,0 +1,97 @@
+use std::{error::Error, fmt, io, process::ExitCode};
+
+/// Everything that can stop the server from starting, grouped by what the
+/// operator has to fix. Each category exits with its own code (from
+/// sysexits.h) so supervisors can tell them apart.
+#[derive(Debug)]
+pub enum StartupError {
+    Config(Box<dyn Error>),
+    Database(sqlx::Error),
+    Migration(sqlx::migrate::MigrateError),
+    Bind { address: String, source: io::Error },
+    AccessLog(io::Error),
+    Server(io::Error),
+}
+
+impl StartupError {
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            // EX_CONFIG
+            StartupError::Config(_) => 78,
+            // EX_UNAVAILABLE
+            StartupError::Database(_) => 69,
+            // EX_DATAERR
+            StartupError::Migration(_) => 65,
+            // EX_OSERR
+            StartupError::Bind { .. } => 71,
+            // EX_CANTCREAT
+            StartupError::AccessLog(_) => 73,
+            // EX_SOFTWARE
+            StartupError::Server(_) => 70,
+        })
+    }
+
+    /// Prints the error and its causes to stderr, without a backtrace.
+    pub fn report(&self) {
+        eprintln!("Error: {}", self);
+        let mut source = self.source();
+        while let Some(cause) = source {
+            eprintln!("  Caused by: {}", cause);
+            source = cause.source();
+        }
+    }
+}
+
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartupError::Config(_) => write!(
+                f,
+                "invalid configuration, check the environment variables or the .env file"
+            ),
+            StartupError::Database(_) => write!(
+                f,
+                "could not connect to PostgreSQL, check that the database is running and that DATABASE_URL has the right host, port and credentials"
+            ),
+            StartupError::Migration(_) => write!(
+                f,
+                "could not apply database migrations, check the migration that failed and the state of the _sqlx_migrations table"
+            ),
+            StartupError::Bind { address, .. } => write!(
+                f,
+                "could not listen on {}, check that SERVER_HOST/SERVER_PORT are correct and the port is not already in use",
+                address
+            ),
+            StartupError::AccessLog(_) => write!(
+                f,
+                "could not open the access log, check that ACCESS_LOG_PATH is writable"
+            ),
+            StartupError::Server(_) => write!(f, "the HTTP server stopped unexpectedly"),
+        }
+    }
+}
+
+impl Error for StartupError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StartupError::Config(e) => Some(e.as_ref()),
+            StartupError::Database(e) => Some(e),
+            StartupError::Migration(e) => Some(e),
+            StartupError::Bind { source, .. } => Some(source),
+            StartupError::AccessLog(e) => Some(e),
+            StartupError::Server(e) => Some(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for StartupError {
+    fn from(err: sqlx::Error) -> Self {
+        StartupError::Database(err)
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for StartupError {
+    fn from(err: sqlx::migrate::MigrateError) -> Self {
+        StartupError::Migration(err)
+    }
+}
@@ -0,0 +1,208 @@
+use axum::{
+    extract::{rejection::QueryRejection, Json, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+use crate::{
+    model::AppState,
+    response::ApiResponse,
+    error::AppError
+};
+
+const DEFAULT_LIMIT: u32 = 5;
+const MAX_LIMIT: u32 = 20;
+const MIN_QUERY_CHARS: usize = 2;
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+pub struct SuggestQuery {
+    #[schema(example = "gro")]
+    /// Prefix to complete (case-insensitive, at least 2 characters)
+    q: String,
+    #[schema(example = "title")]
+    /// What to suggest; only `title` is supported
+    kind: Option<String>,
+    #[schema(example = 5)]
+    /// Maximum number of suggestions (max 20)
+    limit: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, sqlx::FromRow)]
+pub struct Suggestion {
+    #[schema(example = "Buy groceries")]
+    pub value: String,
+    #[schema(example = 3)]
+    /// How many todos use this value, ignoring case
+    pub count: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/suggest",
+    params(SuggestQuery),
+    responses(
+        (status = 200, description = "Previously used titles starting with the prefix, most recently used first", body = ApiResponseVecSuggestion),
+        (status = 400, description = "Missing q or unsupported kind", body = ApiResponseString),
+        (status = 500, description = "Database error", body = ApiResponseString)
+    ),
+    tag = "todos"
+)]
+pub async fn suggest(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<SuggestQuery>, QueryRejection>,
+) -> Result<impl IntoResponse, AppError> {
+    let Query(query) = query?;
+    match query.kind.as_deref() {
+        None | Some("title") => {}
+        Some(kind) => {
+            return Err(AppError::ValidationError(format!(
+                "Unsupported suggestion kind \"{}\", expected \"title\"",
+                kind
+            )));
+        }
+    }
+
+    let prefix = query.q.trim().to_lowercase();
+    if prefix.chars().count() < MIN_QUERY_CHARS {
+        return Ok((StatusCode::OK, Json(ApiResponse::success(Vec::<Suggestion>::new()))));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let pattern = format!(
+        "{}%",
+        prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+    );
+    let suggestions = sqlx::query_as::<_, Suggestion>(
+        r#"
+        -- "Milk" y "milk" son una sola sugerencia, con la grafía usada más recientemente
+        SELECT (array_agg(title ORDER BY updated_at DESC))[1] AS value, COUNT(*) AS count
+        FROM todos
+        WHERE lower(title) LIKE $1
+        GROUP BY lower(title)
+        ORDER BY MAX(updated_at) DESC, lower(title)
+        LIMIT $2
+        "#
+    )
+    .bind(pattern)
+    .bind(limit as i64)
+    .fetch_all(&state.db)
+    .await?;
+
+    info!("Returning {} title suggestions for prefix {:?}", suggestions.len(), prefix);
+    Ok((StatusCode::OK, Json(ApiResponse::success(suggestions))))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use crate::testing;
+
+    /// Inserts a todo last changed `minutes_ago`.
+    async fn insert(db: &PgPool, title: &str, minutes_ago: i32) {
+        sqlx::query("INSERT INTO todos (title, updated_at) VALUES ($1, now() - make_interval(mins => $2))")
+            .bind(title)
+            .bind(minutes_ago)
+            .execute(db)
+            .await
+            .unwrap();
+    }
+
+    async fn suggest(db: &PgPool, query: &str) -> (StatusCode, Value) {
+        let state = testing::state(db.clone(), testing::config(&[]));
+        let response = testing::send(&state, testing::get(&format!("/api/v1/suggest?{}", query))).await;
+        (response.status(), testing::json(response).await)
+    }
+
+    async fn values(db: &PgPool, query: &str) -> Vec<String> {
+        let (status, body) = suggest(db, query).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        body["data"].as_array().unwrap().iter().map(|suggestion| suggestion["value"].as_str().unwrap().to_string()).collect()
+    }
+
+    #[sqlx::test]
+    async fn needs_at_least_two_characters(db: PgPool) {
+        insert(&db, "Buy milk", 1).await;
+
+        for query in ["q=", "q=b", "q=%20%20b%20", "q=%C3%A9"] {
+            let (status, body) = suggest(&db, query).await;
+            assert_eq!((status, &body["data"]), (StatusCode::OK, &json!([])), "{}", query);
+        }
+        assert_eq!(values(&db, "q=bu").await, ["Buy milk"]);
+    }
+
+    #[sqlx::test]
+    async fn orders_by_most_recent_use_and_merges_case(db: PgPool) {
+        insert(&db, "Milk", 30).await;
+        insert(&db, "milk", 20).await;
+        insert(&db, "Mild salsa", 10).await;
+        insert(&db, "Mint", 40).await;
+        insert(&db, "Call mom", 1).await;
+
+        let (status, body) = suggest(&db, "q=MI").await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["data"],
+            json!([
+                { "value": "Mild salsa", "count": 1 },
+                { "value": "milk", "count": 2 },
+                { "value": "Mint", "count": 1 }
+            ])
+        );
+        // Usar otra vez "Milk" hace que su grafía sea la sugerida
+        insert(&db, "Milk", 0).await;
+        assert_eq!(values(&db, "q=mil").await, ["Milk", "Mild salsa"]);
+    }
+
+    #[sqlx::test]
+    async fn matches_like_wildcards_literally(db: PgPool) {
+        insert(&db, "100% done", 1).await;
+        insert(&db, "1000 steps", 2).await;
+        insert(&db, "a_b test", 3).await;
+        insert(&db, "axb test", 4).await;
+        insert(&db, "C:\\temp cleanup", 5).await;
+        insert(&db, "C:xtemp", 6).await;
+
+        assert_eq!(values(&db, "q=100%25").await, ["100% done"]);
+        assert_eq!(values(&db, "q=a_").await, ["a_b test"]);
+        assert_eq!(values(&db, "q=%25%25").await, Vec::<String>::new());
+        assert_eq!(values(&db, "q=c:%5C").await, ["C:\\temp cleanup"]);
+    }
+
+    #[sqlx::test]
+    async fn caps_the_limit(db: PgPool) {
+        for number in 0..25 {
+            insert(&db, &format!("Task {:02}", number), number).await;
+        }
+
+        assert_eq!(values(&db, "q=task").await, ["Task 00", "Task 01", "Task 02", "Task 03", "Task 04"]);
+        assert_eq!(values(&db, "q=task&limit=2").await, ["Task 00", "Task 01"]);
+        assert_eq!(values(&db, "q=task&limit=0").await, ["Task 00"]);
+        assert_eq!(values(&db, "q=task&limit=100").await.len(), 20);
+    }
+
+    #[sqlx::test]
+    async fn rejects_unknown_kinds_and_bad_parameters_with_the_envelope(db: PgPool) {
+        insert(&db, "Buy milk", 1).await;
+        assert_eq!(values(&db, "q=bu&kind=title").await, ["Buy milk"]);
+
+        let (status, body) = suggest(&db, "q=bu&kind=tag").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            json!({ "status": "error", "data": null, "error": "Unsupported suggestion kind \"tag\", expected \"title\"" })
+        );
+
+        for query in ["", "kind=title", "q=bu&limit=many", "q=bu&limit=-1"] {
+            let (status, body) = suggest(&db, query).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", query);
+            assert_eq!((&body["status"], &body["data"]), (&json!("error"), &Value::Null), "{}", query);
+            assert!(body["error"].as_str().unwrap().starts_with("Failed to deserialize query string"), "{}: {}", query, body);
+        }
+    }
+}
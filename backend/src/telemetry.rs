@@ -0,0 +1,210 @@
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request},
+    http::HeaderValue,
+    middleware::{self, Next},
+    response::Response,
+    Router,
+};
+use std::{net::IpAddr, sync::{Arc, LazyLock}};
+use uuid::Uuid;
+use crate::{client_ip::ClientIp, config::Config, info, model::AppState, signature};
+
+pub(crate) const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Request headers dropped from events on top of the ones sentry-tower
+/// already filters (cookies, authorization, X-Forwarded-For, X-Real-IP).
+const SCRUBBED_HEADERS: [&str; 3] = ["forwarded", "x-admin-secret", "x-feature-overrides-signature"];
+
+/// Key for the client ids sent to Sentry, new on every start: a plain hash of
+/// an IPv4 address could be reversed by hashing all of them.
+static CLIENT_ID_KEY: LazyLock<[u8; 16]> = LazyLock::new(|| *Uuid::new_v4().as_bytes());
+
+/// Id of the current request, the caller's `X-Request-Id` or a generated
+/// one; stored in the request extensions by [`assign_request_id`].
+#[derive(Clone)]
+pub struct RequestId(pub String);
+
+/// Starts the Sentry client when `SENTRY_DSN` is set. Dropping the guard
+/// flushes pending events, so it must live until the process exits.
+pub fn init_sentry(config: &Config) -> Option<sentry::ClientInitGuard> {
+    let dsn = config.sentry_dsn.as_deref()?;
+    let release = match info::git_commit() {
+        Some(commit) => format!("backend@{}+{}", info::VERSION, commit),
+        None => format!("backend@{}", info::VERSION),
+    };
+    Some(sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: Some(release.into()),
+            environment: Some(config.app_env.clone().into()),
+            before_send: Some(Arc::new(|mut event| {
+                if let Some(request) = &mut event.request {
+                    scrub_headers(request);
+                }
+                Some(event)
+            })),
+            ..Default::default()
+        },
+    )))
+}
+
+/// Wraps the router so every request gets its own hub with the HTTP request,
+/// matched route and request id attached to whatever it reports.
+pub fn sentry_layers(app: Router<Arc<AppState>>) -> Router<Arc<AppState>> {
+    // axum aplica las capas en orden inverso: la última es la más externa
+    app.layer(middleware::from_fn(tag_scope))
+        .layer(sentry_tower::SentryHttpLayer::new())
+        .layer(sentry_tower::NewSentryLayer::<Request<Body>>::new_from_top())
+}
+
+/// Gives every request a [`RequestId`] and echoes it back in `X-Request-Id`,
+/// whether Sentry is enabled or not.
+pub async fn assign_request_id(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    request.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Tags the Sentry scope with the matched route, the request id and the
+/// client, identified by [`client_id`] rather than by its address.
+async fn tag_scope(request: Request, next: Next) -> Response {
+    let request_id = request.extensions().get::<RequestId>().map(|id| id.0.clone());
+    let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let client = request.extensions().get::<ClientIp>().copied();
+    sentry::configure_scope(|scope| {
+        if let Some(request_id) = &request_id {
+            scope.set_tag("request_id", request_id);
+        }
+        if let Some(ClientIp(ip)) = client {
+            scope.set_user(Some(sentry::User {
+                id: Some(client_id(ip)),
+                ..Default::default()
+            }));
+        }
+        if let Some(route) = &route {
+            scope.set_tag("route", route);
+            scope.set_transaction(Some(route));
+        }
+    });
+
+    next.run(request).await
+}
+
+/// Stable for the life of the process, so Sentry can count affected clients,
+/// but not reversible to the address.
+fn client_id(ip: IpAddr) -> String {
+    hex::encode(&signature::sign(CLIENT_ID_KEY.as_slice(), &[ip.to_string().as_bytes()])[..16])
+}
+
+fn scrub_headers(request: &mut sentry::protocol::Request) {
+    request
+        .headers
+        .retain(|name, _| !SCRUBBED_HEADERS.contains(&name.to_ascii_lowercase().as_str()));
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{extract::Request, http::StatusCode, routing::get, Router};
+    use sqlx::{postgres::PgPoolOptions, PgPool};
+    use std::{collections::BTreeMap, net::IpAddr};
+    use tower::ServiceExt;
+    use super::{client_id, scrub_headers, sentry_layers, RequestId, REQUEST_ID_HEADER};
+    use crate::{client_ip::ClientIp, testing};
+
+    #[sqlx::test]
+    async fn assigns_a_request_id_without_sentry(db: PgPool) {
+        let state = testing::state(db, testing::config(&[]));
+
+        let generated = testing::send(&state, testing::get("/api/v1/health")).await;
+        let id = generated.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(id).is_ok(), "{}", id);
+
+        let mut request = testing::get("/api/v1/health");
+        request.headers_mut().insert(REQUEST_ID_HEADER, "abc-123".parse().unwrap());
+        let echoed = testing::send(&state, request).await;
+        assert_eq!(echoed.headers()[REQUEST_ID_HEADER], "abc-123");
+
+        // También en los errores
+        let missing = testing::send(&state, testing::get("/api/v1/todos/not-a-uuid")).await;
+        assert_eq!(missing.status(), StatusCode::BAD_REQUEST);
+        assert!(missing.headers().contains_key(REQUEST_ID_HEADER));
+    }
+
+    #[test]
+    fn client_ids_are_stable_hashes_that_do_not_contain_the_address() {
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        let other: IpAddr = "203.0.113.8".parse().unwrap();
+
+        let id = client_id(ip);
+        assert_eq!(id, client_id(ip));
+        assert_ne!(id, client_id(other));
+        assert_eq!(id.len(), 32);
+        assert!(!id.contains("203") && id.chars().all(|c| c.is_ascii_hexdigit()), "{}", id);
+    }
+
+    #[test]
+    fn scrubs_secrets_and_addresses_from_request_headers() {
+        let mut request = sentry::protocol::Request {
+            headers: BTreeMap::from(
+                [
+                    ("Forwarded", "for=203.0.113.7"),
+                    ("x-admin-secret", "s3cr3t"),
+                    ("X-Feature-Overrides-Signature", "abcd"),
+                    ("user-agent", "curl/8.0"),
+                ]
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+            ),
+            ..Default::default()
+        };
+
+        scrub_headers(&mut request);
+
+        assert_eq!(request.headers.keys().collect::<Vec<_>>(), ["user-agent"]);
+    }
+
+    #[test]
+    fn tags_events_with_the_request_id_and_a_client_id_but_no_address() {
+        let events = sentry::test::with_captured_events(|| {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async {
+                // Nunca se conecta: el handler no usa la base de datos
+                let db = PgPoolOptions::new().connect_lazy("postgres://test@localhost/test").unwrap();
+                let state = testing::state(db, testing::config(&[]));
+                let app = sentry_layers(Router::new().route(
+                    "/boom",
+                    get(|| async {
+                        sentry::capture_message("boom", sentry::Level::Error);
+                        "ok"
+                    }),
+                ))
+                .with_state(state);
+
+                let mut request = Request::builder().uri("/boom").body(axum::body::Body::empty()).unwrap();
+                request.extensions_mut().insert(ClientIp("203.0.113.7".parse().unwrap()));
+                request.extensions_mut().insert(RequestId("abc-123".to_string()));
+                app.oneshot(request).await.unwrap();
+            });
+        });
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        let user = event.user.as_ref().unwrap();
+        assert_eq!(user.id.as_deref(), Some(client_id("203.0.113.7".parse().unwrap()).as_str()));
+        assert!(user.ip_address.is_none());
+        assert_eq!(event.tags["request_id"], "abc-123");
+        assert_eq!(event.tags["route"], "/boom");
+        assert_eq!(event.transaction.as_deref(), Some("/boom"));
+    }
+}
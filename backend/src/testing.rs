@@ -0,0 +1,88 @@
+//! Helpers for the unit tests: a configuration built from a few variables,
+//! state on the database of an `#[sqlx::test]`, and requests sent through the
+//! whole service, middleware included.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request},
+    http::{header::CONTENT_TYPE, Method},
+    response::Response,
+};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use uuid::Uuid;
+use tower::ServiceExt;
+use crate::{clock::MockClock, config::Config, model::AppState};
+
+/// Address requests come from unless they carry their own `ConnectInfo`.
+pub const PEER: &str = "192.0.2.10:40000";
+
+/// The configuration for `vars` on top of the defaults; `DATABASE_URL` only
+/// needs to be given when the test connects with it.
+pub fn config(vars: &[(&str, &str)]) -> Config {
+    let vars = [("DATABASE_URL", "postgres://test@localhost/test")]
+        .iter()
+        .chain(vars)
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect::<HashMap<_, _>>();
+    Config::from_lookup(|name| vars.get(name).cloned()).expect("valid test configuration")
+}
+
+/// Ready state on `db`, which `#[sqlx::test]` has already migrated.
+pub fn state(db: PgPool, config: Config) -> Arc<AppState> {
+    Arc::new(AppState::new(db, config, true))
+}
+
+/// Like [`state`], but telling the time by `clock`.
+pub fn state_at(db: PgPool, config: Config, clock: &MockClock) -> Arc<AppState> {
+    let mut state = AppState::new(db, config, true);
+    state.clock = Box::new(clock.clone());
+    Arc::new(state)
+}
+
+/// Sends `request` through the service built by `crate::app`, without Sentry
+/// or an access log.
+pub async fn send(state: &Arc<AppState>, mut request: Request) -> Response {
+    if request.extensions().get::<ConnectInfo<SocketAddr>>().is_none() {
+        request.extensions_mut().insert(ConnectInfo(PEER.parse::<SocketAddr>().unwrap()));
+    }
+    crate::app(state.clone(), false, None).oneshot(request).await.unwrap()
+}
+
+/// Inserts a todo straight into the database, returning its id.
+pub async fn insert_todo(db: &PgPool, title: &str) -> Uuid {
+    sqlx::query_scalar("INSERT INTO todos (title) VALUES ($1) RETURNING id")
+        .bind(title)
+        .fetch_one(db)
+        .await
+        .unwrap()
+}
+
+pub fn get(uri: &str) -> Request {
+    request(Method::GET, uri, None, Body::empty())
+}
+
+/// A request with `body` as JSON.
+pub fn json_request(method: Method, uri: &str, body: &Value) -> Request {
+    request(method, uri, Some("application/json"), Body::from(body.to_string()))
+}
+
+pub fn request(method: Method, uri: &str, content_type: Option<&str>, body: Body) -> Request {
+    let mut builder = Request::builder().method(method).uri(uri);
+    if let Some(content_type) = content_type {
+        builder = builder.header(CONTENT_TYPE, content_type);
+    }
+    builder.body(body).unwrap()
+}
+
+pub async fn body(response: Response) -> Vec<u8> {
+    to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()
+}
+
+/// The body of `response`, which must be JSON.
+pub async fn json(response: Response) -> Value {
+    let body = body(response).await;
+    serde_json::from_slice(&body)
+        .unwrap_or_else(|e| panic!("invalid JSON ({}): {}", e, String::from_utf8_lossy(&body)))
+}
@@ -0,0 +1,136 @@
+//! The single wire format for timestamps. Every `DateTime` a response
+//! carries is serialized through this module (`#[serde(with = "crate::timestamp")]`,
+//! or [`option`] for optional fields): always UTC with a `Z`, and
+//! millisecond precision unless `TIMESTAMP_PRECISION=seconds`. Parsing is
+//! lenient and shared by every field that accepts a timestamp.
+
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+use std::{str::FromStr, sync::OnceLock};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimestampPrecision {
+    Millis,
+    Seconds,
+}
+
+impl FromStr for TimestampPrecision {
+    type Err = &'static str;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "millis" | "milliseconds" => Ok(TimestampPrecision::Millis),
+            "seconds" => Ok(TimestampPrecision::Seconds),
+            _ => Err("TIMESTAMP_PRECISION must be millis or seconds"),
+        }
+    }
+}
+
+// Global porque serde no puede recibir la configuración; se fija una vez al arrancar
+static PRECISION: OnceLock<TimestampPrecision> = OnceLock::new();
+
+/// Sets the output precision; only the first call has an effect.
+pub fn set_precision(precision: TimestampPrecision) {
+    let _ = PRECISION.set(precision);
+}
+
+/// `2023-01-01T00:00:00.123Z`, or `2023-01-01T00:00:00Z` at second precision.
+pub fn format(value: &DateTime<Utc>) -> String {
+    format_with(value, PRECISION.get().copied().unwrap_or(TimestampPrecision::Millis))
+}
+
+fn format_with(value: &DateTime<Utc>, precision: TimestampPrecision) -> String {
+    let format = match precision {
+        TimestampPrecision::Millis => SecondsFormat::Millis,
+        TimestampPrecision::Seconds => SecondsFormat::Secs,
+    };
+    value.to_rfc3339_opts(format, true)
+}
+
+/// Any RFC 3339 form (`Z` or a numeric offset, `T` or a space, any
+/// fraction), plus offsets without a colon such as `+0000`.
+pub fn parse(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| value.parse::<DateTime<FixedOffset>>())
+        .ok()
+        .map(|value| value.with_timezone(&Utc))
+}
+
+pub fn serialize<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format(value))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse(&value).ok_or_else(|| D::Error::custom(format!("invalid timestamp {:?}, expected RFC 3339", value)))
+}
+
+/// The same format for `Option<DateTime<Utc>>`, with `None` as `null`.
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => super::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| {
+                parse(&value)
+                    .ok_or_else(|| D::Error::custom(format!("invalid timestamp {:?}, expected RFC 3339", value)))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+    use super::{format_with, parse, TimestampPrecision};
+
+    #[test]
+    fn parses_every_rfc_3339_spelling_of_the_same_instant() {
+        let instant = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap();
+        for input in [
+            "2024-05-01T10:00:00Z",
+            "2024-05-01T10:00:00z",
+            "2024-05-01t10:00:00Z",
+            "2024-05-01T10:00:00+00:00",
+            "2024-05-01T10:00:00-00:00",
+            "2024-05-01T12:00:00+02:00",
+            "2024-05-01T04:30:00-05:30",
+            "2024-05-01 10:00:00Z",
+            "2024-05-01T10:00:00.000Z",
+            "2024-05-01T10:00:00.000000000+00:00",
+            "2024-05-01T10:00:00+0000",
+            "  2024-05-01T10:00:00Z  ",
+        ] {
+            assert_eq!(parse(input), Some(instant), "{}", input);
+        }
+        assert_eq!(parse("2024-05-01T10:00:00.123456789Z").unwrap().timestamp_subsec_nanos(), 123_456_789);
+    }
+
+    #[test]
+    fn leaves_dates_and_garbage_to_the_caller() {
+        for input in ["2024-05-01", "2024-05-01T10:00:00", "10:00", "tomorrow", "", "2024-13-01T10:00:00Z"] {
+            assert_eq!(parse(input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn formats_in_utc_with_millis_or_seconds() {
+        let instant = "2023-01-01T01:00:00.123456+01:00".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(format_with(&instant, TimestampPrecision::Millis), "2023-01-01T00:00:00.123Z");
+        assert_eq!(format_with(&instant, TimestampPrecision::Seconds), "2023-01-01T00:00:00Z");
+        let whole = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(format_with(&whole, TimestampPrecision::Millis), "2023-01-01T00:00:00.000Z");
+
+        assert_eq!("Millis".parse(), Ok(TimestampPrecision::Millis));
+        assert_eq!(" seconds ".parse(), Ok(TimestampPrecision::Seconds));
+        assert!("micros".parse::<TimestampPrecision>().is_err());
+    }
+}
@@ -0,0 +1,24 @@
+mod common;
+
+use common::Server;
+use reqwest::StatusCode;
+
+#[tokio::test]
+async fn exits_cleanly_after_an_admin_shutdown() {
+    let mut server = Server::start(&[("ADMIN_SHARED_SECRET", "test-admin-secret")]).await;
+
+    let request = server.post("/api/v1/admin/shutdown?drain_secs=1").header("X-Admin-Secret", "test-admin-secret");
+    let (status, _) = server.json(request).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    // Durante el drenaje sigue vivo pero ya no está listo
+    let (status, _) = server.json(server.get("/api/v1/health/ready")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let (status, _) = server.json(server.get("/api/v1/health")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (code, output) = server.wait_for_exit().await;
+    assert_eq!(code, Some(0), "{}", output);
+    assert!(output.contains("Shutting down gracefully"), "{}", output);
+    server.stop().await;
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::Server;
+use reqwest::{header::LOCATION, StatusCode};
+use serde_json::json;
+
+#[tokio::test]
+async fn serves_everything_under_the_base_path() {
+    let server = Server::spawn(&[("BASE_PATH", "/todo/")]).await;
+    server.wait_for("/todo/api/v1/health/ready").await;
+
+    let (status, openapi) = server.json(server.get("/todo/api-docs/openapi.json")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(openapi["servers"], json!([{"url": "/todo"}]));
+    assert!(openapi["paths"]["/api/v1/todos"].is_object());
+    let swagger_ui = server.get("/todo/swagger-ui/").send().await.unwrap();
+    assert_eq!(swagger_ui.status(), StatusCode::OK);
+    assert!(swagger_ui.text().await.unwrap().contains("<html"));
+
+    let response = server.post("/todo/api/v1/todos").json(&json!({"title": "Behind the proxy"})).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = response.headers()[LOCATION].to_str().unwrap().to_string();
+    let created: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(location, format!("/todo/api/v1/todos/{}", created["data"]["id"].as_str().unwrap()));
+    let (status, todo) = server.json(server.get(&location)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(todo["data"], created["data"]);
+
+    for path in ["/api/v1/health", "/api/v1/todos", "/api-docs/openapi.json", "/swagger-ui/", "/todos/api/v1/todos"] {
+        let response = server.get(path).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+    }
+
+    server.stop().await;
+}
@@ -0,0 +1,204 @@
+//! `todo-cli` run against a spawned server. Only built with
+//! `--features cli-client`.
+
+mod common;
+
+use axum::{http::{header::AUTHORIZATION, HeaderMap, StatusCode}, Json, Router};
+use common::Server;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+struct Output {
+    code: Option<i32>,
+    stdout: String,
+    stderr: String,
+}
+
+impl Output {
+    fn json(&self) -> Value {
+        serde_json::from_str(&self.stdout).unwrap_or_else(|e| panic!("invalid JSON ({}): {}", e, self.stdout))
+    }
+}
+
+/// An empty config directory, so the user's own config.toml is never read.
+fn config_home() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ha_todo_cli_{}", Uuid::new_v4().simple()));
+    std::fs::create_dir_all(dir.join("ha-todo")).unwrap();
+    dir
+}
+
+/// Runs `todo-cli` with only `vars` in its environment.
+async fn cli(args: &[&str], vars: &[(&str, &str)]) -> Output {
+    let config_home = config_home();
+    cli_with_config(args, vars, &config_home).await
+}
+
+async fn cli_with_config(args: &[&str], vars: &[(&str, &str)], config_home: &std::path::Path) -> Output {
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_todo-cli"))
+        .args(args)
+        .env_clear()
+        .env("XDG_CONFIG_HOME", config_home)
+        .envs(vars.iter().copied())
+        .output()
+        .await
+        .unwrap();
+    Output {
+        code: output.status.code(),
+        stdout: String::from_utf8(output.stdout).unwrap(),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn adds_lists_completes_and_removes_todos() {
+    let server = Server::start(&[]).await;
+    let env = [("TODO_URL", server.url.as_str()), ("TODO_TZ", "Europe/Madrid")];
+
+    let added = cli(&["--json", "add", "Buy milk", "--due", "2030-06-01", "--description", "Two liters"], &env).await;
+    assert_eq!(added.code, Some(0), "{}", added.stderr);
+    let todo = &added.json()[0];
+    assert_eq!(
+        (&todo["title"], &todo["description"], &todo["completed"], &todo["due_date"]),
+        (&json!("Buy milk"), &json!("Two liters"), &json!(false), &json!("2030-05-31T22:00:00.000Z"))
+    );
+    let id = todo["id"].as_str().unwrap().to_string();
+    assert_eq!(cli(&["add", "Call the plumber"], &env).await.code, Some(0));
+
+    // La tabla muestra el id corto y la fecha en TODO_TZ
+    let table = cli(&["list"], &env).await;
+    assert_eq!(table.code, Some(0), "{}", table.stderr);
+    let lines = table.stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3, "{}", table.stdout);
+    assert!(lines[0].starts_with("ID "), "{}", table.stdout);
+    let row = lines.iter().find(|line| line.ends_with("Buy milk")).unwrap();
+    assert!(row.starts_with(&id[..8]), "{}", row);
+    assert!(row.contains("2030-06-01 00:00"), "{}", row);
+
+    let done = cli(&["--json", "done", &id[..8]], &env).await;
+    assert_eq!(done.code, Some(0), "{}", done.stderr);
+    assert_eq!(done.json()[0]["completed"], true);
+    let active = cli(&["list", "--status", "active", "--json"], &env).await.json();
+    assert_eq!(active.as_array().unwrap().iter().map(|todo| &todo["title"]).collect::<Vec<_>>(), [&json!("Call the plumber")]);
+    let completed = cli(&["list", "--status", "completed", "--json"], &env).await.json();
+    assert_eq!(completed[0]["id"], json!(id));
+
+    let removed = cli(&["rm", &id], &env).await;
+    assert_eq!((removed.code, removed.stdout.trim()), (Some(0), format!("Deleted {}", id).as_str()));
+    let again = cli(&["rm", &id], &env).await;
+    assert_eq!(again.code, Some(66), "{}", again.stderr);
+    assert_eq!(again.stderr.trim(), "Error: not found: Resource not found");
+    let completed = cli(&["list", "--status", "completed"], &env).await;
+    assert_eq!(completed.stdout.trim(), "No todos");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn resolves_short_ids_only_when_unambiguous() {
+    let server = Server::start(&[("PUT_CREATES", "true")]).await;
+    for id in ["abcd1111-0000-4000-8000-000000000000", "abcd2222-0000-4000-8000-000000000000"] {
+        let body = json!({"title": id, "description": null, "completed": false, "due_date": null});
+        let response = server.client.put(format!("{}/api/v1/todos/{}", server.url, id)).json(&body).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+    let env = [("TODO_URL", server.url.as_str())];
+
+    let ambiguous = cli(&["done", "abcd"], &env).await;
+    assert_eq!(ambiguous.code, Some(64), "{}", ambiguous.stderr);
+    assert!(ambiguous.stderr.contains("'abcd' matches 2 todos, use more characters"), "{}", ambiguous.stderr);
+
+    let done = cli(&["--json", "done", "ABCD2"], &env).await;
+    assert_eq!(done.code, Some(0), "{}", done.stderr);
+    assert_eq!(done.json()[0]["id"], "abcd2222-0000-4000-8000-000000000000");
+
+    let unknown = cli(&["rm", "ffff"], &env).await;
+    assert_eq!(unknown.code, Some(66), "{}", unknown.stderr);
+    assert!(unknown.stderr.contains("no todo id starts with 'ffff'"), "{}", unknown.stderr);
+    for not_an_id in ["abc", "milk"] {
+        let output = cli(&["rm", not_an_id], &env).await;
+        assert_eq!(output.code, Some(64), "{}: {}", not_an_id, output.stderr);
+    }
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn exit_codes_tell_failures_apart() {
+    let server = Server::start(&[]).await;
+    let env = [("TODO_URL", server.url.as_str())];
+
+    for (args, code) in [
+        (&[][..], 64),
+        (&["frobnicate"][..], 64),
+        (&["add"][..], 64),
+        (&["add", "Buy", "milk"][..], 64),
+        (&["list", "--status", "someday"][..], 64),
+        (&["list", "--limit", "-1"][..], 64),
+        (&["done"][..], 64),
+        (&["help"][..], 0),
+    ] {
+        let output = cli(args, &env).await;
+        assert_eq!(output.code, Some(code), "{:?}: {}", args, output.stderr);
+    }
+
+    let rejected = cli(&["add", "Buy milk", "--due", "whenever"], &env).await;
+    assert_eq!(rejected.code, Some(65), "{}", rejected.stderr);
+    assert!(rejected.stderr.starts_with("Error: rejected by the server: "), "{}", rejected.stderr);
+
+    let bad_tz = cli(&["list"], &[("TODO_URL", server.url.as_str()), ("TODO_TZ", "Mars/Olympus")]).await;
+    assert_eq!(bad_tz.code, Some(78), "{}", bad_tz.stderr);
+
+    // Nada escucha en el puerto 1
+    let unreachable = cli(&["list"], &[("TODO_URL", "http://127.0.0.1:1")]).await;
+    assert_eq!(unreachable.code, Some(69), "{}", unreachable.stderr);
+    assert!(unreachable.stderr.starts_with("Error: could not reach the server: "), "{}", unreachable.stderr);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn reads_the_config_file_below_the_environment() {
+    let server = Server::start(&[]).await;
+    let config_home = config_home();
+    let config = config_home.join("ha-todo").join("config.toml");
+
+    std::fs::write(&config, format!("url = \"{}\"\ntz = \"America/New_York\"\n", server.url)).unwrap();
+    let added = cli_with_config(&["--json", "add", "From the file", "--due", "2030-06-01"], &[], &config_home).await;
+    assert_eq!(added.code, Some(0), "{}", added.stderr);
+    assert_eq!(added.json()[0]["due_date"], "2030-06-01T04:00:00.000Z");
+
+    // TODO_URL gana al fichero
+    let overridden = cli_with_config(&["list"], &[("TODO_URL", "http://127.0.0.1:1")], &config_home).await;
+    assert_eq!(overridden.code, Some(69), "{}", overridden.stderr);
+
+    std::fs::write(&config, "url = \"http://localhost\"\ncolour = \"blue\"\n").unwrap();
+    let invalid = cli_with_config(&["list"], &[], &config_home).await;
+    assert_eq!(invalid.code, Some(78), "{}", invalid.stderr);
+    assert!(invalid.stderr.contains("config.toml"), "{}", invalid.stderr);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn sends_the_api_key_and_reports_server_errors() {
+    // Un proxy que exige la clave y un servicio que falla detrás de él
+    let app = Router::new().fallback(|headers: HeaderMap| async move {
+        if headers.get(AUTHORIZATION).is_some_and(|value| value == "Bearer s3cret") {
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"status": "error", "data": null, "error": "Database error"})))
+        } else {
+            (StatusCode::UNAUTHORIZED, Json(json!({"status": "error", "data": null, "error": "Unauthorized"})))
+        }
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let failing = cli(&["list"], &[("TODO_URL", url.as_str()), ("TODO_API_KEY", "s3cret")]).await;
+    assert_eq!(failing.code, Some(70), "{}", failing.stderr);
+    assert_eq!(failing.stderr.trim(), "Error: server error: 500 Database error");
+
+    let unauthorized = cli(&["list"], &[("TODO_URL", url.as_str())]).await;
+    assert_eq!(unauthorized.code, Some(65), "{}", unauthorized.stderr);
+    assert_eq!(unauthorized.stderr.trim(), "Error: rejected by the server: Unauthorized");
+}
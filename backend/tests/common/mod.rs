@@ -0,0 +1,152 @@
+//! The backend binary started on a database of its own, for tests that go
+//! through a real HTTP server. `DATABASE_URL` must point at a PostgreSQL
+//! server where the tests may create databases, as for `#[sqlx::test]`.
+
+#![allow(dead_code)]
+
+use serde_json::Value;
+use sqlx::{Connection, PgConnection};
+use std::{
+    fs::File,
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Server {
+    /// `http://127.0.0.1:<port>`, without a trailing slash.
+    pub url: String,
+    /// Where the server writes its logs.
+    pub log: PathBuf,
+    pub client: reqwest::Client,
+    child: Child,
+    admin_url: String,
+    database: String,
+}
+
+impl Server {
+    /// Starts the server with `vars` on top of the database and address
+    /// settings, and waits until it is ready.
+    pub async fn start(vars: &[(&str, &str)]) -> Server {
+        let server = Server::spawn(vars).await;
+        server.wait_for("/api/v1/health/ready").await;
+        server
+    }
+
+    /// Starts the server without waiting for it.
+    pub async fn spawn(vars: &[(&str, &str)]) -> Server {
+        let admin_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for the integration tests");
+        let database = format!("ha_todo_test_{}", Uuid::new_v4().simple());
+        let mut conn = PgConnection::connect(&admin_url).await.unwrap();
+        sqlx::query(&format!("CREATE DATABASE {}", database)).execute(&mut conn).await.unwrap();
+        conn.close().await.unwrap();
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let log = std::env::temp_dir().join(format!("{}.log", database));
+        let output = File::create(&log).unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_backend"))
+            // Sin .env: solo cuenta lo que se pasa aquí
+            .current_dir(std::env::temp_dir())
+            .env_clear()
+            .env("DATABASE_URL", database_url(&admin_url, &database))
+            .env("SERVER_HOST", "127.0.0.1")
+            .env("SERVER_PORT", port.to_string())
+            .envs(vars.iter().copied())
+            .stdout(output.try_clone().unwrap())
+            .stderr(output)
+            .stdin(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        Server {
+            url: format!("http://127.0.0.1:{}", port),
+            log,
+            client: reqwest::Client::new(),
+            child,
+            admin_url,
+            database,
+        }
+    }
+
+    /// Waits until a GET to `path` succeeds, e.g. the liveness or readiness
+    /// endpoint.
+    pub async fn wait_for(&self, path: &str) {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        let url = format!("{}{}", self.url, path);
+        while Instant::now() < deadline {
+            let response = self.client.get(&url).send().await;
+            if response.is_ok_and(|response| response.status().is_success()) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("GET {} never succeeded, see {}", path, self.log.display());
+    }
+
+    /// Waits for the server to exit on its own, returning its exit code and
+    /// everything it logged.
+    pub async fn wait_for_exit(&mut self) -> (Option<i32>, String) {
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return (status.code(), std::fs::read_to_string(&self.log).unwrap());
+            }
+            if Instant::now() >= deadline {
+                panic!("the server did not exit, see {}", self.log.display());
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    pub fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client.get(format!("{}{}", self.url, path))
+    }
+
+    pub fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client.post(format!("{}{}", self.url, path))
+    }
+
+    /// Sends `request` and returns the status with the JSON body.
+    pub async fn json(&self, request: reqwest::RequestBuilder) -> (reqwest::StatusCode, Value) {
+        let response = request.send().await.unwrap();
+        let status = response.status();
+        let body = response.text().await.unwrap();
+        let body = serde_json::from_str(&body).unwrap_or_else(|e| panic!("invalid JSON ({}): {}", e, body));
+        (status, body)
+    }
+
+    /// Stops the server and drops its database.
+    pub async fn stop(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let mut conn = PgConnection::connect(&self.admin_url).await.unwrap();
+        sqlx::query(&format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", self.database))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        // Si el test falla antes de stop, al menos no queda el proceso
+        let _ = self.child.kill();
+    }
+}
+
+/// `url` pointing at `database` instead of the one it names.
+fn database_url(url: &str, database: &str) -> String {
+    let (url, query) = match url.split_once('?') {
+        Some((url, query)) => (url, Some(query)),
+        None => (url, None),
+    };
+    let (server, _) = url.rsplit_once('/').expect("DATABASE_URL must name a database");
+    match query {
+        Some(query) => format!("{}/{}?{}", server, database, query),
+        None => format!("{}/{}", server, database),
+    }
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::Server;
+use reqwest::{header::CONTENT_TYPE, StatusCode};
+use serde_json::{json, Value};
+
+const FIXTURE: &str = include_str!("fixtures/google-takeout-tasks.json");
+const IMPORT: &str = "/api/v1/todos/import/google-tasks";
+
+#[tokio::test]
+async fn imports_a_takeout_export_once() {
+    let server = Server::start(&[]).await;
+    let import = || server.post(IMPORT).header(CONTENT_TYPE, "application/json").body(FIXTURE);
+
+    let (status, body) = server.json(import()).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let report = &body["data"];
+    assert_eq!(report["created"].as_array().unwrap().len(), 5);
+    assert_eq!(report["skipped"], 0);
+    assert_eq!(
+        report["lists"],
+        json!([
+            { "title": "My Tasks", "created": 3, "skipped": 0, "errors": 1 },
+            { "title": "Groceries", "created": 2, "skipped": 0, "errors": 0 },
+            { "title": "Someday", "created": 0, "skipped": 0, "errors": 0 }
+        ])
+    );
+    // El de título vacío es el quinto del fichero, contando el borrado
+    assert_eq!(report["errors"][0]["index"], 5);
+    assert_eq!(report["errors"][0]["uid"], "VnJTbl9KeUJ0aWZHZ2FzRQ");
+    assert_eq!(report["warnings"][0]["uid"], "cFdMNWVGVFh3X0VhN0dWNw");
+
+    let (_, todos) = server.json(server.get("/api/v1/todos?limit=100")).await;
+    let todos = todos["data"].as_array().unwrap();
+    let todo = |title: &str| -> &Value {
+        todos.iter().find(|todo| todo["title"] == title).unwrap_or_else(|| panic!("{} not imported", title))
+    };
+    assert_eq!(todos.len(), 5);
+    assert_eq!(todo("Renew passport")["description"], "Bring two photos\nand the old passport");
+    assert_eq!(todo("Renew passport")["due_date"], "2031-03-01T00:00:00.000Z");
+    assert_eq!(todo("Book flights")["completed"], true);
+    assert_eq!(todo("Milk")["completed"], false);
+    assert_eq!(todo("Olive oil")["description"], Value::Null);
+    assert!(todos.iter().all(|todo| todo["title"] != "Cancel gym membership"));
+
+    // Importar el mismo fichero otra vez no duplica nada
+    let (status, body) = server.json(import()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["data"]["created"], json!([]));
+    assert_eq!(body["data"]["skipped"], 5);
+    assert_eq!(body["data"]["lists"][1], json!({ "title": "Groceries", "created": 0, "skipped": 2, "errors": 0 }));
+
+    server.stop().await;
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::Server;
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[tokio::test]
+async fn serves_liveness_but_not_readiness_without_a_database() {
+    // Nada escucha en el puerto 1: la conexión se rechaza enseguida
+    let server = Server::spawn(&[
+        ("DB_CONNECT_LAZY", "true"),
+        ("DATABASE_URL", "postgres://postgres@127.0.0.1:1/postgres"),
+    ])
+    .await;
+    server.wait_for("/api/v1/health").await;
+
+    let (status, body) = server.json(server.get("/api/v1/health/ready")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"], "Database migrations have not run yet");
+
+    // Sigue vivo mientras reintenta las migraciones en segundo plano
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    let (status, _) = server.json(server.get("/api/v1/health")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = server.json(server.get("/api/v1/health/ready")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn becomes_ready_once_the_deferred_migrations_run() {
+    let server = Server::start(&[("DB_CONNECT_LAZY", "true")]).await;
+
+    let (status, _) = server.json(server.get("/api/v1/todos")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    server.stop().await;
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::Server;
+use std::net::TcpListener;
+
+#[tokio::test]
+async fn exits_with_ex_config_on_an_invalid_setting() {
+    let mut server = Server::spawn(&[("SERVER_PORT", "eighty")]).await;
+
+    let (code, output) = server.wait_for_exit().await;
+
+    assert_eq!(code, Some(78), "{}", output);
+    assert!(output.contains("Error: invalid configuration"), "{}", output);
+    assert!(output.contains("Caused by: SERVER_PORT must be a valid number"), "{}", output);
+    // Sin backtrace ni mensaje de pánico
+    assert!(!output.contains("panicked"), "{}", output);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn exits_with_ex_config_without_a_database_url() {
+    let mut server = Server::spawn(&[("DATABASE_URL", "")]).await;
+
+    let (code, output) = server.wait_for_exit().await;
+
+    assert_eq!(code, Some(78), "{}", output);
+    assert!(output.contains("DATABASE_URL, or DB_HOST, DB_NAME and DB_USER, must be set"), "{}", output);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn warns_about_db_variables_ignored_for_database_url() {
+    let server = Server::start(&[("DB_HOST", "elsewhere"), ("DB_PASSWORD", "s3cret")]).await;
+
+    let output = std::fs::read_to_string(&server.log).unwrap();
+    assert!(output.contains("DATABASE_URL is set, ignoring DB_HOST, DB_PASSWORD"), "{}", output);
+    assert!(!output.contains("s3cret"), "{}", output);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn exits_with_ex_unavailable_when_the_database_cannot_be_used() {
+    // Un servidor que rechaza la conexión hace que sqlx reintente hasta su
+    // timeout; una base de datos inexistente falla en el acto
+    let url = std::env::var("DATABASE_URL").unwrap();
+    let (server_url, _) = url.split('?').next().unwrap().rsplit_once('/').unwrap();
+    let missing = format!("{}/ha_todo_missing_database", server_url);
+    let mut server = Server::spawn(&[("DATABASE_URL", &missing)]).await;
+
+    let (code, output) = server.wait_for_exit().await;
+
+    assert_eq!(code, Some(69), "{}", output);
+    assert!(output.contains("Error: could not connect to PostgreSQL"), "{}", output);
+    assert!(output.contains("Caused by:"), "{}", output);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn exits_with_ex_oserr_when_the_port_is_taken() {
+    let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = taken.local_addr().unwrap().port().to_string();
+    let mut server = Server::spawn(&[("SERVER_PORT", &port)]).await;
+
+    let (code, output) = server.wait_for_exit().await;
+
+    assert_eq!(code, Some(71), "{}", output);
+    assert!(output.contains(&format!("Error: could not listen on 127.0.0.1:{}", port)), "{}", output);
+    assert!(output.contains("Caused by:"), "{}", output);
+    drop(taken);
+    server.stop().await;
+}